@@ -0,0 +1,166 @@
+use crate::linear_algebra::Vec3;
+use crate::quaternion::Quaternion;
+use crate::math_helpers;
+
+// Values which can be interpolated between keyframes
+pub trait Interpolate: Copy {
+    // Returns the value t of the way from a to b, t is in [0, 1]
+    fn interpolate(a: &Self, b: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for Vec3<f32> {
+    fn interpolate(a: &Self, b: &Self, t: f32) -> Self {
+        math_helpers::lerp(a, b, t)
+    }
+}
+
+impl Interpolate for Quaternion {
+    fn interpolate(a: &Self, b: &Self, t: f32) -> Self {
+        Quaternion::slerp(a, b, t)
+    }
+}
+
+// A sequence of values over time which can be sampled at any time
+// Keyframes are kept sorted by time
+#[derive(Debug, Clone)]
+pub struct KeyframeTrack<T: Interpolate> {
+    keyframes: Vec<(f32, T)>,
+}
+
+impl<T: Interpolate> Default for KeyframeTrack<T> {
+    fn default() -> Self {
+        KeyframeTrack::new()
+    }
+}
+
+impl<T: Interpolate> KeyframeTrack<T> {
+    pub fn new() -> Self {
+        KeyframeTrack { keyframes: Vec::new() }
+    }
+
+    // Adds a keyframe to the track, keyframes added at the same time as an existing keyframe are placed after it
+    pub fn push_keyframe(&mut self, time: f32, value: T) {
+        let index = self.keyframes.partition_point(|(keyframe_time, _)| *keyframe_time <= time);
+        self.keyframes.insert(index, (time, value));
+    }
+
+    // Returns the value of the track at time
+    // Times outside of the track are clamped to the first or last keyframe, a NaN time returns the first keyframe
+    // Panics if the track has no keyframes
+    pub fn sample(&self, time: f32) -> T {
+        let (first_time, first_value) = self.keyframes.first().expect("Cannot sample a track without keyframes");
+        let (last_time, last_value) = self.keyframes[self.keyframes.len() - 1];
+
+        if time.is_nan() || time <= *first_time {
+            return *first_value;
+        }
+
+        if time >= last_time {
+            return last_value;
+        }
+
+        // Index of the first keyframe after time, this is never the first keyframe because of the clamp above
+        let next = self.keyframes.partition_point(|(keyframe_time, _)| *keyframe_time <= time);
+        let (time0, value0) = &self.keyframes[next - 1];
+        let (time1, value1) = &self.keyframes[next];
+
+        if time == *time0 {
+            return *value0;
+        }
+
+        T::interpolate(value0, value1, (time - time0) / (time1 - time0))
+    }
+
+    // Returns the time between the first and last keyframes
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some((first_time, _)), Some((last_time, _))) => last_time - first_time,
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3_track() -> KeyframeTrack<Vec3<f32>> {
+        let mut track = KeyframeTrack::new();
+
+        // Pushed out of order to check the track stays sorted
+        track.push_keyframe(2.0, Vec3::new(4.0, 2.0, 0.0));
+        track.push_keyframe(0.0, Vec3::new(0.0, 0.0, 0.0));
+        track.push_keyframe(1.0, Vec3::new(2.0, 0.0, 0.0));
+
+        track
+    }
+
+    #[test]
+    fn test_sample_exact_keyframe() {
+        let track = vec3_track();
+
+        assert_eq!(track.sample(0.0), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(track.sample(1.0), Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(track.sample(2.0), Vec3::new(4.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_sample_between_keyframes() {
+        let track = vec3_track();
+
+        assert_eq!(track.sample(0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(track.sample(1.5), Vec3::new(3.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_sample_outside_range() {
+        let track = vec3_track();
+
+        assert_eq!(track.sample(-1.0), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(track.sample(5.0), Vec3::new(4.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_duration() {
+        let track = vec3_track();
+
+        assert_eq!(track.duration(), 2.0);
+        assert_eq!(KeyframeTrack::<Vec3<f32>>::new().duration(), 0.0);
+    }
+
+    #[test]
+    fn test_quaternion_track() {
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        let start = Quaternion::identity();
+        let end = Quaternion::new(0.0, 0.0, half_sqrt2, half_sqrt2);
+
+        let mut track = KeyframeTrack::new();
+        track.push_keyframe(0.0, start);
+        track.push_keyframe(1.0, end);
+
+        assert_eq!(track.sample(0.0), start);
+        assert_eq!(track.sample(1.0), end);
+        assert_eq!(track.sample(3.0), end);
+
+        // Halfway between no rotation and 90 degrees about z is 45 degrees about z
+        let halfway = track.sample(0.5);
+        let (sin, cos) = (std::f32::consts::FRAC_PI_8.sin(), std::f32::consts::FRAC_PI_8.cos());
+        for (actual, expected) in [(halfway.x, 0.0), (halfway.y, 0.0), (halfway.z, sin), (halfway.w, cos)] {
+            assert!((actual - expected).abs() < 1e-5);
+        }
+
+        let rotated = halfway.rotate_vec3(&Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated.x - half_sqrt2).abs() < 1e-5);
+        assert!((rotated.y - half_sqrt2).abs() < 1e-5);
+        assert!(rotated.z.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_sample_non_finite_time() {
+        let track = vec3_track();
+
+        assert_eq!(track.sample(f32::NAN), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(track.sample(f32::NEG_INFINITY), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(track.sample(f32::INFINITY), Vec3::new(4.0, 2.0, 0.0));
+    }
+}
@@ -1,4 +1,8 @@
+use crate::colour::Colour;
+use crate::frame_buffer::{FrameBuffer, FrameBufferTrait};
 use crate::linear_algebra::*;
+use crate::mesh::{BoundingBox3D, Mesh};
+use crate::rasterisation::{Triangle, VertexAttribute};
 
 // Camera points in the negative z direction 
 // https://www.scratchapixel.com/images/cameras/canvascoordinates4.png?
@@ -37,6 +41,54 @@ pub enum FitResolutionGate {
     Overscan, // Fit film gate within resolution gate (grow film to match canvas)
 }
 
+// Colour of the lines drawn by Camera::project_world_grid
+pub const WORLD_GRID_COLOUR: Colour = Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 1.0};
+
+// Raster coordinates of an origin and the end points of its x, y, and z axes respectively
+pub type ProjectedAxes = (Vec2<i32>, Vec2<i32>, Vec2<i32>, Vec2<i32>);
+
+// Plane defined by normal . p + distance = 0
+// Points on the side the normal faces have a positive signed distance
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Plane {
+    pub normal: Vec3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vec3<f32>, distance: f32) -> Self {
+        Plane {normal, distance}
+    }
+
+    // Returns the signed distance from the plane to a point, scaled by the length of the normal
+    pub fn signed_distance(&self, point: &Vec3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+// Half line starting at origin and travelling along direction
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3<f32>,
+    pub direction: Vec3<f32>, // Unit length
+}
+
+impl Ray {
+    pub fn new(origin: Vec3<f32>, direction: Vec3<f32>) -> Self {
+        Ray {origin, direction}
+    }
+
+    // Returns the point at distance t along the ray
+    pub fn at(&self, t: f32) -> Vec3<f32> {
+        Vec3::new(
+            self.origin.x + self.direction.x * t,
+            self.origin.y + self.direction.y * t,
+            self.origin.z + self.direction.z * t,
+        )
+    }
+}
+
+#[derive(Debug)]
 pub enum ProjectionError {
     PointCLipped,
     PointOutsideCanvas,
@@ -61,33 +113,7 @@ impl Camera {
         let film_gate_aspect_ratio = camera_aperture.x / camera_aperture.y;
         let resolution_gate_aspect_ratio = image_size.x as f32 / image_size.y as f32;
 
-        // Determine canvas x and y scale factors depending on fit mode
-        // I still don't understand this part that well I pretty much yoinked it from here https://www.scratchapixel.com/lessons/3d-basic-rendering/3d-viewing-pinhole-camera/implementing-virtual-pinhole-camera.html
-        let (scale_x, scale_y) = match fit_resolution_gate {
-            FitResolutionGate::Fill => {
-                if film_gate_aspect_ratio > resolution_gate_aspect_ratio {
-                    (resolution_gate_aspect_ratio / film_gate_aspect_ratio, 1.0)
-                } else {
-                    (1.0, film_gate_aspect_ratio / resolution_gate_aspect_ratio)
-                }
-            },
-            FitResolutionGate::Overscan => {
-                if film_gate_aspect_ratio > resolution_gate_aspect_ratio {
-                    (1.0, film_gate_aspect_ratio / resolution_gate_aspect_ratio)
-                } else {
-                    (resolution_gate_aspect_ratio / film_gate_aspect_ratio, 1.0)
-                }
-            },
-        };
-
-        // Calculate canvas size
-        let canvas_height = (camera_aperture.y / 2.0 / focal_length) * z_near; // Using similiar triangles 
-        let canvas_size = Vec2::new(canvas_height * film_gate_aspect_ratio * scale_x, canvas_height * scale_y);
-
-        // Calculate screen window
-        let bottom_left = Vec2::new(canvas_size.x / -2.0, canvas_size.y / -2.0);
-        let top_right = Vec2::new(-bottom_left.x, -bottom_left.y);
-        let screen_window = (bottom_left, top_right);
+        let (canvas_size, screen_window) = fit_canvas(&fit_resolution_gate, camera_aperture, focal_length, z_near, film_gate_aspect_ratio, resolution_gate_aspect_ratio);
 
         Camera {
             transformation_matrix,
@@ -106,13 +132,49 @@ impl Camera {
         }
     }
 
+    // Changes how the film gate is fitted to the image, only the canvas is recalculated
+    pub fn set_fit_mode(&mut self, mode: FitResolutionGate) {
+        let (canvas_size, screen_window) = fit_canvas(&mode, self.camera_aperture, self.focal_length, self.z_near, self.film_gate_aspect_ratio, self.resolution_gate_aspect_ratio);
+
+        self.fit_resolution_gate = mode;
+        self.canvas_size = canvas_size;
+        self.screen_window = screen_window;
+    }
+
+    // Accessors for values calculated when the camera is made
+    // Angles are in radians
+
+    pub fn film_gate_aspect_ratio(&self) -> f32 {
+        self.film_gate_aspect_ratio
+    }
+
+    pub fn resolution_gate_aspect_ratio(&self) -> f32 {
+        self.resolution_gate_aspect_ratio
+    }
+
+    pub fn horizontal_angle_of_view(&self) -> f32 {
+        self.horizontal_angle_of_view
+    }
+
+    pub fn vertical_angle_of_view(&self) -> f32 {
+        self.vertical_angle_of_view
+    }
+
+    pub fn canvas_size(&self) -> &Vec2<f32> {
+        &self.canvas_size
+    }
+
+    pub fn screen_window(&self) -> &(Vec2<f32>, Vec2<f32>) {
+        &self.screen_window
+    }
+
     // Converts a point from world space to screen space
     pub fn point_to_screen(&self, world_point: &Vec3<f32>) -> Result<Vec3<f32>, ProjectionError> {
 
         // Convert point from world to camera coordinates
         let camera_point = world_point.homogeneous_mult_matrix(&self.transformation_matrix);
 
-        if camera_point.z < self.z_near || camera_point.z > self.z_far {
+        if !(self.z_near..=self.z_far).contains(&camera_point.z) {
             return Err(ProjectionError::PointCLipped);
         }
 
@@ -132,7 +194,7 @@ impl Camera {
         let ndc_y = screen_point.y / self.canvas_size.y + 0.5;
 
         // Check point is inside the canvas
-        if ndc_x > 1.0 || ndc_x < 0.0 || ndc_y > 1.0 || ndc_y < 0.0 {
+        if !(0.0..=1.0).contains(&ndc_x) || !(0.0..=1.0).contains(&ndc_y) {
             return Err(ProjectionError::PointOutsideCanvas);
         }
 
@@ -145,9 +207,375 @@ impl Camera {
         Ok(raster_coordinates)
     }
 
+    // Converts a point from camera space to continuous raster coordinates
+    // Unlike screen_to_raster the point may be outside of the canvas, so the result can be outside of the image
+    pub fn camera_to_raster(&self, camera_point: &Vec3<f32>) -> Result<Vec2<f32>, ProjectionError> {
+        if !(self.z_near..=self.z_far).contains(&camera_point.z) {
+            return Err(ProjectionError::PointCLipped);
+        }
+
+        // Same projection as point_to_screen and screen_to_raster
+        let screen_x = camera_point.x / -camera_point.z * self.z_near;
+        let screen_y = camera_point.y / camera_point.z * self.z_near;
+
+        Ok(Vec2::new(
+            (screen_x / self.canvas_size.x + 0.5) * self.image_size.x as f32,
+            (screen_y / self.canvas_size.y + 0.5) * self.image_size.y as f32,
+        ))
+    }
+
     // Converts a point from world space to raster space
     pub fn point_to_raster(&self, world_point: &Vec3<f32>) -> Result<Vec2<i32>, ProjectionError> {
         let screen_point = self.point_to_screen(world_point)?;
         self.screen_to_raster(&screen_point)
     }
+
+    // Projects an objects origin and the end points of its x, y, and z axes into raster space
+    // The matrix transforms from object to world space, length is the length of each axis in object space
+    // The result can be drawn with FrameBuffer::draw_debug_axes
+    pub fn project_axes(&self, matrix: &Matrix44, length: f32) -> Result<ProjectedAxes, ProjectionError> {
+        let project = |point: Vec3<f32>| self.point_to_raster(&point.homogeneous_mult_matrix(matrix));
+
+        Ok((
+            project(Vec3::new(0.0, 0.0, 0.0))?,
+            project(Vec3::new(length, 0.0, 0.0))?,
+            project(Vec3::new(0.0, length, 0.0))?,
+            project(Vec3::new(0.0, 0.0, length))?,
+        ))
+    }
+
+    // Returns the ray from the eye through the centre of a pixel
+    // The ray is in camera space, where the eye is at the origin and the forward vector is (0, 0, 1)
+    // Projecting any point on the ray with point_to_raster returns the same pixel
+    pub fn generate_ray(&self, pixel: &Vec2<i32>) -> Ray {
+        // Inverse of screen_to_raster using the pixel centre
+        let ndc_x = (pixel.x as f32 + 0.5) / self.image_size.x as f32;
+        let ndc_y = (pixel.y as f32 + 0.5) / self.image_size.y as f32;
+        let screen_x = (ndc_x - 0.5) * self.canvas_size.x;
+        let screen_y = (ndc_y - 0.5) * self.canvas_size.y;
+
+        // Inverse of the z divide in point_to_screen with the canvas at z_near
+        let mut direction = Vec3::new(-screen_x, screen_y, self.z_near);
+        direction.normalise();
+
+        Ray::new(Vec3::splat(0.0), direction)
+    }
+
+    // Returns a ray for every pixel in row y of the image, ordered from left to right
+    pub fn unproject_row(&self, y: usize) -> Vec<(Vec2<i32>, Ray)> {
+        (0..self.image_size.x)
+            .map(|x| {
+                let pixel = Vec2::new(x, y as i32);
+                (pixel, self.generate_ray(&pixel))
+            })
+            .collect()
+    }
+
+    // Returns a ray for every pixel in the image
+    // Rays are in scan order, starting from the top left of the image and moving along each row
+    pub fn unproject_image(&self) -> Vec<(Vec2<i32>, Ray)> {
+        (0..self.image_size.y.max(0) as usize)
+            .rev()
+            .flat_map(|y| self.unproject_row(y))
+            .collect()
+    }
+
+    // Draws a grid of lines spacing world units apart on the plane y = y_level
+    // The grid is centred on the world origin and extends z_far units in the x and z directions
+    // Parts of lines in front of the near plane or behind the far plane are clipped
+    pub fn project_world_grid<T: FrameBufferTrait>(spacing: f32, y_level: f32, camera: &Camera, frame_buffer: &mut FrameBuffer<T>) {
+        if spacing <= 0.0 {
+            return;
+        }
+
+        let extent = camera.z_far;
+        let lines = (extent / spacing).floor() as i32;
+        for i in -lines..=lines {
+            let offset = i as f32 * spacing;
+            let segments = [
+                (Vec3::new(offset, y_level, -extent), Vec3::new(offset, y_level, extent)),
+                (Vec3::new(-extent, y_level, offset), Vec3::new(extent, y_level, offset)),
+            ];
+
+            for (start, end) in segments {
+                let start = start.homogeneous_mult_matrix(&camera.transformation_matrix);
+                let end = end.homogeneous_mult_matrix(&camera.transformation_matrix);
+                let Some((start, end)) = camera.clip_segment(start, end) else {
+                    continue;
+                };
+
+                if let (Ok(start), Ok(end)) = (camera.camera_to_raster(&start), camera.camera_to_raster(&end)) {
+                    frame_buffer.draw_line(start.x.floor() as i32, start.y.floor() as i32, end.x.floor() as i32, end.y.floor() as i32, WORLD_GRID_COLOUR.to_colour8());
+                }
+            }
+        }
+    }
+
+    // Clips a camera space line segment to the near and far planes
+    // Returns None if the whole segment is clipped
+    fn clip_segment(&self, mut start: Vec3<f32>, mut end: Vec3<f32>) -> Option<(Vec3<f32>, Vec3<f32>)> {
+        for (plane_z, keep_greater) in [(self.z_near, true), (self.z_far, false)] {
+            let inside = |point: &Vec3<f32>| if keep_greater {point.z >= plane_z} else {point.z <= plane_z};
+
+            match (inside(&start), inside(&end)) {
+                (true, true) => {},
+                (false, false) => return None,
+                (start_inside, _) => {
+                    let t = (plane_z - start.z) / (end.z - start.z);
+                    let intersection = Vec3::lerp_unclamped(&start, &end, t);
+                    if start_inside {
+                        end = intersection;
+                    } else {
+                        start = intersection;
+                    }
+                },
+            }
+        }
+
+        Some((start, end))
+    }
+
+    // Returns the near, far, left, right, bottom and top planes of the viewing frustum in camera space
+    // The normals face into the frustum so visible points have a positive signed distance to every plane
+    pub fn frustum_planes(&self) -> [Plane; 6] {
+        // Slopes of the side planes, found using similar triangles with the canvas at z_near
+        let slope_x = self.canvas_size.x / 2.0 / self.z_near;
+        let slope_y = self.canvas_size.y / 2.0 / self.z_near;
+
+        [
+            Plane::new(Vec3::new(0.0, 0.0, 1.0), -self.z_near),
+            Plane::new(Vec3::new(0.0, 0.0, -1.0), self.z_far),
+            Plane::new(Vec3::new(1.0, 0.0, slope_x), 0.0),
+            Plane::new(Vec3::new(-1.0, 0.0, slope_x), 0.0),
+            Plane::new(Vec3::new(0.0, 1.0, slope_y), 0.0),
+            Plane::new(Vec3::new(0.0, -1.0, slope_y), 0.0),
+        ]
+    }
+
+    // Returns the triangles of a mesh which may be visible to the camera
+    // A triangle is culled if every corner of its axis aligned bounding box is outside one of the frustum planes
+    // The test is conservative, some returned triangles may still be entirely outside the frustum
+    pub fn frustum_cull_mesh<'a, A: VertexAttribute>(&self, mesh: &'a Mesh<A>) -> Vec<&'a Triangle<f32, A>> {
+        let planes = self.frustum_planes();
+
+        mesh.triangles.iter()
+            .filter(|triangle| {
+                let corners = BoundingBox3D::from_triangles(std::slice::from_ref(*triangle)).corners().map(|corner| corner.homogeneous_mult_matrix(&self.transformation_matrix));
+
+                !planes.iter().any(|plane| corners.iter().all(|corner| plane.signed_distance(corner) < 0.0))
+            })
+            .collect()
+    }
+}
+
+// Returns the canvas size and screen window for the fit mode
+fn fit_canvas(
+    fit_resolution_gate: &FitResolutionGate,
+    camera_aperture: Vec2<f32>,
+    focal_length: f32,
+    z_near: f32,
+    film_gate_aspect_ratio: f32,
+    resolution_gate_aspect_ratio: f32,
+) -> (Vec2<f32>, (Vec2<f32>, Vec2<f32>)) {
+    // Determine canvas x and y scale factors depending on fit mode
+    // I still don't understand this part that well I pretty much yoinked it from here https://www.scratchapixel.com/lessons/3d-basic-rendering/3d-viewing-pinhole-camera/implementing-virtual-pinhole-camera.html
+    let (scale_x, scale_y) = match fit_resolution_gate {
+        FitResolutionGate::Fill => {
+            if film_gate_aspect_ratio > resolution_gate_aspect_ratio {
+                (resolution_gate_aspect_ratio / film_gate_aspect_ratio, 1.0)
+            } else {
+                (1.0, film_gate_aspect_ratio / resolution_gate_aspect_ratio)
+            }
+        },
+        FitResolutionGate::Overscan => {
+            if film_gate_aspect_ratio > resolution_gate_aspect_ratio {
+                (1.0, film_gate_aspect_ratio / resolution_gate_aspect_ratio)
+            } else {
+                (resolution_gate_aspect_ratio / film_gate_aspect_ratio, 1.0)
+            }
+        },
+    };
+
+    // Calculate canvas size
+    let canvas_height = (camera_aperture.y / 2.0 / focal_length) * z_near; // Using similiar triangles 
+    let canvas_size = Vec2::new(canvas_height * film_gate_aspect_ratio * scale_x, canvas_height * scale_y);
+
+    // Calculate screen window
+    let bottom_left = canvas_size / -2.0;
+    let top_right = -bottom_left;
+    let screen_window = (bottom_left, top_right);
+
+    (canvas_size, screen_window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::WHITE;
+    use crate::rasterisation::{ColourAttribute, Vertex};
+
+    fn camera() -> Camera {
+        Camera::new(Matrix44::identity(), Vec2::new(100, 100), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill)
+    }
+
+    #[test]
+    fn test_accessors() {
+        let camera = camera();
+
+        assert_eq!(camera.film_gate_aspect_ratio(), 1.0);
+        assert_eq!(camera.resolution_gate_aspect_ratio(), 1.0);
+        assert_eq!(camera.horizontal_angle_of_view(), camera.vertical_angle_of_view());
+        assert!((camera.horizontal_angle_of_view() - 2.0 * (12.0f32 / 35.0).atan()).abs() < 1e-6);
+        assert_eq!(camera.canvas_size().x, camera.canvas_size().y);
+    }
+
+    #[test]
+    fn test_set_fit_mode() {
+        // Image is wider than the film so the modes fit it differently
+        let mut camera = Camera::new(Matrix44::identity(), Vec2::new(200, 100), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill);
+        let point = Vec3::new(0.2, 0.1, 10.0);
+
+        let fill_canvas = camera.canvas_size;
+        let fill_raster = camera.point_to_raster(&point).unwrap();
+
+        camera.set_fit_mode(FitResolutionGate::Overscan);
+        let overscan_raster = camera.point_to_raster(&point).unwrap();
+
+        // Overscan shows more of the scene so the canvas grows and points move towards the centre of the image
+        assert!(camera.canvas_size.x > fill_canvas.x && camera.canvas_size.y > fill_canvas.y);
+        assert!((overscan_raster.x - 100).abs() < (fill_raster.x - 100).abs());
+        assert!((overscan_raster.y - 50).abs() < (fill_raster.y - 50).abs());
+
+        // Switching back matches a camera made with the mode
+        camera.set_fit_mode(FitResolutionGate::Fill);
+        assert_eq!(camera.canvas_size, fill_canvas);
+        assert_eq!(camera.point_to_raster(&point).unwrap(), fill_raster);
+    }
+
+    #[test]
+    fn test_project_axes() {
+        let camera = camera();
+
+        // Object placed in front of the camera
+        let object_to_world = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 10.0, 1.0],
+        ]);
+
+        let (origin, x_axis, y_axis, z_axis) = camera.project_axes(&object_to_world, 1.0).unwrap();
+
+        assert_eq!(origin, Vec2::new(50, 50));
+        assert_eq!(x_axis.y, origin.y);
+        assert_ne!(x_axis.x, origin.x);
+        assert_eq!(y_axis.x, origin.x);
+        assert!(y_axis.y > origin.y);
+
+        // The z axis points directly away from the camera so it projects onto the origin
+        assert_eq!(z_axis, origin);
+    }
+
+    // Camera moved 10 units back from the origin so the origin is in view
+    fn camera_looking_at_origin() -> Camera {
+        let world_to_camera = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 10.0, 1.0],
+        ]);
+
+        Camera::new(world_to_camera, Vec2::new(100, 100), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill)
+    }
+
+    fn mesh(z: f32) -> Mesh {
+        let attributes = ColourAttribute { colour: WHITE };
+        let triangle = |x: f32| Triangle::new(
+            Vertex::new(Vec3::new(x, 0.0, z), attributes),
+            Vertex::new(Vec3::new(x + 1.0, 0.0, z), attributes),
+            Vertex::new(Vec3::new(x, 1.0, z), attributes),
+        );
+
+        Mesh::new(vec![triangle(-1.0), triangle(0.0), triangle(0.5)])
+    }
+
+    #[test]
+    fn test_frustum_cull_mesh_at_origin() {
+        let camera = camera_looking_at_origin();
+        let mesh = mesh(0.0);
+
+        assert_eq!(camera.frustum_cull_mesh(&mesh).len(), mesh.triangles.len());
+    }
+
+    #[test]
+    fn test_frustum_cull_mesh_behind_camera() {
+        let camera = camera_looking_at_origin();
+        let mesh = mesh(-20.0);
+
+        assert!(camera.frustum_cull_mesh(&mesh).is_empty());
+    }
+
+    #[test]
+    fn test_frustum_cull_mesh_outside_side_plane() {
+        let camera = camera_looking_at_origin();
+
+        // Far off to the side of the camera
+        let mut mesh = mesh(0.0);
+        for triangle in mesh.triangles.iter_mut() {
+            for vertex in triangle.vertices_mut() {
+                vertex.vertex.x += 100.0;
+            }
+        }
+
+        assert!(camera.frustum_cull_mesh(&mesh).is_empty());
+    }
+
+    #[test]
+    fn test_unproject_image_top_left() {
+        let camera = camera();
+        let rays = camera.unproject_image();
+        assert_eq!(rays.len(), 100 * 100);
+
+        // Raster y increases up the image so the top left pixel is first in scan order
+        let (pixel, ray) = rays[0];
+        assert_eq!(pixel, Vec2::new(0, 99));
+
+        // Points up and to the side of the forward vector which projects to the left edge of the image
+        assert!(ray.direction.z > 0.0);
+        assert!(ray.direction.y > 0.0);
+        assert_eq!(camera.point_to_raster(&ray.at(10.0)).unwrap(), pixel);
+
+        // The centre of the image is straight ahead
+        let (_, centre) = camera.unproject_row(50)[50];
+        assert!(ray.direction.dot(&centre.direction) < 1.0);
+        assert!(centre.direction.z > 0.99);
+    }
+
+    #[test]
+    fn test_unproject_row() {
+        let camera = camera();
+        let row = camera.unproject_row(20);
+
+        assert_eq!(row.len(), 100);
+        for (pixel, ray) in row {
+            assert_eq!(pixel.y, 20);
+            assert_eq!(camera.point_to_raster(&ray.at(5.0)).unwrap(), pixel);
+        }
+    }
+
+    #[test]
+    fn test_project_world_grid() {
+        let camera = camera();
+        let mut frame_buffer = FrameBuffer::new(100, 100, vec![0u32; 100 * 100]);
+
+        // The ground plane is below the camera so the grid fills the bottom of the image
+        Camera::project_world_grid(1.0, -1.0, &camera, &mut frame_buffer);
+
+        let drawn = |y_range: std::ops::Range<usize>| y_range
+            .flat_map(|y| (0..100).map(move |x| (x, y)))
+            .filter(|(x, y)| frame_buffer.read_buf(*x, *y).unwrap().alpha > 0.0)
+            .count();
+        assert!(drawn(0..50) > 0);
+        assert_eq!(drawn(50..100), 0);
+    }
 }
@@ -1,4 +1,8 @@
-#[derive(Clone, Copy)]
+use crate::num::Num;
+use crate::linear_algebra::{Vec2, Vec3};
+
+// Defaults to BLANK
+#[derive(Clone, Copy, Default)]
 pub struct Colour {
     pub red: f32,
     pub green: f32,
@@ -20,6 +24,280 @@ impl Colour {
         }
     }
 
+    pub fn to_colour8(&self) -> Colour8 {
+        let bytes = self.to_bytes();
+        Colour8 {
+            red: bytes[0],
+            green: bytes[1],
+            blue: bytes[2],
+            alpha: bytes[3],
+        }
+    }
+
+    // Makes a colour from [red, green, blue, alpha] bytes, this is the inverse of to_bytes
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        Colour {
+            red: byte_to_normalised(bytes[0]),
+            green: byte_to_normalised(bytes[1]),
+            blue: byte_to_normalised(bytes[2]),
+            alpha: byte_to_normalised(bytes[3]),
+        }
+    }
+
+    // Parses "#RRGGBB" or "#RRGGBBAA" hex strings, the leading # is optional and either case is accepted
+    // Alpha defaults to opaque when it isn't given
+    pub fn from_hex(hex: &str) -> Result<Colour, ColourParseError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ColourParseError::InvalidHex);
+        }
+        if digits.len() != 6 && digits.len() != 8 {
+            return Err(ColourParseError::InvalidLength);
+        }
+
+        let mut bytes = [255; 4];
+        for (i, byte) in bytes.iter_mut().enumerate().take(digits.len() / 2) {
+            *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| ColourParseError::InvalidHex)?;
+        }
+
+        Ok(Colour::from_bytes(bytes))
+    }
+
+    // Returns the colour as an uppercase "#RRGGBBAA" string, channels are clamped to [0, 1] like to_bytes
+    pub fn to_hex(&self) -> String {
+        let [red, green, blue, alpha] = self.to_bytes();
+        format!("#{:02X}{:02X}{:02X}{:02X}", red, green, blue, alpha)
+    }
+
+    // Returns true if every channel is within epsilon of the other colours channel
+    pub fn approx_eq(&self, other: &Colour, epsilon: f32) -> bool {
+        (self.red - other.red).abs() <= epsilon &&
+        (self.green - other.green).abs() <= epsilon &&
+        (self.blue - other.blue).abs() <= epsilon &&
+        (self.alpha - other.alpha).abs() <= epsilon
+    }
+
+    // Makes an opaque colour from a vector, x, y, and z become red, green, and blue
+    pub fn from_vec3(v: &Vec3<f32>) -> Self {
+        Colour {red: v.x, green: v.y, blue: v.z, alpha: 1.0}
+    }
+
+    // Returns the red, green, and blue channels as a vector, alpha is dropped
+    pub fn to_vec3(&self) -> Vec3<f32> {
+        Vec3::new(self.red, self.green, self.blue)
+    }
+
+    // Makes an opaque colour from texture coordinates, u becomes red and v becomes green
+    pub fn from_vec2(uv: &Vec2<f32>) -> Self {
+        Colour {red: uv.x, green: uv.y, blue: 0.0, alpha: 1.0}
+    }
+
+    // Returns the red and green channels as texture coordinates
+    pub fn to_vec2(&self) -> Vec2<f32> {
+        Vec2::new(self.red, self.green)
+    }
+
+    // Returns the Euclidean distance between the colours in RGB space, alpha is ignored
+    pub fn distance(&self, other: &Colour) -> f32 {
+        self.distance_sq(other).sqrt()
+    }
+
+    // Returns the squared distance between the colours in RGB space, cheaper than distance when only comparing
+    pub fn distance_sq(&self, other: &Colour) -> f32 {
+        (self.red - other.red).powi(2) + (self.green - other.green).powi(2) + (self.blue - other.blue).powi(2)
+    }
+
+    // Returns the largest of the red, green, and blue channels
+    pub fn max_channel(&self) -> f32 {
+        self.red.max(self.green).max(self.blue)
+    }
+
+    // Returns true if no channel is NaN or infinite
+    pub fn is_finite(&self) -> bool {
+        self.red.is_finite() && self.green.is_finite() && self.blue.is_finite() && self.alpha.is_finite()
+    }
+
+    // Linearly interpolates every channel including alpha, t is not clamped
+    // Weighting both ends means t = 0 and t = 1 return a and b exactly
+    pub const fn lerp(a: Colour, b: Colour, t: f32) -> Colour {
+        let s = 1.0 - t;
+        Colour {
+            red: a.red * s + b.red * t,
+            green: a.green * s + b.green * t,
+            blue: a.blue * s + b.blue * t,
+            alpha: a.alpha * s + b.alpha * t,
+        }
+    }
+
+    // Restricts every channel including alpha to [0, 1], e.g. to bring a high dynamic range colour into display range
+    pub const fn clamp(&self) -> Colour {
+        Colour {
+            red: self.red.clamp(0.0, 1.0),
+            green: self.green.clamp(0.0, 1.0),
+            blue: self.blue.clamp(0.0, 1.0),
+            alpha: self.alpha.clamp(0.0, 1.0),
+        }
+    }
+
+    // Same as clamp, the name used by shading languages
+    pub const fn saturate(&self) -> Colour {
+        self.clamp()
+    }
+
+    // Makes an opaque colour from hue in degrees, saturation in [0, 1] and value in [0, 1]
+    // Hue wraps around so 360 and -360 are the same as 0
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Colour {
+        let chroma = v * s;
+        let sector = h.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+        // The hue circle is split into six sectors, in each one channel is at its maximum and another is changing
+        let (red, green, blue) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        let m = v - chroma;
+        Colour {red: red + m, green: green + m, blue: blue + m, alpha: 1.0}
+    }
+
+    // Returns (hue, saturation, value), the inverse of from_hsv with hue in [0, 360)
+    // Greys have no hue so it is 0, black also has no saturation so it is 0, alpha is ignored
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let max = self.max_channel();
+        let min = self.red.min(self.green).min(self.blue);
+        let delta = max - min;
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == self.red {
+            60.0 * ((self.green - self.blue) / delta).rem_euclid(6.0)
+        } else if max == self.green {
+            60.0 * ((self.blue - self.red) / delta + 2.0)
+        } else {
+            60.0 * ((self.red - self.green) / delta + 4.0)
+        };
+
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+        (hue, saturation, max)
+    }
+
+    // Applies the sRGB transfer function (IEC 61966-2-1) to red, green, and blue, alpha is unchanged
+    // Shorthand for ColourEncoding::Srgb.encode_colour
+    pub fn linear_to_srgb(&self) -> Colour {
+        ColourEncoding::Srgb.encode_colour(self)
+    }
+
+    // Inverse of linear_to_srgb
+    pub fn srgb_to_linear(&self) -> Colour {
+        ColourEncoding::Srgb.decode_colour(self)
+    }
+
+    // Tone mapping compresses high dynamic range red, green, and blue channels into [0, 1], alpha is unchanged
+    // Negative channels are treated as 0
+
+    // Reinhard operator, c / (c + 1) per channel
+    pub fn reinhard_tonemap(&self) -> Colour {
+        let map = |channel: f32| {
+            let channel = channel.max(0.0);
+            channel / (channel + 1.0)
+        };
+
+        Colour {red: map(self.red), green: map(self.green), blue: map(self.blue), alpha: self.alpha}
+    }
+
+    // Stephen Hill's 2015 fit of the ACES reference rendering and output transforms
+    // Has a filmic toe and shoulder and desaturates very bright colours, unlike Reinhard white is reached at a finite value
+    pub fn aces_filmic(&self) -> Colour {
+        // sRGB to the RRT input space, and back from the ODT output space
+        const INPUT: [[f32; 3]; 3] = [
+            [0.59719, 0.35458, 0.04823],
+            [0.07600, 0.90834, 0.01566],
+            [0.02840, 0.13383, 0.83777],
+        ];
+        const OUTPUT: [[f32; 3]; 3] = [
+            [1.60475, -0.53108, -0.07367],
+            [-0.10208, 1.10813, -0.00605],
+            [-0.00327, -0.07276, 1.07602],
+        ];
+
+        let transform = |m: &[[f32; 3]; 3], c: [f32; 3]| m.map(|row| row[0] * c[0] + row[1] * c[1] + row[2] * c[2]);
+        let fit = |v: f32| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.432951) + 0.238081);
+
+        let rgb = [self.red.max(0.0), self.green.max(0.0), self.blue.max(0.0)];
+        let [red, green, blue] = transform(&OUTPUT, transform(&INPUT, rgb).map(fit)).map(|c| c.clamp(0.0, 1.0));
+
+        Colour {red, green, blue, alpha: self.alpha}
+    }
+
+    // Returns the relative luminance using the Rec. 709 weights, alpha is ignored
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    // Blend modes combine a base colour a with a blend colour b
+    // The red, green, and blue channels are blended separately and the alpha of the base is kept
+
+    // 1 - (1 - a) * (1 - b), brightens the base
+    pub fn blend_screen(a: &Colour, b: &Colour) -> Colour {
+        a.blend_channels(b, |a, b| 1.0 - (1.0 - a) * (1.0 - b))
+    }
+
+    // a * b, darkens the base
+    pub fn blend_multiply(a: &Colour, b: &Colour) -> Colour {
+        a.blend_channels(b, |a, b| a * b)
+    }
+
+    // Multiplies where the base channel is dark and screens where it is bright, increasing contrast
+    pub fn blend_overlay(a: &Colour, b: &Colour) -> Colour {
+        a.blend_channels(b, |a, b| {
+            if a < 0.5 {
+                2.0 * a * b
+            } else {
+                1.0 - 2.0 * (1.0 - a) * (1.0 - b)
+            }
+        })
+    }
+
+    // Porter-Duff over, composites fg on top of bg
+    // Both colours must have premultiplied alpha, meaning red, green, and blue are already multiplied by alpha
+    // The result is also premultiplied
+    pub fn premultiplied_over(fg: &Colour, bg: &Colour) -> Colour {
+        *fg + bg.multiply_float(1.0 - fg.alpha)
+    }
+
+    // Porter-Duff over for straight (not premultiplied) alpha, composites src on top of dst
+    // Floating point counterpart of Colour8::blend_over, the result also has straight alpha
+    pub fn blend_over(dst: &Colour, src: &Colour) -> Colour {
+        let dst_weight = dst.alpha * (1.0 - src.alpha);
+        let alpha = src.alpha + dst_weight;
+        if alpha <= 0.0 {
+            return *dst;
+        }
+
+        let blend = |src_channel: f32, dst_channel: f32| (src_channel * src.alpha + dst_channel * dst_weight) / alpha;
+        Colour {
+            red: blend(src.red, dst.red),
+            green: blend(src.green, dst.green),
+            blue: blend(src.blue, dst.blue),
+            alpha,
+        }
+    }
+
+    fn blend_channels(&self, other: &Colour, blend: impl Fn(f32, f32) -> f32) -> Colour {
+        Colour {
+            red: blend(self.red, other.red),
+            green: blend(self.green, other.green),
+            blue: blend(self.blue, other.blue),
+            alpha: self.alpha,
+        }
+    }
+
     pub fn to_bytes(&self) -> [u8; 4] {
         [
             normalised_to_byte(self.red),
@@ -30,9 +308,235 @@ impl Colour {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ColourParseError {
+    InvalidLength, // Not 6 or 8 hex digits
+    InvalidHex, // Contains something other than hex digits after the #
+}
+
+impl std::fmt::Display for ColourParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColourParseError::InvalidLength => write!(f, "Hex colour must have 6 or 8 digits"),
+            ColourParseError::InvalidHex => write!(f, "Hex colour contains a character which isn't a hex digit"),
+        }
+    }
+}
+
+// Colour with a byte per channel, used when reading and writing pixels
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Colour8 {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Colour8 {
+    pub fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Colour8 {red, green, blue, alpha}
+    }
+
+    // Packs the colour into a u32 in ARGB format
+    // minifb ignores the alpha byte so these can be displayed directly
+    pub fn to_u32(&self) -> u32 {
+        u32::from_be_bytes([self.alpha, self.red, self.green, self.blue])
+    }
+
+    // Unpacks a colour from a u32 in ARGB format
+    pub fn from_u32(colour: u32) -> Self {
+        let bytes = colour.to_be_bytes();
+        Colour8::new(bytes[1], bytes[2], bytes[3], bytes[0])
+    }
+
+    pub fn to_colour(&self) -> Colour {
+        Colour::from_bytes([self.red, self.green, self.blue, self.alpha])
+    }
+
+    // Linearly interpolates every channel in fixed point, t = 0 returns a and t = 255 returns b
+    pub fn lerp(a: Colour8, b: Colour8, t: u8) -> Colour8 {
+        let t = t as u32;
+        let mix = |a: u8, b: u8| div_255(a as u32 * (255 - t) + b as u32 * t) as u8;
+
+        Colour8::new(mix(a.red, b.red), mix(a.green, b.green), mix(a.blue, b.blue), mix(a.alpha, b.alpha))
+    }
+
+    // Multiplies red, green, and blue by alpha / 255, alpha is unchanged
+    pub fn premultiply_alpha(&self) -> Colour8 {
+        let alpha = self.alpha as u32;
+        let scale = |channel: u8| div_255(channel as u32 * alpha) as u8;
+
+        Colour8::new(scale(self.red), scale(self.green), scale(self.blue), self.alpha)
+    }
+
+    // Porter-Duff over, composites src on top of dst without converting to floating point
+    // Both colours have straight (not premultiplied) alpha like the frame buffer, and so does the result
+    pub fn blend_over(dst: Colour8, src: Colour8) -> Colour8 {
+        match src.alpha {
+            0 => return dst,
+            255 => return src,
+            _ => (),
+        }
+
+        let src_weight = src.alpha as u32 * 255;
+        let dst_weight = dst.alpha as u32 * (255 - src.alpha as u32);
+        let total_weight = src_weight + dst_weight;
+
+        // Weighted average of the straight channels, rounded to nearest
+        let blend = |src_channel: u8, dst_channel: u8| {
+            ((src_channel as u32 * src_weight + dst_channel as u32 * dst_weight + total_weight / 2) / total_weight) as u8
+        };
+
+        Colour8::new(
+            blend(src.red, dst.red),
+            blend(src.green, dst.green),
+            blend(src.blue, dst.blue),
+            div_255(total_weight) as u8,
+        )
+    }
+}
+
+// Divides by 255 rounding to nearest, used for fixed point maths where 255 represents 1
+fn div_255(value: u32) -> u32 {
+    (value + 127) / 255
+}
+
+// Fixed set of colours used for indexed colour output
+// Panics if colours are looked up in an empty palette
+#[derive(Clone)]
+pub struct Palette(pub Vec<Colour>);
+
+impl Palette {
+    pub fn new(colours: Vec<Colour>) -> Self {
+        Palette(colours)
+    }
+
+    // Returns the index and value of the palette colour closest to colour
+    // Distance is measured in RGB space, alpha is ignored
+    pub fn nearest(&self, colour: &Colour) -> (usize, Colour) {
+        let (index, nearest) = self.0.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.distance_sq(colour).total_cmp(&b.distance_sq(colour)))
+            .expect("Cannot find the nearest colour in an empty palette");
+
+        (index, *nearest)
+    }
+
+    // Nearest colour lookup for error diffusion dithering
+    // error is the quantisation error carried from neighbouring pixels, it is added to colour before the lookup
+    // Returns the nearest palette entry and the quantisation error of this pixel to diffuse to its neighbours
+    pub fn dither_nearest(&self, colour: &Colour, error: &Colour) -> ((usize, Colour), Colour) {
+        let target = *colour + *error;
+        let (index, nearest) = self.nearest(&target);
+
+        let new_error = Colour {
+            red: target.red - nearest.red,
+            green: target.green - nearest.green,
+            blue: target.blue - nearest.blue,
+            alpha: 0.0,
+        };
+
+        ((index, nearest), new_error)
+    }
+}
+
+// Operators for compressing high dynamic range colours into the displayable [0, 1] range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapOp {
+    Clamp, // Channels above 1 are clipped
+    Reinhard, // c / (1 + c) per channel
+    AcesFilmic, // Fitted ACES curve, see Colour::aces_filmic
+}
+
+impl ToneMapOp {
+    // Applies the operator to the red, green, and blue channels, alpha is clamped
+    pub fn apply(&self, colour: &Colour) -> Colour {
+        let mapped = match self {
+            ToneMapOp::Clamp => colour.clamp(),
+            ToneMapOp::Reinhard => colour.reinhard_tonemap(),
+            ToneMapOp::AcesFilmic => colour.aces_filmic(),
+        };
+
+        Colour {alpha: colour.alpha.clamp(0.0, 1.0), ..mapped}
+    }
+}
+
+// How linear colour channels are encoded when they are stored
+// Linear stores channels as they are, Srgb applies the piecewise sRGB transfer function used by most displays
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColourEncoding {
+    #[default]
+    Linear,
+    Srgb,
+}
+
+impl ColourEncoding {
+    // Encodes a linear normalised channel
+    pub fn encode(&self, channel: f32) -> f32 {
+        match self {
+            ColourEncoding::Linear => channel,
+            ColourEncoding::Srgb => {
+                if channel <= 0.0031308 {
+                    channel * 12.92
+                } else if channel >= 1.0 {
+                    // The curve maps 1 to 1, skip it so floating point error doesn't turn 255 into 254
+                    channel
+                } else {
+                    1.055 * channel.powf(1.0 / 2.4) - 0.055
+                }
+            },
+        }
+    }
+
+    // Decodes an encoded normalised channel back to linear, this is the inverse of encode
+    pub fn decode(&self, channel: f32) -> f32 {
+        match self {
+            ColourEncoding::Linear => channel,
+            ColourEncoding::Srgb => {
+                if channel <= 0.04045 {
+                    channel / 12.92
+                } else {
+                    ((channel + 0.055) / 1.055).powf(2.4)
+                }
+            },
+        }
+    }
+
+    // Encodes the red, green, and blue channels of a colour, alpha is always linear
+    pub fn encode_colour(&self, colour: &Colour) -> Colour {
+        Colour {
+            red: self.encode(colour.red),
+            green: self.encode(colour.green),
+            blue: self.encode(colour.blue),
+            alpha: colour.alpha,
+        }
+    }
+
+    // Decodes the red, green, and blue channels of a colour, alpha is always linear
+    pub fn decode_colour(&self, colour: &Colour) -> Colour {
+        Colour {
+            red: self.decode(colour.red),
+            green: self.decode(colour.green),
+            blue: self.decode(colour.blue),
+            alpha: colour.alpha,
+        }
+    }
+}
+
 // Converts default colour normalised [0, 1] channel to byte channel [0, 255]
+// Rounds to the nearest byte so byte_to_normalised followed by this gives back the same byte
 pub fn normalised_to_byte(normalised_colour_chanel: f32) -> u8 {
-    (normalised_colour_chanel * 255.0).clamp(0.0, 255.0) as u8
+    normalised_to_byte_encoded(normalised_colour_chanel, ColourEncoding::Linear)
+}
+
+// Converts a normalised channel to a byte channel, encoding it first
+pub fn normalised_to_byte_encoded(normalised_colour_chanel: f32, encoding: ColourEncoding) -> u8 {
+    (Num::clamp(encoding.encode(normalised_colour_chanel) * 255.0, 0.0, 255.0) + 0.5) as u8
+}
+
+// Converts a linear normalised channel to an sRGB encoded byte channel
+pub fn normalised_to_byte_srgb(normalised_colour_chanel: f32) -> u8 {
+    normalised_to_byte_encoded(normalised_colour_chanel, ColourEncoding::Srgb)
 }
 
 // Converts byte colour channel [0, 255] to normalised channel [0, 1]
@@ -53,10 +557,444 @@ impl std::ops::Add for Colour {
     }
 }
 
+// Channel by channel product including alpha, e.g. to tint a texture colour
+impl std::ops::Mul for Colour {
+    type Output = Colour;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Colour {
+            red: self.red * rhs.red,
+            green: self.green * rhs.green,
+            blue: self.blue * rhs.blue,
+            alpha: self.alpha * rhs.alpha,
+        }
+    }
+}
+
+// Channel by channel quotient, the inverse of Mul so it removes a tint
+// Dividing by a zero channel follows f32 so gives infinity or NaN
+impl std::ops::Div for Colour {
+    type Output = Colour;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        Colour {
+            red: self.red / rhs.red,
+            green: self.green / rhs.green,
+            blue: self.blue / rhs.blue,
+            alpha: self.alpha / rhs.alpha,
+        }
+    }
+}
+
 // Test colours
 pub const BLANK: Colour = Colour {red: 0.0, green: 0.0, blue: 0.0, alpha: 0.0};
 pub const BLACK: Colour = Colour {red: 0.0, green: 0.0, blue: 0.0, alpha: 1.0};
 pub const WHITE: Colour = Colour {red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0};
 pub const RED: Colour = Colour {red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0};
 pub const GREEN: Colour = Colour {red: 0.0, green: 1.0, blue: 0.0, alpha: 1.0};
-pub const BLUE: Colour = Colour {red: 0.0, green: 0.0, blue: 1.0, alpha: 1.0};
\ No newline at end of file
+pub const BLUE: Colour = Colour {red: 0.0, green: 0.0, blue: 1.0, alpha: 1.0};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_COLOURS: [Colour; 6] = [BLANK, BLACK, WHITE, RED, GREEN, BLUE];
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let grey = Colour {red: 0.5, green: 0.25, blue: 0.75, alpha: 0.1};
+
+        for colour in TEST_COLOURS.iter().chain([&grey]) {
+            assert!(Colour::from_bytes(colour.to_bytes()).approx_eq(colour, 1.0 / 255.0));
+        }
+    }
+
+    #[test]
+    fn test_vec_round_trip() {
+        let v = Vec3::new(0.5, 0.25, 2.0);
+        let colour = Colour::from_vec3(&v);
+        assert_eq!(colour.to_vec3(), v);
+        assert_eq!(colour.alpha, 1.0);
+
+        let grey = Colour {red: 0.5, green: 0.25, blue: 0.75, alpha: 0.1};
+        assert!(Colour::from_vec3(&grey.to_vec3()).approx_eq(&Colour {alpha: 1.0, ..grey}, 0.0));
+
+        let uv = Vec2::new(0.3, 0.8);
+        assert_eq!(Colour::from_vec2(&uv).to_vec2(), uv);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let grey = Colour {red: 0.3, green: 0.6, blue: 0.9, alpha: 0.1};
+
+        assert!(Colour::lerp(grey, RED, 0.0).approx_eq(&grey, 0.0));
+        assert!(Colour::lerp(grey, RED, 1.0).approx_eq(&RED, 0.0));
+        assert!(Colour::lerp(RED, BLUE, 0.5).approx_eq(&Colour {red: 0.5, green: 0.0, blue: 0.5, alpha: 1.0}, 0.0));
+
+        // Alpha is interpolated too
+        assert_eq!(Colour::lerp(BLANK, BLACK, 0.25).alpha, 0.25);
+    }
+
+    #[test]
+    fn test_clamp() {
+        let over_bright = Colour {red: 4.0, green: 1.5, blue: 1.0, alpha: 2.0};
+        assert!(over_bright.clamp().approx_eq(&WHITE, 0.0));
+        assert!(over_bright.saturate().approx_eq(&WHITE, 0.0));
+
+        let negative = Colour {red: -1.0, green: 0.5, blue: -0.1, alpha: 1.0};
+        assert!(negative.clamp().approx_eq(&Colour {red: 0.0, green: 0.5, blue: 0.0, alpha: 1.0}, 0.0));
+
+        // Usable in constants
+        const MIDPOINT: Colour = Colour::lerp(BLACK, WHITE, 0.5).clamp();
+        assert_eq!(MIDPOINT.green, 0.5);
+    }
+
+    #[test]
+    fn test_mul() {
+        assert!((WHITE * RED).approx_eq(&RED, 0.0));
+
+        for colour in TEST_COLOURS {
+            assert!((BLACK * colour).approx_eq(&Colour {alpha: colour.alpha, ..BLACK}, 0.0));
+        }
+
+        let grey = Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 1.0};
+        assert!((grey * RED).approx_eq(&Colour {red: 0.5, green: 0.0, blue: 0.0, alpha: 1.0}, 0.0));
+    }
+
+    #[test]
+    fn test_div() {
+        let tint = Colour {red: 0.5, green: 0.25, blue: 1.0, alpha: 1.0};
+        let base = Colour {red: 0.8, green: 0.4, blue: 0.2, alpha: 0.5};
+
+        assert!(((base * tint) / tint).approx_eq(&base, 1e-6));
+        assert!((RED / RED).red == 1.0 && (RED / RED).green.is_nan());
+    }
+
+    #[test]
+    fn test_hsv_primaries() {
+        assert!(Colour::from_hsv(0.0, 1.0, 1.0).approx_eq(&RED, 1e-6));
+        assert!(Colour::from_hsv(120.0, 1.0, 1.0).approx_eq(&GREEN, 1e-6));
+        assert!(Colour::from_hsv(240.0, 1.0, 1.0).approx_eq(&BLUE, 1e-6));
+        assert!(Colour::from_hsv(200.0, 0.7, 0.0).approx_eq(&BLACK, 1e-6));
+        assert!(Colour::from_hsv(200.0, 0.0, 1.0).approx_eq(&WHITE, 1e-6));
+
+        assert_eq!(RED.to_hsv(), (0.0, 1.0, 1.0));
+        assert_eq!(GREEN.to_hsv(), (120.0, 1.0, 1.0));
+        assert_eq!(BLUE.to_hsv(), (240.0, 1.0, 1.0));
+        assert_eq!(BLACK.to_hsv(), (0.0, 0.0, 0.0));
+        assert_eq!(WHITE.to_hsv(), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_hsv_wrap_around() {
+        // 360 degrees is red again and just below it is red with a little blue
+        assert!(Colour::from_hsv(360.0, 1.0, 1.0).approx_eq(&RED, 1e-6));
+        assert!(Colour::from_hsv(-120.0, 1.0, 1.0).approx_eq(&BLUE, 1e-6));
+
+        let almost_red = Colour::from_hsv(359.0, 1.0, 1.0);
+        assert_eq!(almost_red.red, 1.0);
+        assert!(almost_red.blue > 0.0 && almost_red.blue < 0.05);
+
+        let (hue, _, _) = almost_red.to_hsv();
+        assert!((hue - 359.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_hsv_round_trip() {
+        let colours = [
+            Colour {red: 0.2, green: 0.6, blue: 0.9, alpha: 1.0},
+            Colour {red: 0.9, green: 0.1, blue: 0.4, alpha: 1.0},
+            Colour {red: 0.5, green: 0.5, blue: 0.1, alpha: 1.0},
+            Colour {red: 0.3, green: 0.3, blue: 0.3, alpha: 1.0},
+        ];
+
+        for colour in colours {
+            let (h, s, v) = colour.to_hsv();
+            assert!(Colour::from_hsv(h, s, v).approx_eq(&colour, 1e-4));
+        }
+    }
+
+    #[test]
+    fn test_tone_map_operators() {
+        let bright = Colour {red: 10.0, green: 10.0, blue: 10.0, alpha: 0.5};
+
+        for mapped in [bright.reinhard_tonemap(), bright.aces_filmic()] {
+            for channel in [mapped.red, mapped.green, mapped.blue] {
+                assert!(channel > 0.9 && channel < 1.0, "{}", channel);
+            }
+            assert_eq!(mapped.alpha, 0.5);
+        }
+
+        assert_eq!(bright.reinhard_tonemap().red, 10.0 / 11.0);
+
+        // Black stays black and nothing goes above white
+        assert!(BLACK.reinhard_tonemap().approx_eq(&BLACK, 0.0));
+        assert!(BLACK.aces_filmic().approx_eq(&BLACK, 1e-3));
+        let very_bright = Colour {red: 1000.0, green: 0.0, blue: 1.0, alpha: 1.0}.aces_filmic();
+        assert!(very_bright.red <= 1.0 && very_bright.green >= 0.0 && very_bright.blue <= 1.0);
+
+        // Channels increase with the input
+        let dim = Colour {red: 0.2, green: 0.2, blue: 0.2, alpha: 1.0}.aces_filmic();
+        let mid = Colour {red: 0.8, green: 0.8, blue: 0.8, alpha: 1.0}.aces_filmic();
+        assert!(dim.green < mid.green && mid.green < bright.aces_filmic().green);
+    }
+
+    #[test]
+    fn test_tone_map_op() {
+        let hdr = Colour {red: 3.0, green: -1.0, blue: 0.5, alpha: 2.0};
+
+        assert!(ToneMapOp::Clamp.apply(&hdr).approx_eq(&Colour {red: 1.0, green: 0.0, blue: 0.5, alpha: 1.0}, 0.0));
+        assert!(ToneMapOp::Reinhard.apply(&hdr).approx_eq(&Colour {alpha: 1.0, ..hdr.reinhard_tonemap()}, 0.0));
+        assert!(ToneMapOp::AcesFilmic.apply(&hdr).approx_eq(&Colour {alpha: 1.0, ..hdr.aces_filmic()}, 0.0));
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(RED.to_hex(), "#FF0000FF");
+        assert_eq!(GREEN.to_hex(), "#00FF00FF");
+        assert_eq!(BLUE.to_hex(), "#0000FFFF");
+        assert_eq!(WHITE.to_hex(), "#FFFFFFFF");
+        assert_eq!(BLANK.to_hex(), "#00000000");
+    }
+
+    #[test]
+    fn test_from_hex() {
+        assert!(Colour::from_hex("#FF0000").unwrap().approx_eq(&RED, 0.0));
+        assert!(Colour::from_hex("#0000ff00").unwrap().approx_eq(&Colour {alpha: 0.0, ..BLUE}, 0.0));
+        assert!(Colour::from_hex("00FF00").unwrap().approx_eq(&GREEN, 0.0));
+
+        let orange_red = Colour::from_hex("#FF4500").unwrap();
+        assert_eq!(orange_red.to_bytes(), [255, 69, 0, 255]);
+
+        for colour in TEST_COLOURS {
+            assert!(Colour::from_hex(&colour.to_hex()).unwrap().approx_eq(&colour, 0.0));
+        }
+    }
+
+    #[test]
+    fn test_from_hex_errors() {
+        assert_eq!(Colour::from_hex("#FFF").err(), Some(ColourParseError::InvalidLength));
+        assert_eq!(Colour::from_hex("#FF00000").err(), Some(ColourParseError::InvalidLength));
+        assert_eq!(Colour::from_hex("").err(), Some(ColourParseError::InvalidLength));
+        assert_eq!(Colour::from_hex("#GG0000").err(), Some(ColourParseError::InvalidHex));
+        assert_eq!(Colour::from_hex("#+F0000").err(), Some(ColourParseError::InvalidHex));
+        assert_eq!(Colour::from_hex("##FF0000").err(), Some(ColourParseError::InvalidHex));
+        assert_eq!(Colour::from_hex("#FF00é").err(), Some(ColourParseError::InvalidHex));
+    }
+
+    #[test]
+    fn test_colour8_lerp() {
+        let a = Colour8::new(10, 200, 0, 255);
+        let b = Colour8::new(250, 0, 100, 0);
+
+        assert_eq!(Colour8::lerp(a, b, 0), a);
+        assert_eq!(Colour8::lerp(a, b, 255), b);
+        assert_eq!(Colour8::lerp(a, b, 128), Colour8::new(130, 100, 50, 127));
+    }
+
+    #[test]
+    fn test_colour8_premultiply_alpha() {
+        assert_eq!(Colour8::new(255, 100, 0, 255).premultiply_alpha(), Colour8::new(255, 100, 0, 255));
+        assert_eq!(Colour8::new(255, 100, 0, 0).premultiply_alpha(), Colour8::new(0, 0, 0, 0));
+        assert_eq!(Colour8::new(255, 100, 3, 128).premultiply_alpha(), Colour8::new(128, 50, 2, 128));
+    }
+
+    #[test]
+    fn test_colour8_blend_over() {
+        let backgrounds = [Colour8::new(10, 20, 30, 255), Colour8::new(200, 100, 0, 60), Colour8::new(1, 2, 3, 0)];
+        let src = Colour8::new(255, 0, 128, 255);
+
+        for dst in backgrounds {
+            assert_eq!(Colour8::blend_over(dst, Colour8 {alpha: 0, ..src}), dst);
+            assert_eq!(Colour8::blend_over(dst, src), src);
+        }
+
+        // Half transparent white over opaque black is mid grey
+        let grey = Colour8::blend_over(Colour8::new(0, 0, 0, 255), Colour8::new(255, 255, 255, 128));
+        assert_eq!(grey, Colour8::new(128, 128, 128, 255));
+
+        // Over a transparent background the source colour is kept
+        let over_blank = Colour8::blend_over(Colour8::new(0, 0, 0, 0), Colour8::new(255, 0, 0, 100));
+        assert_eq!(over_blank, Colour8::new(255, 0, 0, 100));
+
+        // Matches compositing premultiplied floating point colours
+        let dst = Colour8::new(40, 180, 90, 200);
+        let src = Colour8::new(220, 30, 60, 90);
+        let premultiply = |c: Colour8| {
+            let c = c.to_colour();
+            Colour {alpha: c.alpha, ..c.multiply_float(c.alpha)}
+        };
+        let expected = Colour::premultiplied_over(&premultiply(src), &premultiply(dst));
+        let blended = Colour8::blend_over(dst, src).to_colour();
+        assert!((blended.alpha - expected.alpha).abs() <= 1.0 / 255.0);
+        assert!((blended.red * blended.alpha - expected.red).abs() <= 1.0 / 255.0);
+        assert!((blended.green * blended.alpha - expected.green).abs() <= 1.0 / 255.0);
+    }
+
+    #[test]
+    fn test_premultiplied_over() {
+        // Half transparent white in premultiplied form
+        let fg = Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 0.5};
+
+        let over_transparent = Colour::premultiplied_over(&fg, &BLANK);
+        assert!(over_transparent.approx_eq(&Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 0.5}, 1e-6));
+
+        let over_black = Colour::premultiplied_over(&fg, &BLACK);
+        assert!(over_black.approx_eq(&Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 1.0}, 1e-6));
+
+        // Opaque colours completely cover the background
+        assert!(Colour::premultiplied_over(&RED, &BLUE).approx_eq(&RED, 0.0));
+    }
+
+    #[test]
+    fn test_blend_over() {
+        assert!(Colour::blend_over(&BLUE, &RED).approx_eq(&RED, 0.0));
+        assert!(Colour::blend_over(&BLUE, &Colour {alpha: 0.0, ..RED}).approx_eq(&BLUE, 0.0));
+
+        // Half transparent white over opaque black is mid grey
+        let grey = Colour::blend_over(&BLACK, &Colour {alpha: 0.5, ..WHITE});
+        assert!(grey.approx_eq(&Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 1.0}, 1e-6));
+
+        // Agrees with the byte version to within rounding
+        let (dst, src) = (Colour8::new(40, 180, 90, 200), Colour8::new(220, 30, 60, 90));
+        let expected = Colour8::blend_over(dst, src).to_colour();
+        assert!(Colour::blend_over(&dst.to_colour(), &src.to_colour()).approx_eq(&expected, 1.0 / 255.0));
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(RED.distance(&BLUE), 2.0f32.sqrt());
+        assert_eq!(RED.distance_sq(&BLUE), 2.0);
+
+        // Alpha is ignored
+        assert_eq!(BLACK.distance(&BLANK), 0.0);
+        for colour in TEST_COLOURS {
+            assert_eq!(colour.distance(&colour), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_max_channel() {
+        assert_eq!(RED.max_channel(), 1.0);
+        assert_eq!(Colour {red: 0.2, green: 0.7, blue: 0.4, alpha: 1.0}.max_channel(), 0.7);
+        assert_eq!(BLANK.max_channel(), 0.0);
+    }
+
+    #[test]
+    fn test_is_finite() {
+        assert!(TEST_COLOURS.iter().all(|colour| colour.is_finite()));
+        assert!(!Colour {red: f32::NAN, ..WHITE}.is_finite());
+        assert!(!Colour {alpha: f32::INFINITY, ..WHITE}.is_finite());
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let grey = Colour {red: 0.5, green: 0.25, blue: 0.75, alpha: 0.1};
+
+        assert!(Colour::blend_screen(&BLACK, &WHITE).approx_eq(&WHITE, 0.0));
+        assert!(Colour::blend_multiply(&WHITE, &RED).approx_eq(&RED, 0.0));
+        for colour in TEST_COLOURS.iter().chain([&grey]) {
+            assert!(Colour::blend_multiply(&BLACK, colour).approx_eq(&BLACK, 0.0));
+        }
+
+        // Overlay multiplies dark base channels and screens bright ones
+        let overlay = Colour::blend_overlay(&grey, &grey);
+        assert!(overlay.approx_eq(&Colour {red: 0.5, green: 0.125, blue: 0.875, alpha: 0.1}, 1e-6));
+    }
+
+    fn primary_palette() -> Palette {
+        Palette::new(vec![BLACK, WHITE, RED, GREEN, BLUE])
+    }
+
+    #[test]
+    fn test_palette_nearest() {
+        let palette = primary_palette();
+
+        assert_eq!(palette.nearest(&RED).0, 2);
+
+        let dark_red = Colour {red: 0.7, green: 0.1, blue: 0.0, alpha: 1.0};
+        let (index, colour) = palette.nearest(&dark_red);
+        assert_eq!(index, 2);
+        assert!(colour.approx_eq(&RED, 0.0));
+
+        let light_grey = Colour {red: 0.8, green: 0.8, blue: 0.8, alpha: 1.0};
+        assert_eq!(palette.nearest(&light_grey).0, 1);
+    }
+
+    #[test]
+    fn test_palette_dither_nearest() {
+        let palette = Palette::new(vec![BLACK, WHITE]);
+        let grey = Colour {red: 0.4, green: 0.4, blue: 0.4, alpha: 1.0};
+
+        // Grey rounds to black, the error carried to the next pixel pushes it to white
+        let ((index, _), error) = palette.dither_nearest(&grey, &BLANK);
+        assert_eq!(index, 0);
+        assert!((error.red - 0.4).abs() < 1e-6);
+
+        let ((index, _), error) = palette.dither_nearest(&grey, &error);
+        assert_eq!(index, 1);
+        assert!((error.red + 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_srgb_normalised_to_byte() {
+        assert_eq!(normalised_to_byte_encoded(0.2176, ColourEncoding::Srgb), 128);
+        assert_eq!(normalised_to_byte_encoded(0.0, ColourEncoding::Srgb), 0);
+        assert_eq!(normalised_to_byte_encoded(1.0, ColourEncoding::Srgb), 255);
+
+        // Linear encoding is unchanged
+        assert_eq!(normalised_to_byte(0.2176), 55);
+    }
+
+    #[test]
+    fn test_normalised_to_byte_srgb() {
+        assert_eq!(normalised_to_byte_srgb(0.0), 0);
+        assert_eq!(normalised_to_byte_srgb(1.0), 255);
+
+        // Mid grey in linear light is much brighter once encoded
+        assert_eq!(normalised_to_byte_srgb(0.5), 188);
+        assert_eq!(normalised_to_byte(0.5), 128);
+    }
+
+    #[test]
+    fn test_normalised_to_byte_round_trip() {
+        // Decoding a stored byte and encoding it again gives back the same byte
+        for encoding in [ColourEncoding::Linear, ColourEncoding::Srgb] {
+            for byte in 0..=255u8 {
+                let decoded = encoding.decode(byte_to_normalised(byte));
+                assert_eq!(normalised_to_byte_encoded(decoded, encoding), byte);
+            }
+        }
+    }
+
+    #[test]
+    fn test_linear_srgb_colour_round_trip() {
+        let grey = Colour {red: 0.5, green: 0.0031, blue: 1.0, alpha: 0.25};
+
+        let encoded = grey.linear_to_srgb();
+        assert!((encoded.red - 0.7354).abs() < 1e-4);
+        assert_eq!(encoded.blue, 1.0);
+        assert_eq!(encoded.alpha, 0.25);
+
+        assert!(encoded.srgb_to_linear().approx_eq(&grey, 1e-5));
+    }
+
+    #[test]
+    fn test_srgb_decode() {
+        for channel in [0.0, 0.001, 0.2176, 0.5, 1.0] {
+            let encoding = ColourEncoding::Srgb;
+            assert!((encoding.decode(encoding.encode(channel)) - channel).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_conversion_routes_match() {
+        let grey = Colour {red: 0.5, green: 0.25, blue: 0.75, alpha: 0.1};
+
+        for colour in TEST_COLOURS.iter().chain([&grey]) {
+            let colour8 = colour.to_colour8();
+            assert_eq!(colour.to_bytes(), [colour8.red, colour8.green, colour8.blue, colour8.alpha]);
+            assert!(colour8.to_colour().approx_eq(&Colour::from_bytes(colour.to_bytes()), 0.0));
+        }
+    }
+}
@@ -1,35 +1,68 @@
-use std::marker::PhantomData;
-use crate::colour::{Colour, BLANK};
+use std::ops::{Deref, DerefMut};
+use crate::colour::{Colour, Colour8, ColourEncoding, Palette, ToneMapOp, BLANK, RED, GREEN, BLUE};
+use crate::linear_algebra::Vec2;
+use crate::camera::Camera;
+use crate::mesh::Mesh;
+use crate::rasterisation::VertexAttribute;
 
 pub struct FrameBuffer<T: FrameBufferTrait> {
     pub width_px: usize,
     pub height_px: usize,
     pub buf: T,
+
+    // Encoding applied to colours written with write_buf and removed from colours read with read_buf
+    // Defaults to ColourEncoding::Linear, set to ColourEncoding::Srgb for gamma correct output
+    // Colour8 values (clear_with_colour, pixels, pixels_mut) are stored as they are
+    pub encoding: ColourEncoding,
 }
 
 impl<T: FrameBufferTrait> FrameBuffer<T> {
+    // Panics if buf can't store width_px * height_px pixels, use try_new to handle this
     pub fn new(width_px: usize, height_px: usize, buf: T) -> Self {
-        FrameBuffer {
+        match Self::try_new(width_px, height_px, buf) {
+            Ok(frame_buffer) => frame_buffer,
+            Err(_) => panic!("Frame buffer is too small for {} x {} pixels", width_px, height_px),
+        }
+    }
+
+    // Returns FrameBufError::SizeMismatch if buf can't store width_px * height_px pixels
+    pub fn try_new(width_px: usize, height_px: usize, buf: T) -> Result<Self, FrameBufError> {
+        if buf.pixel_capacity() < width_px * height_px {
+            return Err(FrameBufError::SizeMismatch);
+        }
+
+        Ok(FrameBuffer {
             width_px,
             height_px,
             buf,
-        }       
+            encoding: ColourEncoding::Linear,
+        })
     }
 
     pub fn write_buf(&mut self, px_x:usize, px_y: usize, colour: &Colour) -> Result<(), FrameBufError> {
-        self.buf.write_buf(px_x, px_y, colour, self.width_px, self.height_px)
+        let colour = self.encoding.encode_colour(colour);
+        self.buf.write_buf(px_x, px_y, &colour, self.width_px, self.height_px)
     }
 
     pub fn read_buf(&self, px_x:usize, px_y: usize) -> Result<Colour, FrameBufError> {
-        self.buf.read_buf(px_x, px_y, self.width_px, self.height_px)
+        let colour = self.buf.read_buf(px_x, px_y, self.width_px, self.height_px)?;
+        Ok(self.encoding.decode_colour(&colour))
     }
 
     pub fn clear_buf(&mut self) {
-        for x in 0..self.width_px {
-            for y in 0..self.height_px{
-                let _ = self.buf.write_buf(x, y, &BLANK, self.width_px, self.height_px);
-            }
-        }
+        self.clear_with_colour(&BLANK.to_colour8());
+    }
+
+    // Sets every pixel in the buffer to colour
+    // The bytes are stored as given, the encoding isn't applied
+    pub fn clear_with_colour(&mut self, colour: &Colour8) {
+        self.buf.fill(&colour.to_colour(), self.width_px, self.height_px);
+    }
+
+    // Sets every pixel in the buffer to colour, which is encoded like write_buf
+    pub fn clear_with_colour_f32(&mut self, colour: &Colour) {
+        let colour = self.encoding.encode_colour(colour);
+        self.buf.fill(&colour, self.width_px, self.height_px);
     }
 
     // Writes the contents of the frame buf to a scaled frame buf
@@ -46,6 +79,431 @@ impl<T: FrameBufferTrait> FrameBuffer<T> {
         Ok(())
     }
 
+    // Copies the src_w x src_h region of src with its bottom left pixel at (src_x, src_y) to (dst_x, dst_y) in this frame buffer
+    // The region is clipped to both frame buffers, colours are decoded from src and encoded for this buffer
+    // When both buffers use the same encoding the stored colours are copied as is, so nothing is lost to rounding
+    #[allow(clippy::too_many_arguments)] // Mirrors the source rectangle and destination position
+    pub fn blit<U: FrameBufferTrait>(&mut self, src: &FrameBuffer<U>, src_x: usize, src_y: usize, src_w: usize, src_h: usize, dst_x: usize, dst_y: usize) {
+        if src.encoding != self.encoding {
+            self.blit_with(src, (src_x, src_y, src_w, src_h), (dst_x, dst_y), |_, src_colour| *src_colour);
+            return;
+        }
+
+        let (width, height) = self.blit_size(src, (src_x, src_y, src_w, src_h), (dst_x, dst_y));
+        for x in 0..width {
+            for y in 0..height {
+                if let Ok(colour) = src.buf.read_buf(src_x + x, src_y + y, src.width_px, src.height_px) {
+                    let _ = self.buf.write_buf(dst_x + x, dst_y + y, &colour, self.width_px, self.height_px);
+                }
+            }
+        }
+    }
+
+    // Like blit, but the source pixels are alpha blended over the pixels already in this frame buffer
+    #[allow(clippy::too_many_arguments)]
+    pub fn blit_over<U: FrameBufferTrait>(&mut self, src: &FrameBuffer<U>, src_x: usize, src_y: usize, src_w: usize, src_h: usize, dst_x: usize, dst_y: usize) {
+        self.blit_with(src, (src_x, src_y, src_w, src_h), (dst_x, dst_y), Colour::blend_over);
+    }
+
+    // Combines each decoded source pixel in the region with the decoded destination pixel it lands on
+    // The result is encoded once when it's written, so blending happens in floating point rather than on stored bytes
+    fn blit_with<U: FrameBufferTrait>(&mut self, src: &FrameBuffer<U>, src_rect: (usize, usize, usize, usize), dst: (usize, usize), combine: impl Fn(&Colour, &Colour) -> Colour) {
+        let (src_x, src_y, _, _) = src_rect;
+        let (dst_x, dst_y) = dst;
+        let (width, height) = self.blit_size(src, src_rect, dst);
+
+        for x in 0..width {
+            for y in 0..height {
+                let (Ok(src_colour), Ok(dst_colour)) = (src.read_buf(src_x + x, src_y + y), self.read_buf(dst_x + x, dst_y + y)) else {
+                    continue;
+                };
+
+                let _ = self.write_buf(dst_x + x, dst_y + y, &combine(&dst_colour, &src_colour));
+            }
+        }
+    }
+
+    // Width and height of the source region once it's clipped to both frame buffers
+    fn blit_size<U: FrameBufferTrait>(&self, src: &FrameBuffer<U>, src_rect: (usize, usize, usize, usize), dst: (usize, usize)) -> (usize, usize) {
+        let (src_x, src_y, src_w, src_h) = src_rect;
+        let (dst_x, dst_y) = dst;
+
+        let width = src_w
+            .min(src.width_px.saturating_sub(src_x))
+            .min(self.width_px.saturating_sub(dst_x));
+        let height = src_h
+            .min(src.height_px.saturating_sub(src_y))
+            .min(self.height_px.saturating_sub(dst_y));
+
+        (width, height)
+    }
+
+    // Draws a line between two pixels using Bresenham's line algorithm
+    // Both end points are drawn, pixels outside of the frame buffer are skipped
+    // Like the other debug drawing functions the colour is stored as given, the encoding isn't applied
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, colour: Colour8) {
+        let colour = colour.to_colour();
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 {1} else {-1};
+        let step_y = if y0 < y1 {1} else {-1};
+
+        // Error term handles all octants by stepping x and y independently
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.write_clipped(x, y, &colour);
+
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let double_error = 2 * error;
+            if double_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if double_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    // Draws the outline of a circle using the midpoint circle algorithm
+    // One octant is stepped through and mirrored into the other seven, a negative radius draws nothing
+    pub fn draw_circle(&mut self, cx: i32, cy: i32, radius: i32, colour: Colour8) {
+        let colour = colour.to_colour();
+        for (x, y) in circle_octant(radius) {
+            for (px, py) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                self.write_clipped(cx + px, cy + py, &colour);
+            }
+        }
+    }
+
+    // Fills a circle with horizontal spans between the points of the midpoint circle outline
+    // The filled circle covers exactly the pixels inside and on the outline drawn by draw_circle
+    pub fn draw_filled_circle(&mut self, cx: i32, cy: i32, radius: i32, colour: Colour8) {
+        let colour = colour.to_colour();
+        for (x, y) in circle_octant(radius) {
+            for (half_width, py) in [(x, y), (x, -y), (y, x), (y, -x)] {
+                self.write_span_clipped(cx - half_width, cx + half_width, cy + py, &colour);
+            }
+        }
+    }
+
+    // Draws the outline of the w x h rectangle whose bottom left pixel is (x, y)
+    // The rectangle is clipped to the frame buffer, a width or height of zero draws nothing
+    pub fn draw_rect(&mut self, x: i32, y: i32, w: usize, h: usize, colour: Colour8) {
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        // Edges are clamped to just outside the buffer so that huge rectangles don't step through every offscreen pixel
+        let clamp_x = |px_x: i32| px_x.clamp(-1, self.width_px as i32);
+        let clamp_y = |px_y: i32| px_y.clamp(-1, self.height_px as i32);
+        let (left, right) = (clamp_x(x), clamp_x(rect_end(x, w)));
+        let (bottom, top) = (clamp_y(y), clamp_y(rect_end(y, h)));
+
+        if left == x {
+            self.draw_line(left, bottom, left, top, colour);
+        }
+        if right == rect_end(x, w) {
+            self.draw_line(right, bottom, right, top, colour);
+        }
+        if bottom == y {
+            self.draw_line(left, bottom, right, bottom, colour);
+        }
+        if top == rect_end(y, h) {
+            self.draw_line(left, top, right, top, colour);
+        }
+    }
+
+    // Fills the w x h rectangle whose bottom left pixel is (x, y) one row at a time
+    // The rectangle is clipped to the frame buffer, a width or height of zero draws nothing
+    pub fn fill_rect(&mut self, x: i32, y: i32, w: usize, h: usize, colour: Colour8) {
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        let colour = colour.to_colour();
+        let bottom = y.max(0);
+        let top = rect_end(y, h).min(self.height_px as i32 - 1);
+        for px_y in bottom..=top {
+            self.write_span_clipped(x, rect_end(x, w), px_y, &colour);
+        }
+    }
+
+    // Draws the x, y, and z axes as red, green, and blue lines from the origin to the end of each axis
+    // Axis end points are in raster space, see Camera::project_axes
+    pub fn draw_debug_axes(&mut self, origin: Vec2<i32>, x_axis: Vec2<i32>, y_axis: Vec2<i32>, z_axis: Vec2<i32>) {
+        for (axis, colour) in [(x_axis, RED), (y_axis, GREEN), (z_axis, BLUE)] {
+            self.draw_line(origin.x, origin.y, axis.x, axis.y, colour.to_colour8());
+        }
+    }
+
+    // Draws the three edges of a triangle whose vertices are in raster space
+    pub fn draw_wireframe_triangle(&mut self, v0: Vec2<i32>, v1: Vec2<i32>, v2: Vec2<i32>, colour: Colour8) {
+        for (start, end) in [(v0, v1), (v1, v2), (v2, v0)] {
+            self.draw_line(start.x, start.y, end.x, end.y, colour);
+        }
+    }
+
+    // Projects every triangle of the mesh with the camera and draws its edges
+    // Triangles with a vertex which cannot be projected are skipped
+    pub fn draw_wireframe_mesh<A: VertexAttribute>(&mut self, mesh: &Mesh<A>, colour: Colour8, camera: &Camera) {
+        for triangle in mesh.triangles.iter() {
+            let projected = (
+                camera.point_to_raster(&triangle.v0.vertex),
+                camera.point_to_raster(&triangle.v1.vertex),
+                camera.point_to_raster(&triangle.v2.vertex),
+            );
+
+            if let (Ok(v0), Ok(v1), Ok(v2)) = projected {
+                self.draw_wireframe_triangle(v0, v1, v2, colour);
+            }
+        }
+    }
+
+    // Draws horizontal and vertical lines every spacing pixels across the whole buffer
+    // Lines are not drawn along the edges at 0, a spacing of zero draws nothing
+    pub fn draw_debug_grid(&mut self, spacing: usize, colour: Colour8) {
+        if spacing == 0 {
+            return;
+        }
+
+        let colour = colour.to_colour();
+        for x in (spacing..self.width_px).step_by(spacing) {
+            for y in 0..self.height_px {
+                let _ = self.buf.write_buf(x, y, &colour, self.width_px, self.height_px);
+            }
+        }
+        for y in (spacing..self.height_px).step_by(spacing) {
+            for x in 0..self.width_px {
+                let _ = self.buf.write_buf(x, y, &colour, self.width_px, self.height_px);
+            }
+        }
+    }
+
+    // Returns an iterator over every pixel as (px_x, px_y, colour)
+    // Pixels are visited in scan order, starting from the top left of the image and moving along each row
+    pub fn pixels(&self) -> PixelIter<'_, T> {
+        PixelIter {
+            frame_buffer: self,
+            index: 0,
+        }
+    }
+
+    // Replaces every pixel with the result of f(px_x, px_y, colour), visiting pixels in the same order as pixels
+    // Unlike pixels_mut this works for any buffer, each pixel is read and written back through the buffer trait
+    pub fn modify_pixels(&mut self, f: impl Fn(usize, usize, Colour8) -> Colour8) {
+        for index in 0..self.width_px * self.height_px {
+            let (px_x, px_y) = scan_index_to_coordinates(index, self.width_px, self.height_px);
+            if let Ok(colour) = self.buf.read_buf(px_x, px_y, self.width_px, self.height_px) {
+                let modified = f(px_x, px_y, colour.to_colour8()).to_colour();
+                let _ = self.buf.write_buf(px_x, px_y, &modified, self.width_px, self.height_px);
+            }
+        }
+    }
+
+    // Saves the frame buffer as an 8 bit RGBA PNG
+    // The first row of the PNG is the top of the image
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width_px as u32, self.height_px as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        // Pixels are iterated in scan order which matches the row order of the PNG
+        let mut data = Vec::with_capacity(self.width_px * self.height_px * 4);
+        for (_, _, colour) in self.pixels() {
+            data.extend_from_slice(&[colour.red, colour.green, colour.blue, colour.alpha]);
+        }
+
+        let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+        writer.write_image_data(&data).map_err(std::io::Error::other)?;
+        writer.finish().map_err(std::io::Error::other)
+    }
+
+    // Saves the frame buffer as a binary PPM (P6), which needs no external crates
+    // Rows are written from the top of the image down and alpha is dropped
+    pub fn save_ppm(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let header = format!("P6\n{} {}\n255\n", self.width_px, self.height_px);
+
+        let mut data = Vec::with_capacity(header.len() + self.width_px * self.height_px * 3);
+        data.extend_from_slice(header.as_bytes());
+        for (_, _, colour) in self.pixels() {
+            data.extend_from_slice(&[colour.red, colour.green, colour.blue]);
+        }
+
+        std::fs::write(path, data)
+    }
+
+    // Saves the frame buffer as an uncompressed 24 bit TGA, alpha is dropped
+    pub fn save_tga(&self, path: &std::path::Path) -> std::io::Result<()> {
+        self.write_tga(path, false)
+    }
+
+    // Saves the frame buffer as an uncompressed 32 bit TGA which keeps alpha
+    pub fn save_tga_with_alpha(&self, path: &std::path::Path) -> std::io::Result<()> {
+        self.write_tga(path, true)
+    }
+
+    // Writes an 18 byte header then the pixels as BGR or BGRA with no footer
+    // TGA rows start at the bottom left by default, which matches the frame buffer coordinates
+    // Returns an InvalidInput error if either dimension doesn't fit in the 16 bit header fields
+    fn write_tga(&self, path: &std::path::Path, with_alpha: bool) -> std::io::Result<()> {
+        let too_large = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "TGA dimensions are limited to 65535 pixels");
+        let width = u16::try_from(self.width_px).map_err(|_| too_large())?;
+        let height = u16::try_from(self.height_px).map_err(|_| too_large())?;
+        let (pixel_depth, alpha_bits) = if with_alpha { (32, 8) } else { (24, 0) };
+
+        let mut data = Vec::with_capacity(18 + self.width_px * self.height_px * pixel_depth as usize / 8);
+        data.extend_from_slice(&[0, 0, TGA_UNCOMPRESSED_TRUE_COLOUR, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&[pixel_depth, alpha_bits]);
+
+        for y in 0..self.height_px {
+            for x in 0..self.width_px {
+                let colour = self.buf.read_buf(x, y, self.width_px, self.height_px).unwrap_or(BLANK).to_colour8();
+                data.extend_from_slice(&[colour.blue, colour.green, colour.red]);
+                if with_alpha {
+                    data.push(colour.alpha);
+                }
+            }
+        }
+
+        std::fs::write(path, data)
+    }
+
+    // Returns a copy of the frame buffer with every pixel replaced by the nearest colour in the palette
+    pub fn apply_palette(&self, palette: &Palette) -> FrameBuffer<Vec<u32>> {
+        let mut palettised = FrameBuffer::new(self.width_px, self.height_px, vec![0; self.width_px * self.height_px]);
+        palettised.encoding = self.encoding;
+
+        for x in 0..self.width_px {
+            for y in 0..self.height_px {
+                if let Ok(colour) = self.read_buf(x, y) {
+                    let (_, nearest) = palette.nearest(&colour);
+                    let _ = palettised.write_buf(x, y, &nearest);
+                }
+            }
+        }
+
+        palettised
+    }
+
+    // Returns the fraction of pixels which have been drawn to, pixels with an alpha of 0 (like BLANK) are empty
+    // An empty frame buffer has no coverage
+    pub fn coverage(&self) -> f32 {
+        let pixel_count = self.width_px * self.height_px;
+        if pixel_count == 0 {
+            return 0.0;
+        }
+
+        let covered = self.colours().filter(|colour| colour.alpha > 0.0).count();
+        covered as f32 / pixel_count as f32
+    }
+
+    // Counts the pixels whose luminance falls in each of buckets equal ranges covering [0, 1]
+    // Luminance is found from the decoded colour, values outside of [0, 1] are counted in the first or last bucket
+    pub fn histogram_luminance(&self, buckets: usize) -> Vec<usize> {
+        let mut histogram = vec![0; buckets];
+        if buckets == 0 {
+            return histogram;
+        }
+
+        for colour in self.colours() {
+            let bucket = (colour.luminance() * buckets as f32).max(0.0) as usize;
+            histogram[bucket.min(buckets - 1)] += 1;
+        }
+
+        histogram
+    }
+
+    // Returns the colour of every pixel as read by read_buf, in no particular order
+    fn colours(&self) -> impl Iterator<Item = Colour> + '_ {
+        (0..self.width_px)
+            .flat_map(move |x| (0..self.height_px).map(move |y| (x, y)))
+            .filter_map(|(x, y)| self.read_buf(x, y).ok())
+    }
+
+    // Copies the region with its bottom left pixel at (px_x, px_y) into a new frame buffer
+    // Returns an error if any part of the region is outside the frame buffer
+    pub fn crop(&self, px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<FrameBuffer<Vec<u32>>, FrameBufError> {
+        let outside_x = px_x.checked_add(width_px).is_none_or(|end| end > self.width_px);
+        let outside_y = px_y.checked_add(height_px).is_none_or(|end| end > self.height_px);
+        if outside_x || outside_y {
+            // Report the corner of the region furthest from the origin, it is always outside the frame buffer
+            return Err(FrameBufError::PixelOutsideBuf {x: px_x.saturating_add(width_px).saturating_sub(1), y: px_y.saturating_add(height_px).saturating_sub(1)});
+        }
+
+        let mut cropped = FrameBuffer::new(width_px, height_px, vec![0; width_px * height_px]);
+        cropped.encoding = self.encoding;
+
+        // Colours are copied without decoding so they are stored exactly as they are in the source
+        for x in 0..width_px {
+            for y in 0..height_px {
+                let colour = self.buf.read_buf(px_x + x, px_y + y, self.width_px, self.height_px)?;
+                cropped.buf.write_buf(x, y, &colour, width_px, height_px)?;
+            }
+        }
+
+        Ok(cropped)
+    }
+
+    // Mirrors the image in place by swapping column x with column width_px - 1 - x
+    pub fn flip_horizontal(&mut self) {
+        for x in 0..self.width_px / 2 {
+            for y in 0..self.height_px {
+                self.swap_pixels((x, y), (self.width_px - 1 - x, y));
+            }
+        }
+    }
+
+    // Mirrors the image in place by swapping row y with row height_px - 1 - y
+    pub fn flip_vertical(&mut self) {
+        for y in 0..self.height_px / 2 {
+            for x in 0..self.width_px {
+                self.swap_pixels((x, y), (x, self.height_px - 1 - y));
+            }
+        }
+    }
+
+    // Swaps two pixels without decoding them, pixels outside of the frame buffer are left alone
+    fn swap_pixels(&mut self, a: (usize, usize), b: (usize, usize)) {
+        let (width_px, height_px) = (self.width_px, self.height_px);
+        let (Ok(colour_a), Ok(colour_b)) = (self.buf.read_buf(a.0, a.1, width_px, height_px), self.buf.read_buf(b.0, b.1, width_px, height_px)) else {
+            return;
+        };
+
+        let _ = self.buf.write_buf(a.0, a.1, &colour_b, width_px, height_px);
+        let _ = self.buf.write_buf(b.0, b.1, &colour_a, width_px, height_px);
+    }
+
+    // Writes a pixel given by signed coordinates, pixels outside of the frame buffer are skipped
+    fn write_clipped(&mut self, px_x: i32, px_y: i32, colour: &Colour) {
+        if px_x >= 0 && px_y >= 0 {
+            let _ = self.buf.write_buf(px_x as usize, px_y as usize, colour, self.width_px, self.height_px);
+        }
+    }
+
+    // Writes every pixel in row px_y from x0 to x1 inclusive, clipped to the frame buffer
+    fn write_span_clipped(&mut self, x0: i32, x1: i32, px_y: i32, colour: &Colour) {
+        if px_y < 0 || px_y as usize >= self.height_px {
+            return;
+        }
+
+        let start = x0.max(0);
+        let end = x1.min(self.width_px as i32 - 1);
+        for px_x in start..=end {
+            let _ = self.buf.write_buf(px_x as usize, px_y as usize, colour, self.width_px, self.height_px);
+        }
+    }
+
     // Writes a square with a solid colour to the frame buffer
     fn write_square(&mut self, px_x: usize, px_y: usize, colour: Colour, size: usize) {
         for x in px_x..(px_x + size) {
@@ -56,18 +514,1348 @@ impl<T: FrameBufferTrait> FrameBuffer<T> {
     }
 }
 
-pub enum FrameBufError {
-    PixelOutsideBuf,
-    Other,
+impl<T: FrameBufferTrait + Clone> FrameBuffer<T> {
+    // Returns a mirrored copy of the frame buffer, leaving this one unchanged
+    pub fn flipped_horizontal(&self) -> FrameBuffer<T> {
+        let mut flipped = self.clone_buf();
+        flipped.flip_horizontal();
+        flipped
+    }
+
+    // Returns an upside down copy of the frame buffer, leaving this one unchanged
+    pub fn flipped_vertical(&self) -> FrameBuffer<T> {
+        let mut flipped = self.clone_buf();
+        flipped.flip_vertical();
+        flipped
+    }
+
+    fn clone_buf(&self) -> FrameBuffer<T> {
+        let mut copy = FrameBuffer::new(self.width_px, self.height_px, self.buf.clone());
+        copy.encoding = self.encoding;
+        copy
+    }
 }
 
-pub trait FrameBufferTrait {
-    // px_x and px_y are the pixels to write to
-    // The origin of px_x and px_y is in the bottom left of the image
+// First three bytes of a file written by save_raw, the fourth byte marks the byte order of the values
+const RAW_MAGIC: [u8; 3] = *b"RFB";
+const TGA_UNCOMPRESSED_TRUE_COLOUR: u8 = 2;
 
-    // Write a colour to the buffer
-    fn write_buf(&mut self, px_x: usize, px_y: usize, colour: &Colour, width_px: usize, height_px: usize) -> Result<(), FrameBufError>;
+const RAW_LITTLE_ENDIAN: u8 = b'L';
+const RAW_BIG_ENDIAN: u8 = b'B';
 
-    // Read a colour from the buffer
-    fn read_buf(&self, px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<Colour, FrameBufError>;
-}
\ No newline at end of file
+impl FrameBuffer<Vec<u32>> {
+    // Loads a frame buffer written by save_raw, the encoding is set to ColourEncoding::Linear
+    // Returns an InvalidData error if the header is wrong or the file is the wrong length for its size
+    pub fn load_raw(path: &std::path::Path) -> std::io::Result<Self> {
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+
+        let data = std::fs::read(path)?;
+        if data.len() < 12 || data[..3] != RAW_MAGIC {
+            return Err(invalid("Not a raw frame buffer file"));
+        }
+
+        let read_u32: fn([u8; 4]) -> u32 = match data[3] {
+            RAW_LITTLE_ENDIAN => u32::from_le_bytes,
+            RAW_BIG_ENDIAN => u32::from_be_bytes,
+            _ => return Err(invalid("Unknown byte order")),
+        };
+        let word = |bytes: &[u8]| read_u32([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        let width_px = word(&data[4..8]) as usize;
+        let height_px = word(&data[8..12]) as usize;
+        let pixels = &data[12..];
+
+        // The size comes from the file so it may be corrupt, an overflowing size can't match the pixel data
+        let byte_count = width_px.checked_mul(height_px).and_then(|pixel_count| pixel_count.checked_mul(4));
+        if byte_count != Some(pixels.len()) {
+            return Err(invalid("Pixel data doesn't match the frame buffer size"));
+        }
+
+        let buf = pixels.chunks_exact(4).map(word).collect();
+        Ok(FrameBuffer::new(width_px, height_px, buf))
+    }
+}
+
+// Frame buffer which stores unbounded f32 colour channels
+// Radiance values above 1 are preserved until the buffer is tone mapped
+pub type HdrFrameBuffer = FrameBuffer<Vec<Colour>>;
+
+impl FrameBuffer<Vec<Colour>> {
+    // Makes a new HDR frame buffer with every pixel set to BLANK
+    pub fn new_hdr(width_px: usize, height_px: usize) -> Self {
+        FrameBuffer::new(width_px, height_px, vec![BLANK; width_px * height_px])
+    }
+
+    // Converts the buffer to a displayable buffer by tone mapping every pixel
+    pub fn tone_map_to_ldr(&self, op: ToneMapOp) -> FrameBuffer<Vec<u32>> {
+        let buf = self.buf.iter().map(|colour| op.apply(colour).to_colour8().to_u32()).collect();
+        FrameBuffer::new(self.width_px, self.height_px, buf)
+    }
+}
+
+// Raw access to buffers which store each pixel as a u32
+// No coordinate transformation is applied, pixels are in the order they are stored
+impl<T: FrameBufferTrait + AsRef<[u32]>> FrameBuffer<T> {
+    pub fn read_as_slice(&self) -> &[u32] {
+        self.buf.as_ref()
+    }
+
+    // Saves the stored words to a file with a 12 byte header
+    // The header is RAW_MAGIC, the width as a u32 and the height as a u32, followed by the words in the order they are stored
+    // Every value is written little endian, the last byte of the magic records this so big endian files can also be read
+    // The encoding isn't saved, see load_raw
+    pub fn save_raw(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let words = &self.read_as_slice()[..self.width_px * self.height_px];
+
+        let mut data = Vec::with_capacity(12 + words.len() * 4);
+        data.extend_from_slice(&RAW_MAGIC);
+        data.push(RAW_LITTLE_ENDIAN);
+        data.extend_from_slice(&(self.width_px as u32).to_le_bytes());
+        data.extend_from_slice(&(self.height_px as u32).to_le_bytes());
+        for word in words {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+
+        std::fs::write(path, data)
+    }
+
+    // Returns a copy of the buffer where every pixel is repeated in a factor x factor square
+    // Gives the same result as scale for either encoding, but copies the stored words directly instead of converting every pixel to a colour and back
+    pub fn scale_nearest(&self, factor: usize) -> FrameBuffer<Vec<u32>> {
+        let (width_px, height_px) = (self.width_px * factor, self.height_px * factor);
+        let source = self.read_as_slice();
+        let mut buf = Vec::with_capacity(width_px * height_px);
+
+        // Rows are stored top to bottom so each stored row becomes factor consecutive stored rows
+        for y in 0..self.height_px {
+            let row_start = buf.len();
+            for word in &source[y * self.width_px..(y + 1) * self.width_px] {
+                buf.extend(std::iter::repeat_n(*word, factor));
+            }
+
+            for _ in 1..factor {
+                buf.extend_from_within(row_start..row_start + width_px);
+            }
+        }
+
+        FrameBuffer {
+            encoding: self.encoding,
+            ..FrameBuffer::new(width_px, height_px, buf)
+        }
+    }
+}
+
+impl<T: FrameBufferTrait + AsMut<[u32]>> FrameBuffer<T> {
+    pub fn read_as_slice_mut(&mut self) -> &mut [u32] {
+        self.buf.as_mut()
+    }
+
+    // Returns an iterator over every pixel as (px_x, px_y, pixel) where the pixel can be modified in place
+    // Pixels are visited in the same order as FrameBuffer::pixels
+    pub fn pixels_mut(&mut self) -> PixelIterMut<'_> {
+        let (width_px, height_px) = (self.width_px, self.height_px);
+        PixelIterMut {
+            words: self.buf.as_mut()[..width_px * height_px].iter_mut().enumerate(),
+            width_px,
+            height_px,
+        }
+    }
+
+    // Copies data into the buffer, data must be the same length as the buffer
+    pub fn write_from_slice(&mut self, data: &[u32]) -> Result<(), FrameBufError> {
+        let buf = self.buf.as_mut();
+        if buf.len() != data.len() {
+            return Err(FrameBufError::SizeMismatch);
+        }
+
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+// Returns the coordinate of the last pixel in a span of size pixels starting at start
+fn rect_end(start: i32, size: usize) -> i32 {
+    start.saturating_add(i32::try_from(size - 1).unwrap_or(i32::MAX))
+}
+
+// Returns the points of one octant of a midpoint circle centred on the origin, from (radius, 0) until x < y
+// The other seven octants are found by swapping and negating the coordinates
+fn circle_octant(radius: i32) -> Vec<(i32, i32)> {
+    let mut points = Vec::new();
+    let (mut x, mut y) = (radius, 0);
+    let mut error = 1 - radius;
+
+    while x >= y {
+        points.push((x, y));
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+
+    points
+}
+
+// Converts an index in scan order (top left to bottom right) to pixel coordinates
+fn scan_index_to_coordinates(index: usize, width_px: usize, height_px: usize) -> (usize, usize) {
+    (index % width_px, height_px - index / width_px - 1)
+}
+
+pub struct PixelIter<'a, T: FrameBufferTrait> {
+    frame_buffer: &'a FrameBuffer<T>,
+    index: usize,
+}
+
+impl<T: FrameBufferTrait> Iterator for PixelIter<'_, T> {
+    type Item = (usize, usize, Colour8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.frame_buffer.width_px * self.frame_buffer.height_px {
+            return None;
+        }
+
+        let (px_x, px_y) = scan_index_to_coordinates(self.index, self.frame_buffer.width_px, self.frame_buffer.height_px);
+        self.index += 1;
+
+        let frame_buffer = self.frame_buffer;
+        let colour = frame_buffer.buf.read_buf(px_x, px_y, frame_buffer.width_px, frame_buffer.height_px).ok()?;
+        Some((px_x, px_y, colour.to_colour8()))
+    }
+}
+
+pub struct PixelIterMut<'a> {
+    words: std::iter::Enumerate<std::slice::IterMut<'a, u32>>,
+    width_px: usize,
+    height_px: usize,
+}
+
+impl<'a> Iterator for PixelIterMut<'a> {
+    type Item = (usize, usize, PixelMut<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, word) = self.words.next()?;
+        let (px_x, px_y) = scan_index_to_coordinates(index, self.width_px, self.height_px);
+
+        let pixel = PixelMut {
+            colour: Colour8::from_u32(*word),
+            word,
+            modified: false,
+        };
+
+        Some((px_x, px_y, pixel))
+    }
+}
+
+// Mutable view of a single pixel, dereferences to a Colour8
+// Changes are written back to the buffer when the pixel is dropped
+pub struct PixelMut<'a> {
+    word: &'a mut u32,
+    colour: Colour8,
+    modified: bool,
+}
+
+impl Deref for PixelMut<'_> {
+    type Target = Colour8;
+
+    fn deref(&self) -> &Colour8 {
+        &self.colour
+    }
+}
+
+impl DerefMut for PixelMut<'_> {
+    fn deref_mut(&mut self) -> &mut Colour8 {
+        self.modified = true;
+        &mut self.colour
+    }
+}
+
+impl Drop for PixelMut<'_> {
+    fn drop(&mut self) {
+        // Only write back modified pixels so untouched words are left exactly as they were
+        if self.modified {
+            *self.word = self.colour.to_u32();
+        }
+    }
+}
+
+// Depth of the nearest surface drawn to each pixel, used by the rasteriser to hide surfaces behind others
+// Pixels use the same bottom left origin as FrameBuffer and start infinitely far away
+pub struct DepthBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub buf: Vec<f32>,
+}
+
+impl DepthBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        DepthBuffer {
+            width,
+            height,
+            buf: vec![f32::INFINITY; width * height],
+        }
+    }
+
+    pub fn read(&self, x: usize, y: usize) -> Result<f32, FrameBufError> {
+        Ok(self.buf[self.index(x, y)?])
+    }
+
+    pub fn write(&mut self, x: usize, y: usize, depth: f32) -> Result<(), FrameBufError> {
+        let index = self.index(x, y)?;
+        self.buf[index] = depth;
+        Ok(())
+    }
+
+    // Resets every pixel to infinitely far away, call this whenever the frame buffer is cleared
+    pub fn clear(&mut self) {
+        self.buf.fill(f32::INFINITY);
+    }
+
+    fn index(&self, x: usize, y: usize) -> Result<usize, FrameBufError> {
+        if x >= self.width || y >= self.height {
+            return Err(FrameBufError::PixelOutsideBuf {x, y});
+        }
+
+        Ok(x + y * self.width)
+    }
+}
+
+#[derive(Debug)]
+pub enum FrameBufError {
+    PixelOutsideBuf {x: usize, y: usize}, // Coordinates of the pixel which is outside of the buffer
+    SizeMismatch,
+    Other,
+}
+
+impl std::fmt::Display for FrameBufError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameBufError::PixelOutsideBuf {x, y} => write!(f, "Pixel ({}, {}) is outside of the frame buffer", x, y),
+            FrameBufError::SizeMismatch => write!(f, "Buffer size doesn't match the frame buffer"),
+            FrameBufError::Other => write!(f, "Frame buffer error"),
+        }
+    }
+}
+
+// Convert pixel coordinates to array index
+pub fn convert_coordinates(px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<usize, FrameBufError> {
+    if px_x >= width_px || px_y >= height_px {
+        return Err(FrameBufError::PixelOutsideBuf {x: px_x, y: px_y});
+    }
+
+    let write_y = height_px - px_y - 1;
+    let index = px_x + (write_y * width_px);
+    Ok(index)
+}
+
+// Buffer with a size only known at runtime, pixels are stored in the same format as the array buffer
+impl FrameBufferTrait for Vec<u32> {
+    fn write_buf(&mut self, px_x: usize, px_y: usize, colour: &Colour, width_px: usize, height_px: usize) -> Result<(), FrameBufError> {
+        let index = convert_coordinates(px_x, px_y, width_px, height_px)?;
+        self[index] = colour.to_colour8().to_u32();
+
+        Ok(())
+    }
+
+    fn read_buf(&self, px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<Colour, FrameBufError> {
+        let index = convert_coordinates(px_x, px_y, width_px, height_px)?;
+        Ok(Colour8::from_u32(self[index]).to_colour())
+    }
+
+    fn pixel_capacity(&self) -> usize {
+        self.len()
+    }
+
+    fn fill(&mut self, colour: &Colour, width_px: usize, height_px: usize) {
+        self[..width_px * height_px].fill(colour.to_colour8().to_u32());
+    }
+}
+
+// HDR buffer, colours are stored without any conversion
+impl FrameBufferTrait for Vec<Colour> {
+    fn write_buf(&mut self, px_x: usize, px_y: usize, colour: &Colour, width_px: usize, height_px: usize) -> Result<(), FrameBufError> {
+        let index = convert_coordinates(px_x, px_y, width_px, height_px)?;
+        self[index] = *colour;
+
+        Ok(())
+    }
+
+    fn read_buf(&self, px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<Colour, FrameBufError> {
+        let index = convert_coordinates(px_x, px_y, width_px, height_px)?;
+        Ok(self[index])
+    }
+
+    fn pixel_capacity(&self) -> usize {
+        self.len()
+    }
+
+    fn fill(&mut self, colour: &Colour, width_px: usize, height_px: usize) {
+        self[..width_px * height_px].fill(*colour);
+    }
+}
+
+pub trait FrameBufferTrait {
+    // px_x and px_y are the pixels to write to
+    // The origin of px_x and px_y is in the bottom left of the image
+
+    // Write a colour to the buffer
+    fn write_buf(&mut self, px_x: usize, px_y: usize, colour: &Colour, width_px: usize, height_px: usize) -> Result<(), FrameBufError>;
+
+    // Read a colour from the buffer
+    fn read_buf(&self, px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<Colour, FrameBufError>;
+
+    // Number of pixels the buffer can store, FrameBuffer::new checks this against the size of the frame buffer
+    fn pixel_capacity(&self) -> usize;
+
+    // Write a colour to every pixel of the image
+    // Buffers which store pixels contiguously should override this to fill their storage directly
+    fn fill(&mut self, colour: &Colour, width_px: usize, height_px: usize) {
+        for x in 0..width_px {
+            for y in 0..height_px {
+                let _ = self.write_buf(x, y, colour, width_px, height_px);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::{BLACK, WHITE};
+
+    const WIDTH: usize = 8;
+    const HEIGHT: usize = 8;
+
+    fn frame_buffer() -> FrameBuffer<[u32; WIDTH * HEIGHT]> {
+        FrameBuffer::new(WIDTH, HEIGHT, [0; WIDTH * HEIGHT])
+    }
+
+    fn pixel<T: FrameBufferTrait>(frame_buffer: &FrameBuffer<T>, x: usize, y: usize) -> Colour8 {
+        frame_buffer.read_buf(x, y).unwrap().to_colour8()
+    }
+
+    // Returns the coordinates of every pixel of the given colour, sorted by x then y
+    fn drawn_pixels<T: FrameBufferTrait>(frame_buffer: &FrameBuffer<T>, colour: Colour8) -> Vec<(usize, usize)> {
+        let mut pixels: Vec<(usize, usize)> = frame_buffer.pixels()
+            .filter(|(_, _, pixel_colour)| *pixel_colour == colour)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        pixels.sort();
+        pixels
+    }
+
+    #[test]
+    fn test_draw_line_octants() {
+        // Horizontal, vertical, diagonal, and steep lines drawn in both directions
+        let lines = [
+            ((0, 0), (7, 0)),
+            ((3, 7), (3, 0)),
+            ((0, 0), (7, 7)),
+            ((5, 1), (2, 7)),
+        ];
+
+        for ((x0, y0), (x1, y1)) in lines {
+            let mut frame_buffer = frame_buffer();
+            frame_buffer.draw_line(x0, y0, x1, y1, RED.to_colour8());
+
+            let mut drawn = 0;
+            for x in 0..WIDTH {
+                for y in 0..HEIGHT {
+                    if pixel(&frame_buffer, x, y) == RED.to_colour8() {
+                        drawn += 1;
+                    }
+                }
+            }
+
+            // Bresenham lines have one pixel for every step along the major axis
+            let major = (x1 - x0).abs().max((y1 - y0).abs()) as usize;
+            assert_eq!(drawn, major + 1);
+            assert_eq!(pixel(&frame_buffer, x0 as usize, y0 as usize), RED.to_colour8());
+            assert_eq!(pixel(&frame_buffer, x1 as usize, y1 as usize), RED.to_colour8());
+        }
+    }
+
+    #[test]
+    fn test_draw_line_exact_pixels() {
+        let drawn = |frame_buffer: &FrameBuffer<[u32; WIDTH * HEIGHT]>| drawn_pixels(frame_buffer, GREEN.to_colour8());
+
+        let mut horizontal = frame_buffer();
+        horizontal.draw_line(5, 3, 2, 3, GREEN.to_colour8());
+        assert_eq!(drawn(&horizontal), [(2, 3), (3, 3), (4, 3), (5, 3)]);
+
+        let mut vertical = frame_buffer();
+        vertical.draw_line(6, 1, 6, 4, GREEN.to_colour8());
+        assert_eq!(drawn(&vertical), [(6, 1), (6, 2), (6, 3), (6, 4)]);
+
+        let mut diagonal = frame_buffer();
+        diagonal.draw_line(4, 1, 1, 4, GREEN.to_colour8());
+        assert_eq!(drawn(&diagonal), [(1, 4), (2, 3), (3, 2), (4, 1)]);
+
+        // A line with no length is a single pixel
+        let mut point = frame_buffer();
+        point.draw_line(7, 0, 7, 0, GREEN.to_colour8());
+        assert_eq!(drawn(&point), [(7, 0)]);
+
+        // Lines entirely outside the buffer draw nothing
+        let mut outside = frame_buffer();
+        outside.draw_line(-5, -1, -1, -5, GREEN.to_colour8());
+        outside.draw_line(8, 0, 20, 20, GREEN.to_colour8());
+        assert!(drawn(&outside).is_empty());
+    }
+
+    #[test]
+    fn test_draw_line_stores_colour_bytes() {
+        let mut frame_buffer = FrameBuffer::new(4, 1, vec![0u32; 4]);
+        frame_buffer.encoding = ColourEncoding::Srgb;
+
+        let grey = Colour8::new(128, 128, 128, 255);
+        frame_buffer.draw_line(0, 0, 3, 0, grey);
+        assert!(frame_buffer.read_as_slice().iter().all(|&word| word == grey.to_u32()));
+    }
+
+    #[test]
+    fn test_draw_circle() {
+        let mut frame_buffer = FrameBuffer::new(32, 32, vec![0u32; 32 * 32]);
+        frame_buffer.draw_circle(10, 10, 5, GREEN.to_colour8());
+        let drawn = drawn_pixels(&frame_buffer, GREEN.to_colour8());
+
+        // Extremes are exactly on the radius
+        for extreme in [(15, 10), (5, 10), (10, 15), (10, 5)] {
+            assert!(drawn.contains(&extreme));
+        }
+        for outside in [(16, 10), (4, 10), (10, 16), (10, 4), (10, 10)] {
+            assert!(!drawn.contains(&outside));
+        }
+
+        // Seven pixels in each quadrant, the pixels on the axes are shared
+        assert_eq!(drawn.len(), 28);
+        for (x, y) in drawn {
+            let distance_squared = (x as i32 - 10).pow(2) + (y as i32 - 10).pow(2);
+            assert!((16..=36).contains(&distance_squared));
+        }
+    }
+
+    #[test]
+    fn test_draw_circle_degenerate() {
+        let mut point = frame_buffer();
+        point.draw_circle(3, 3, 0, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&point, GREEN.to_colour8()), [(3, 3)]);
+
+        // A radius of one leaves the centre and corners of the 3x3 square empty
+        let mut ring = frame_buffer();
+        ring.draw_circle(3, 3, 1, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&ring, GREEN.to_colour8()), [(2, 3), (3, 2), (3, 4), (4, 3)]);
+
+        let mut negative = frame_buffer();
+        negative.draw_circle(3, 3, -1, GREEN.to_colour8());
+        assert!(drawn_pixels(&negative, GREEN.to_colour8()).is_empty());
+    }
+
+    #[test]
+    fn test_draw_filled_circle() {
+        let mut outline = FrameBuffer::new(32, 32, vec![0u32; 32 * 32]);
+        outline.draw_circle(10, 10, 5, GREEN.to_colour8());
+        let mut filled = FrameBuffer::new(32, 32, vec![0u32; 32 * 32]);
+        filled.draw_filled_circle(10, 10, 5, GREEN.to_colour8());
+
+        let outline = drawn_pixels(&outline, GREEN.to_colour8());
+        let filled = drawn_pixels(&filled, GREEN.to_colour8());
+        assert!(outline.iter().all(|pixel| filled.contains(pixel)));
+        assert!(filled.contains(&(10, 10)));
+
+        // Every filled pixel is inside the outline's row extent
+        for &(x, y) in filled.iter() {
+            let row_max = outline.iter().filter(|(_, outline_y)| *outline_y == y).map(|(outline_x, _)| *outline_x).max().unwrap();
+            let row_min = outline.iter().filter(|(_, outline_y)| *outline_y == y).map(|(outline_x, _)| *outline_x).min().unwrap();
+            assert!((row_min..=row_max).contains(&x));
+        }
+
+        let mut point = frame_buffer();
+        point.draw_filled_circle(0, 0, 0, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&point, GREEN.to_colour8()), [(0, 0)]);
+    }
+
+    #[test]
+    fn test_draw_circle_clipped() {
+        // Circles hanging off the corners of the buffer only draw the visible pixels
+        let mut outline = frame_buffer();
+        outline.draw_circle(0, 0, 3, GREEN.to_colour8());
+        outline.draw_circle(7, 7, 3, GREEN.to_colour8());
+        assert!(drawn_pixels(&outline, GREEN.to_colour8()).contains(&(3, 0)));
+
+        let mut filled = frame_buffer();
+        filled.draw_filled_circle(-20, -20, 4, GREEN.to_colour8());
+        assert!(drawn_pixels(&filled, GREEN.to_colour8()).is_empty());
+        filled.draw_filled_circle(7, 0, 2, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&filled, GREEN.to_colour8()).len(), 8);
+    }
+
+    #[test]
+    fn test_fill_rect_whole_buffer() {
+        let colour = Colour8::new(10, 20, 30, 255);
+        let mut filled = frame_buffer();
+        filled.fill_rect(0, 0, WIDTH, HEIGHT, colour);
+        let mut cleared = frame_buffer();
+        cleared.clear_with_colour(&colour);
+        assert_eq!(filled.read_as_slice(), cleared.read_as_slice());
+
+        // Oversized rectangles are clipped to the same result
+        let mut oversized = frame_buffer();
+        oversized.fill_rect(-3, -3, usize::MAX, usize::MAX, colour);
+        assert_eq!(oversized.read_as_slice(), cleared.read_as_slice());
+    }
+
+    #[test]
+    fn test_rect_outline_and_fill() {
+        // Rectangles one pixel wide or high have no interior so the outline and fill match
+        for (x, y, w, h) in [(2, 1, 1, 5), (1, 6, 4, 1), (-2, 3, 5, 1), (3, 3, 1, 1)] {
+            let mut outline = frame_buffer();
+            outline.draw_rect(x, y, w, h, GREEN.to_colour8());
+            let mut filled = frame_buffer();
+            filled.fill_rect(x, y, w, h, GREEN.to_colour8());
+            assert_eq!(outline.read_as_slice(), filled.read_as_slice());
+        }
+
+        let mut outline = frame_buffer();
+        outline.draw_rect(1, 2, 4, 3, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&outline, GREEN.to_colour8()), [
+            (1, 2), (1, 3), (1, 4),
+            (2, 2), (2, 4),
+            (3, 2), (3, 4),
+            (4, 2), (4, 3), (4, 4),
+        ]);
+
+        let mut filled = frame_buffer();
+        filled.fill_rect(1, 2, 4, 3, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&filled, GREEN.to_colour8()).len(), 12);
+    }
+
+    #[test]
+    fn test_rect_clipped() {
+        // Only the two edges inside the buffer are drawn
+        let mut outline = frame_buffer();
+        outline.draw_rect(-2, -2, 5, 5, GREEN.to_colour8());
+        assert_eq!(drawn_pixels(&outline, GREEN.to_colour8()), [(0, 2), (1, 2), (2, 0), (2, 1), (2, 2)]);
+
+        let mut empty = frame_buffer();
+        empty.draw_rect(2, 2, 0, 3, GREEN.to_colour8());
+        empty.fill_rect(2, 2, 3, 0, GREEN.to_colour8());
+        empty.fill_rect(WIDTH as i32, 0, 3, 3, GREEN.to_colour8());
+        empty.draw_rect(-10, -10, usize::MAX, usize::MAX, GREEN.to_colour8());
+        assert!(drawn_pixels(&empty, GREEN.to_colour8()).is_empty());
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut square = FrameBuffer::new(4, 4, vec![0u32; 16]);
+        square.clear_with_colour(&RED.to_colour8());
+        let mut frame_buffer = FrameBuffer::new(16, 16, vec![0u32; 16 * 16]);
+        frame_buffer.clear_with_colour(&BLACK.to_colour8());
+
+        frame_buffer.blit(&square, 0, 0, 4, 4, 5, 6);
+        for (x, y, colour) in frame_buffer.pixels() {
+            let inside = (5..9).contains(&x) && (6..10).contains(&y);
+            assert_eq!(colour, if inside {RED} else {BLACK}.to_colour8());
+        }
+    }
+
+    #[test]
+    fn test_blit_clipped() {
+        let mut src = FrameBuffer::new(4, 4, vec![0u32; 16]);
+        src.clear_with_colour(&RED.to_colour8());
+
+        // Source region runs off the source, and the destination runs off the destination
+        let mut clipped = frame_buffer();
+        clipped.blit(&src, 2, 1, 10, 10, 7, 0);
+        assert_eq!(drawn_pixels(&clipped, RED.to_colour8()), [(7, 0), (7, 1), (7, 2)]);
+
+        let mut untouched = frame_buffer();
+        untouched.blit(&src, 4, 0, 4, 4, 0, 0);
+        untouched.blit(&src, 0, 0, 4, 4, WIDTH, HEIGHT);
+        assert!(untouched.read_as_slice().iter().all(|&word| word == 0));
+    }
+
+    #[test]
+    fn test_blit_over() {
+        let mut src = FrameBuffer::new(2, 1, vec![0u32; 2]);
+        src.write_buf(0, 0, &Colour {alpha: 0.0, ..WHITE}).unwrap();
+        src.write_buf(1, 0, &WHITE).unwrap();
+
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&BLACK.to_colour8());
+        frame_buffer.blit_over(&src, 0, 0, 2, 1, 3, 3);
+
+        // Transparent pixels leave the destination alone, opaque ones replace it
+        assert_eq!(pixel(&frame_buffer, 3, 3), BLACK.to_colour8());
+        assert_eq!(pixel(&frame_buffer, 4, 3), WHITE.to_colour8());
+    }
+
+    #[test]
+    fn test_blit_srgb() {
+        let mut src = frame_buffer();
+        src.encoding = ColourEncoding::Srgb;
+        for x in 0..WIDTH {
+            let byte = (x * 37) as u8;
+            src.fill_rect(x as i32, 0, 1, HEIGHT, Colour8::new(byte, byte / 2, 255 - byte, 255));
+        }
+
+        // Stored bytes come through unchanged between buffers with the same encoding
+        let mut copied = frame_buffer();
+        copied.encoding = ColourEncoding::Srgb;
+        copied.blit(&src, 0, 0, WIDTH, HEIGHT, 0, 0);
+        assert!(copied.pixels().eq(src.pixels()));
+
+        // Opaque pixels blended over anything should also be left as stored
+        let mut blended = frame_buffer();
+        blended.encoding = ColourEncoding::Srgb;
+        blended.clear_with_colour(&Colour8::new(10, 20, 30, 255));
+        blended.blit_over(&src, 0, 0, WIDTH, HEIGHT, 0, 0);
+        assert!(blended.pixels().eq(src.pixels()));
+    }
+
+    // Arrow pointing right with its tip at (6, centre_y)
+    fn arrow(centre_y: i32) -> FrameBuffer<[u32; WIDTH * HEIGHT]> {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.draw_line(1, centre_y, 6, centre_y, GREEN.to_colour8());
+        frame_buffer.draw_line(6, centre_y, 4, centre_y + 2, GREEN.to_colour8());
+        frame_buffer.draw_line(6, centre_y, 4, centre_y - 2, GREEN.to_colour8());
+        frame_buffer
+    }
+
+    #[test]
+    fn test_flip_horizontal() {
+        let mut flipped = arrow(4);
+        flipped.flip_horizontal();
+
+        // The arrow now points left with its tip at (1, 4)
+        let mut expected = frame_buffer();
+        expected.draw_line(6, 4, 1, 4, GREEN.to_colour8());
+        expected.draw_line(1, 4, 3, 6, GREEN.to_colour8());
+        expected.draw_line(1, 4, 3, 2, GREEN.to_colour8());
+        assert_eq!(flipped.read_as_slice(), expected.read_as_slice());
+
+        flipped.flip_horizontal();
+        assert_eq!(flipped.read_as_slice(), arrow(4).read_as_slice());
+    }
+
+    #[test]
+    fn test_flip_vertical() {
+        let mut frame_buffer = arrow(4);
+        frame_buffer.flip_vertical();
+        assert_eq!(frame_buffer.read_as_slice(), arrow(3).read_as_slice());
+
+        frame_buffer.flip_vertical();
+        assert_eq!(frame_buffer.read_as_slice(), arrow(4).read_as_slice());
+    }
+
+    #[test]
+    fn test_flipped() {
+        // Odd sizes leave the middle column or row in place
+        let mut frame_buffer = FrameBuffer::new(3, 3, vec![0u32; 9]);
+        frame_buffer.encoding = ColourEncoding::Srgb;
+        frame_buffer.write_buf(0, 0, &RED).unwrap();
+        frame_buffer.write_buf(1, 2, &BLUE).unwrap();
+
+        let horizontal = frame_buffer.flipped_horizontal();
+        assert_eq!(drawn_pixels(&horizontal, RED.to_colour8()), [(2, 0)]);
+        assert_eq!(drawn_pixels(&horizontal, BLUE.to_colour8()), [(1, 2)]);
+        assert_eq!(horizontal.encoding, ColourEncoding::Srgb);
+
+        let vertical = frame_buffer.flipped_vertical();
+        assert_eq!(drawn_pixels(&vertical, RED.to_colour8()), [(0, 2)]);
+        assert_eq!(drawn_pixels(&vertical, BLUE.to_colour8()), [(1, 0)]);
+
+        // The original is unchanged
+        assert_eq!(drawn_pixels(&frame_buffer, RED.to_colour8()), [(0, 0)]);
+    }
+
+    #[test]
+    fn test_depth_buffer() {
+        let mut depth_buffer = DepthBuffer::new(4, 2);
+        assert!(depth_buffer.buf.iter().all(|depth| *depth == f32::INFINITY));
+
+        depth_buffer.write(3, 1, 2.5).unwrap();
+        assert_eq!(depth_buffer.read(3, 1).unwrap(), 2.5);
+        assert_eq!(depth_buffer.read(0, 0).unwrap(), f32::INFINITY);
+
+        assert!(matches!(depth_buffer.read(4, 0), Err(FrameBufError::PixelOutsideBuf {x: 4, y: 0})));
+        assert!(matches!(depth_buffer.write(0, 2, 1.0), Err(FrameBufError::PixelOutsideBuf {x: 0, y: 2})));
+
+        depth_buffer.clear();
+        assert_eq!(depth_buffer.read(3, 1).unwrap(), f32::INFINITY);
+    }
+
+    #[test]
+    fn test_draw_line_clipped() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.draw_line(-4, 2, 12, 2, RED.to_colour8());
+
+        for x in 0..WIDTH {
+            assert_eq!(pixel(&frame_buffer, x, 2), RED.to_colour8());
+        }
+    }
+
+    #[test]
+    fn test_draw_debug_axes() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.draw_debug_axes(Vec2::new(1, 1), Vec2::new(6, 1), Vec2::new(1, 6), Vec2::new(6, 6));
+
+        assert_eq!(pixel(&frame_buffer, 4, 1), RED.to_colour8());
+        assert_eq!(pixel(&frame_buffer, 1, 4), GREEN.to_colour8());
+        assert_eq!(pixel(&frame_buffer, 4, 4), BLUE.to_colour8());
+    }
+
+    #[test]
+    fn test_draw_wireframe_triangle() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.draw_wireframe_triangle(Vec2::new(1, 1), Vec2::new(6, 1), Vec2::new(1, 6), RED.to_colour8());
+
+        let drawn = |x: usize, y: usize| pixel(&frame_buffer, x, y) == RED.to_colour8();
+
+        // Bottom edge, left edge, and hypotenuse are each a separate run of pixels
+        assert!((1..=6).all(|x| drawn(x, 1)));
+        assert!((1..=6).all(|y| drawn(1, y)));
+        assert!((1..=6).all(|x| drawn(x, 7 - x)));
+
+        // Inside and outside of the triangle are left empty
+        assert!(!drawn(2, 2));
+        assert!(!drawn(3, 2));
+        assert!(!drawn(0, 0));
+        assert!(!drawn(7, 7));
+    }
+
+    #[test]
+    fn test_draw_wireframe_mesh() {
+        use crate::camera::FitResolutionGate;
+        use crate::linear_algebra::{Matrix44, Vec3};
+        use crate::rasterisation::{ColourAttribute, Triangle, Vertex};
+
+        let camera = Camera::new(Matrix44::identity(), Vec2::new(WIDTH as i32, HEIGHT as i32), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill);
+        let attributes = ColourAttribute { colour: RED };
+        let triangle = Triangle::new(
+            Vertex::new(Vec3::new(-1.0, -1.0, 10.0), attributes),
+            Vertex::new(Vec3::new(1.0, -1.0, 10.0), attributes),
+            Vertex::new(Vec3::new(0.0, 1.0, 10.0), attributes),
+        );
+
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.draw_wireframe_mesh(&Mesh::new(vec![triangle]), GREEN.to_colour8(), &camera);
+
+        let mut expected = self::frame_buffer();
+        let project = |vertex: &Vertex<f32>| camera.point_to_raster(&vertex.vertex).unwrap();
+        expected.draw_wireframe_triangle(project(&triangle.v0), project(&triangle.v1), project(&triangle.v2), GREEN.to_colour8());
+
+        assert!(frame_buffer.pixels().any(|(_, _, colour)| colour == GREEN.to_colour8()));
+        assert_eq!(frame_buffer.read_as_slice(), expected.read_as_slice());
+    }
+
+    #[test]
+    fn test_scale_nearest() {
+        let mut frame_buffer = FrameBuffer::new(WIDTH, HEIGHT, vec![0; WIDTH * HEIGHT]);
+        frame_buffer.draw_line(0, 0, 7, 3, RED.to_colour8());
+        frame_buffer.draw_line(2, 7, 5, 0, GREEN.to_colour8());
+        frame_buffer.write_buf(7, 7, &BLUE).unwrap();
+
+        for factor in [1, 2, 4] {
+            let mut expected = FrameBuffer::new(WIDTH * factor, HEIGHT * factor, vec![0; WIDTH * HEIGHT * factor * factor]);
+            frame_buffer.scale(&mut expected, factor).unwrap();
+
+            let scaled = frame_buffer.scale_nearest(factor);
+            assert_eq!((scaled.width_px, scaled.height_px), (WIDTH * factor, HEIGHT * factor));
+            assert_eq!(scaled.read_as_slice(), expected.read_as_slice());
+        }
+    }
+
+    #[test]
+    fn test_scale_nearest_srgb() {
+        // Every byte value in each channel, so scale's decode and encode has to round trip all of them
+        let mut frame_buffer = FrameBuffer::new(16, 16, vec![0; 16 * 16]);
+        frame_buffer.encoding = ColourEncoding::Srgb;
+        frame_buffer.modify_pixels(|x, y, _| {
+            let byte = (y * 16 + x) as u8;
+            Colour8::new(byte, 255 - byte, byte / 2, 255)
+        });
+
+        let mut expected = FrameBuffer::new(32, 32, vec![0; 32 * 32]);
+        expected.encoding = ColourEncoding::Srgb;
+        frame_buffer.scale(&mut expected, 2).unwrap();
+
+        let scaled = frame_buffer.scale_nearest(2);
+        assert_eq!(scaled.encoding, ColourEncoding::Srgb);
+        assert_eq!(scaled.read_as_slice(), expected.read_as_slice());
+    }
+
+    #[test]
+    fn test_clear_with_colour() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&RED.to_colour8());
+
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                assert_eq!(pixel(&frame_buffer, x, y), RED.to_colour8());
+            }
+        }
+
+        // Writes should be visible against the background
+        frame_buffer.write_buf(3, 4, &GREEN).unwrap();
+        assert_eq!(pixel(&frame_buffer, 3, 4), GREEN.to_colour8());
+        assert_eq!(pixel(&frame_buffer, 4, 4), RED.to_colour8());
+
+        frame_buffer.clear_buf();
+        assert!(frame_buffer.pixels().all(|(_, _, colour)| colour == BLANK.to_colour8()));
+    }
+
+    #[test]
+    fn test_clear_with_colour_fill_overrides() {
+        let sky = Colour8::new(135, 206, 235, 255);
+
+        // Vec<u32> fills its storage directly, only the pixels inside the image are touched
+        let mut frame_buffer = FrameBuffer::new(4, 3, vec![7u32; 20]);
+        frame_buffer.clear_with_colour(&sky);
+        assert!(frame_buffer.pixels().all(|(_, _, colour)| colour == sky));
+        assert_eq!(frame_buffer.read_as_slice()[12..], [7; 8]);
+
+        // HDR buffers keep the floating point colour
+        let mut hdr = HdrFrameBuffer::new_hdr(4, 3);
+        let bright = Colour {red: 4.0, green: 2.0, blue: 0.5, alpha: 1.0};
+        hdr.clear_with_colour_f32(&bright);
+        assert!(hdr.buf.iter().all(|colour| colour.approx_eq(&bright, 0.0)));
+
+        // The f32 variant is encoded like write_buf
+        let mut srgb = FrameBuffer::new(2, 2, vec![0u32; 4]);
+        srgb.encoding = ColourEncoding::Srgb;
+        let grey = Colour {red: 0.5, green: 0.5, blue: 0.5, alpha: 1.0};
+        srgb.clear_with_colour_f32(&grey);
+        srgb.write_buf(1, 1, &grey).unwrap();
+        assert!(srgb.read_as_slice().iter().all(|&word| word == srgb.read_as_slice()[0]));
+        assert_eq!(Colour8::from_u32(srgb.read_as_slice()[0]).red, 188);
+    }
+
+    #[test]
+    fn test_slice_round_trip() {
+        let mut frame_buffer = frame_buffer();
+        let data: Vec<u32> = (0..(WIDTH * HEIGHT) as u32).map(|i| i * 0x010203).collect();
+
+        frame_buffer.write_from_slice(&data).unwrap();
+        assert_eq!(frame_buffer.read_as_slice(), data.as_slice());
+
+        frame_buffer.read_as_slice_mut()[0] = 42;
+        assert_eq!(frame_buffer.read_as_slice()[0], 42);
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.write_buf(2, 5, &RED).unwrap();
+
+        let pixels: Vec<(usize, usize, Colour8)> = frame_buffer.pixels().collect();
+        assert_eq!(pixels.len(), WIDTH * HEIGHT);
+
+        // Scan order starts at the top left of the image
+        assert_eq!((pixels[0].0, pixels[0].1), (0, HEIGHT - 1));
+        assert_eq!((pixels[WIDTH * HEIGHT - 1].0, pixels[WIDTH * HEIGHT - 1].1), (WIDTH - 1, 0));
+
+        for (x, y, colour) in pixels {
+            assert_eq!(colour, pixel(&frame_buffer, x, y));
+        }
+    }
+
+    #[test]
+    fn test_pixels_channel_sum() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.modify_pixels(|x, y, _| Colour8::new((x * 30) as u8, (y * 30) as u8, (x * y) as u8, 255));
+
+        let channel_sum = |colour: Colour8| colour.red as u32 + colour.green as u32 + colour.blue as u32 + colour.alpha as u32;
+        let iterated: u32 = frame_buffer.pixels().map(|(_, _, colour)| channel_sum(colour)).sum();
+
+        let mut read = 0;
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                read += channel_sum(pixel(&frame_buffer, x, y));
+            }
+        }
+        assert_eq!(iterated, read);
+    }
+
+    #[test]
+    fn test_modify_pixels() {
+        // HDR buffers can't use pixels_mut but can be modified through the buffer trait
+        let mut frame_buffer = FrameBuffer::new_hdr(4, 2);
+        frame_buffer.modify_pixels(|x, y, colour| {
+            assert_eq!(colour, BLANK.to_colour8());
+            if y == 1 {Colour8::new(x as u8, 0, 0, 255)} else {colour}
+        });
+
+        for (x, y, colour) in frame_buffer.pixels() {
+            let expected = if y == 1 {Colour8::new(x as u8, 0, 0, 255)} else {BLANK.to_colour8()};
+            assert_eq!(colour, expected);
+        }
+    }
+
+    #[test]
+    fn test_pixels_mut() {
+        let mut frame_buffer = frame_buffer();
+
+        let mut count = 0;
+        for (x, _, mut colour) in frame_buffer.pixels_mut() {
+            if x % 2 == 0 {
+                *colour = RED.to_colour8();
+            }
+            count += 1;
+        }
+        assert_eq!(count, WIDTH * HEIGHT);
+
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                let expected = if x % 2 == 0 {RED.to_colour8()} else {BLANK.to_colour8()};
+                assert_eq!(pixel(&frame_buffer, x, y).to_u32(), expected.to_u32());
+            }
+        }
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut frame_buffer = frame_buffer();
+        assert_eq!(frame_buffer.coverage(), 0.0);
+
+        frame_buffer.draw_line(0, 0, WIDTH as i32 - 1, 0, RED.to_colour8());
+        assert_eq!(frame_buffer.coverage(), 1.0 / HEIGHT as f32);
+
+        frame_buffer.clear_with_colour(&BLACK.to_colour8());
+        assert_eq!(frame_buffer.coverage(), 1.0);
+    }
+
+    #[test]
+    fn test_histogram_luminance() {
+        let mut frame_buffer = FrameBuffer::new_hdr(WIDTH, HEIGHT);
+        frame_buffer.write_buf(0, 0, &WHITE).unwrap();
+        frame_buffer.write_buf(1, 0, &Colour {red: 4.0, green: 4.0, blue: 4.0, alpha: 1.0}).unwrap();
+        frame_buffer.write_buf(2, 0, &Colour {red: 0.3, green: 0.3, blue: 0.3, alpha: 1.0}).unwrap();
+
+        let histogram = frame_buffer.histogram_luminance(4);
+        assert_eq!(histogram, vec![WIDTH * HEIGHT - 3, 1, 0, 2]);
+        assert_eq!(histogram.iter().sum::<usize>(), WIDTH * HEIGHT);
+
+        assert!(frame_buffer.histogram_luminance(0).is_empty());
+    }
+
+    #[test]
+    fn test_pixel_outside_buf() {
+        let mut frame_buffer = frame_buffer();
+
+        match frame_buffer.write_buf(WIDTH, 0, &RED) {
+            Err(error @ FrameBufError::PixelOutsideBuf {x, y}) => {
+                assert_eq!((x, y), (WIDTH, 0));
+                assert_eq!(error.to_string(), format!("Pixel ({}, 0) is outside of the frame buffer", WIDTH));
+            },
+            _ => panic!("Writing outside of the frame buffer should fail"),
+        }
+
+        assert!(matches!(frame_buffer.read_buf(2, HEIGHT), Err(FrameBufError::PixelOutsideBuf {x: 2, y: HEIGHT})));
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(FrameBuffer::try_new(WIDTH, HEIGHT, [0u32; WIDTH * HEIGHT]).is_ok());
+        assert!(matches!(FrameBuffer::try_new(WIDTH, HEIGHT, [0u32; WIDTH * HEIGHT - 1]), Err(FrameBufError::SizeMismatch)));
+        assert!(FrameBuffer::try_new(WIDTH, HEIGHT, vec![0u32; 10]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_undersized() {
+        FrameBuffer::new(WIDTH, HEIGHT, vec![BLANK; WIDTH]);
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        let (width, height) = (256, 256);
+        let buf: Vec<u32> = (0..width * height).map(|i| (i as u32).wrapping_mul(2654435761)).collect();
+        let frame_buffer = FrameBuffer::new(width, height, buf);
+
+        let path = std::env::temp_dir().join("raster_test_raw_round_trip.raw");
+        frame_buffer.save_raw(&path).unwrap();
+        let loaded = FrameBuffer::load_raw(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((loaded.width_px, loaded.height_px), (width, height));
+        assert_eq!(loaded.read_as_slice(), frame_buffer.read_as_slice());
+    }
+
+    #[test]
+    fn test_load_raw_big_endian() {
+        let mut data = b"RFBB".to_vec();
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&0x11223344u32.to_be_bytes());
+        data.extend_from_slice(&0xaabbccddu32.to_be_bytes());
+
+        let path = std::env::temp_dir().join("raster_test_load_raw_big_endian.raw");
+        std::fs::write(&path, &data).unwrap();
+        let loaded = FrameBuffer::load_raw(&path).unwrap();
+
+        // Truncated files are rejected
+        std::fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(FrameBuffer::load_raw(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((loaded.width_px, loaded.height_px), (1, 2));
+        assert_eq!(loaded.read_as_slice(), [0x11223344, 0xaabbccdd]);
+    }
+
+    #[test]
+    fn test_load_raw_overflowing_size() {
+        let mut data = b"RFBL".to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+
+        let path = std::env::temp_dir().join("raster_test_load_raw_overflowing_size.raw");
+        std::fs::write(&path, &data).unwrap();
+        let result = FrameBuffer::load_raw(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.err().map(|error| error.kind()), Some(std::io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn test_vec_frame_buffer() {
+        let size = 256;
+        let mut frame_buffer = FrameBuffer::new(size, size, vec![0u32; size * size]);
+
+        let corners = [(0, 0), (size - 1, 0), (0, size - 1), (size - 1, size - 1), (100, 37)];
+        for (i, &(x, y)) in corners.iter().enumerate() {
+            let colour = Colour8::new(i as u8 * 40, 255 - i as u8, x as u8, 255);
+            frame_buffer.write_buf(x, y, &colour.to_colour()).unwrap();
+            assert_eq!(pixel(&frame_buffer, x, y), colour);
+        }
+
+        // Out of bounds writes are rejected rather than growing the vector
+        assert!(frame_buffer.write_buf(size, 0, &RED).is_err());
+        assert_eq!(frame_buffer.buf.len(), size * size);
+
+        frame_buffer.clear_buf();
+        assert!(frame_buffer.read_as_slice().iter().all(|&word| word == 0));
+    }
+
+    #[test]
+    fn test_save_ppm() {
+        let mut frame_buffer = FrameBuffer::new(4, 4, [0u32; 16]);
+        frame_buffer.clear_with_colour(&Colour8::new(10, 20, 30, 255));
+        frame_buffer.write_buf(0, 0, &RED).unwrap();
+        frame_buffer.write_buf(3, 3, &GREEN).unwrap();
+        frame_buffer.write_buf(1, 2, &Colour {alpha: 0.5, ..BLUE}).unwrap();
+
+        let path = std::env::temp_dir().join("raster_test_save_ppm.ppm");
+        frame_buffer.save_ppm(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"P6\n4 4\n255\n";
+        assert_eq!(&data[..header.len()], header);
+        let pixels = &data[header.len()..];
+        assert_eq!(pixels.len(), 4 * 4 * 3);
+
+        // Reading the rows back from the top, every pixel matches the frame buffer without its alpha
+        for (i, rgb) in pixels.chunks_exact(3).enumerate() {
+            let (x, y) = (i % 4, 3 - i / 4);
+            let expected = pixel(&frame_buffer, x, y);
+            assert_eq!(rgb, [expected.red, expected.green, expected.blue], "pixel ({}, {})", x, y);
+        }
+
+        // The top right pixel comes at the end of the first row and the bottom left at the start of the last
+        assert_eq!(&pixels[9..12], [0, 255, 0]);
+        assert_eq!(&pixels[36..39], [255, 0, 0]);
+    }
+
+    #[test]
+    fn test_save_tga() {
+        let mut frame_buffer = FrameBuffer::new(3, 2, [0u32; 6]);
+        frame_buffer.clear_with_colour(&Colour8::new(10, 20, 30, 255));
+        frame_buffer.write_buf(0, 0, &RED).unwrap();
+        frame_buffer.write_buf(2, 1, &Colour {alpha: 0.0, ..GREEN}).unwrap();
+
+        let path = std::env::temp_dir().join("raster_test_save_tga.tga");
+        frame_buffer.save_tga(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+
+        assert_eq!(data.len(), 3 * 2 * 3 + 18);
+        assert_eq!(data[2], 2);
+        assert_eq!(&data[12..18], [3, 0, 2, 0, 24, 0]);
+
+        // The first pixel is the bottom left, stored as BGR
+        assert_eq!(&data[18..21], [0, 0, 255]);
+        assert_eq!(&data[21..24], [30, 20, 10]);
+        assert_eq!(&data[data.len() - 3..], [0, 255, 0]);
+
+        frame_buffer.save_tga_with_alpha(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.len(), 3 * 2 * 4 + 18);
+        assert_eq!(&data[16..18], [32, 8]);
+        assert_eq!(&data[18..22], [0, 0, 255, 255]);
+        assert_eq!(&data[data.len() - 4..], [0, 255, 0, 0]);
+    }
+
+    #[test]
+    fn test_save_tga_srgb() {
+        let mut frame_buffer = FrameBuffer::new(2, 1, [0u32; 2]);
+        frame_buffer.encoding = ColourEncoding::Srgb;
+        frame_buffer.clear_with_colour(&Colour8::new(188, 124, 231, 255));
+        frame_buffer.write_buf(1, 0, &Colour {red: 0.5, green: 0.2, blue: 0.8, alpha: 1.0}).unwrap();
+
+        let path = std::env::temp_dir().join("raster_test_save_tga_srgb.tga");
+        frame_buffer.save_tga(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The stored, already encoded, bytes are written like save_raw and save_png do
+        let stored: Vec<u8> = frame_buffer.pixels()
+            .flat_map(|(_, _, colour)| [colour.blue, colour.green, colour.red])
+            .collect();
+        assert_eq!(&data[18..], stored);
+        assert_eq!(&data[18..21], [231, 124, 188]);
+    }
+
+    #[test]
+    fn test_save_tga_too_large() {
+        let frame_buffer = FrameBuffer::new(70000, 1, vec![0u32; 70000]);
+        let path = std::env::temp_dir().join("raster_test_save_tga_too_large.tga");
+
+        let error = frame_buffer.save_tga(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_save_png() {
+        let mut frame_buffer = FrameBuffer::new(2, 2, [0u32; 4]);
+        frame_buffer.write_buf(0, 0, &RED).unwrap();
+        frame_buffer.write_buf(1, 0, &GREEN).unwrap();
+        frame_buffer.write_buf(0, 1, &BLUE).unwrap();
+
+        let path = std::env::temp_dir().join("raster_test_save_png.png");
+        frame_buffer.save_png(&path).unwrap();
+
+        let decoder = png::Decoder::new(std::io::BufReader::new(std::fs::File::open(&path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut data).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Top row first, the top right pixel was never written so it is blank
+        assert_eq!(data, [
+            0, 0, 255, 255,  0, 0, 0, 0,
+            255, 0, 0, 255,  0, 255, 0, 255,
+        ]);
+    }
+
+    #[test]
+    fn test_tone_map_to_ldr() {
+        let mut hdr = HdrFrameBuffer::new_hdr(2, 2);
+        let radiance = Colour {red: 3.0, green: 0.0, blue: 0.0, alpha: 1.0};
+        hdr.write_buf(1, 1, &radiance).unwrap();
+
+        // The HDR buffer keeps values above 1
+        assert_eq!(hdr.read_buf(1, 1).unwrap().red, 3.0);
+
+        let clamped = hdr.tone_map_to_ldr(ToneMapOp::Clamp);
+        assert_eq!(pixel(&clamped, 1, 1), RED.to_colour8());
+
+        // Reinhard maps 3 to 3 / 4
+        let reinhard = hdr.tone_map_to_ldr(ToneMapOp::Reinhard);
+        assert_eq!(pixel(&reinhard, 1, 1), Colour8::new(191, 0, 0, 255));
+        assert_eq!(pixel(&reinhard, 0, 0), BLANK.to_colour8());
+    }
+
+    #[test]
+    fn test_srgb_encoding() {
+        let grey = Colour {red: 0.2176, green: 0.2176, blue: 0.2176, alpha: 1.0};
+
+        let mut linear = frame_buffer();
+        linear.write_buf(0, 0, &grey).unwrap();
+        assert_eq!(linear.pixels().find(|(x, y, _)| (*x, *y) == (0, 0)).unwrap().2, Colour8::new(55, 55, 55, 255));
+
+        let mut srgb = frame_buffer();
+        srgb.encoding = ColourEncoding::Srgb;
+        srgb.write_buf(0, 0, &grey).unwrap();
+        assert_eq!(srgb.pixels().find(|(x, y, _)| (*x, *y) == (0, 0)).unwrap().2, Colour8::new(128, 128, 128, 255));
+
+        // Reading decodes the stored colour back to linear
+        assert!(srgb.read_buf(0, 0).unwrap().approx_eq(&grey, 1e-2));
+    }
+
+    #[test]
+    fn test_apply_palette() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.write_buf(1, 1, &Colour {red: 0.9, green: 0.2, blue: 0.1, alpha: 1.0}).unwrap();
+        frame_buffer.write_buf(2, 2, &Colour {red: 0.1, green: 0.2, blue: 0.8, alpha: 1.0}).unwrap();
+
+        let palette = Palette::new(vec![BLANK, RED, GREEN, BLUE]);
+        let palettised = frame_buffer.apply_palette(&palette);
+
+        assert_eq!(pixel(&palettised, 1, 1), RED.to_colour8());
+        assert_eq!(pixel(&palettised, 2, 2), BLUE.to_colour8());
+        assert_eq!(pixel(&palettised, 0, 0), BLANK.to_colour8());
+    }
+
+    #[test]
+    fn test_write_from_slice_wrong_length() {
+        let mut frame_buffer = frame_buffer();
+
+        assert!(frame_buffer.write_from_slice(&[0; 3]).is_err());
+    }
+
+    #[test]
+    fn test_crop() {
+        // 16x16 buffer where every pixel has a unique colour
+        let size = 16;
+        let mut frame_buffer = FrameBuffer::new(size, size, vec![0u32; size * size]);
+        for x in 0..size {
+            for y in 0..size {
+                let colour = Colour8::new(x as u8 * 16, y as u8 * 16, 0, 255);
+                frame_buffer.write_buf(x, y, &colour.to_colour()).unwrap();
+            }
+        }
+
+        let cropped = frame_buffer.crop(6, 6, 4, 4).unwrap();
+        assert_eq!((cropped.width_px, cropped.height_px), (4, 4));
+
+        for x in 0..4 {
+            for y in 0..4 {
+                assert_eq!(pixel(&cropped, x, y), pixel(&frame_buffer, x + 6, y + 6));
+            }
+        }
+    }
+
+    #[test]
+    fn test_crop_outside_buffer() {
+        let frame_buffer = frame_buffer();
+
+        assert!(frame_buffer.crop(6, 0, 4, 4).is_err());
+        assert!(frame_buffer.crop(0, 0, WIDTH, HEIGHT + 1).is_err());
+        assert!(frame_buffer.crop(0, 0, WIDTH, HEIGHT).is_ok());
+
+        // The end of the region overflows rather than landing back inside the buffer
+        assert!(frame_buffer.crop(usize::MAX, 0, 2, 1).is_err());
+        assert!(frame_buffer.crop(0, 1, 1, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_draw_debug_grid() {
+        let size = 100;
+        let mut frame_buffer = FrameBuffer::new(size, size, vec![0u32; size * size]);
+        let colour = Colour8::new(80, 80, 80, 255);
+        frame_buffer.draw_debug_grid(10, colour);
+
+        let full_columns = (0..size).filter(|x| (0..size).all(|y| pixel(&frame_buffer, *x, y) == colour)).count();
+        let full_rows = (0..size).filter(|y| (0..size).all(|x| pixel(&frame_buffer, x, *y) == colour)).count();
+        assert_eq!(full_columns, 9);
+        assert_eq!(full_rows, 9);
+        assert_eq!(pixel(&frame_buffer, 5, 5), BLANK.to_colour8());
+    }
+}
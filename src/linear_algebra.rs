@@ -1,6 +1,7 @@
 //This file contains definitions and implementations for vector and matrix types 
 
-use crate::num::Num;
+use crate::num::{Num, FloatNum};
+use crate::quaternion::Quaternion;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec2<T: Num> {
@@ -11,24 +12,134 @@ pub struct Vec2<T: Num> {
 impl<T: Num> Vec2<T> {
     pub fn new(x: T, y: T) -> Self {
         Vec2 {
-            x: x,
-            y: y,
+            x,
+            y,
         }
     }
     
     pub fn splat(d: T) -> Self {
         Vec2 {
-            x: d.clone(),
-            y: d.clone(),
+            x: d,
+            y: d,
         }
     }
 
     pub fn from_vec3(vec: &Vec3<T>) -> Self {
         Vec2 {
-            x: vec.x.clone(),
-            y: vec.y.clone(),
+            x: vec.x,
+            y: vec.y,
         }
     }
+
+    // Does vector dot product with another vector
+    pub fn dot(&self, v: &Vec2<T>) -> T {
+        self.x * v.x + self.y * v.y
+    }
+
+    // Returns the vector rotated 90 degrees counterclockwise
+    // The dot product of a with b.perp() is the 2D cross product, which gives the side of a line a point is on
+    pub fn perp(&self) -> Vec2<T> {
+        Vec2::new(-self.y, self.x)
+    }
+
+    // Returns vector 2 norm
+    pub fn len(&self) -> T where T: FloatNum {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    // Makes vector length 1
+    pub fn normalise(&mut self) where T: FloatNum {
+        let normalisation_constant: T = T::from_f32(1.0) / self.len();
+
+        self.x = self.x * normalisation_constant;
+        self.y = self.y * normalisation_constant;
+    }
+
+    // Linearly interpolates between a and b, t is not clamped so values outside [0, 1] extrapolate
+    pub fn lerp(a: &Vec2<T>, b: &Vec2<T>, t: T) -> Vec2<T> where T: FloatNum {
+        *a * (T::from_f32(1.0) - t) + *b * t
+    }
+}
+
+// Component wise arithmetic, matching the operators on Vec3
+impl<T: Num> std::ops::Add for Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl<T: Num> std::ops::Add for &Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        *self + *rhs
+    }
+}
+
+impl<T: Num> std::ops::Sub for Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl<T: Num> std::ops::Sub for &Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        *self - *rhs
+    }
+}
+
+impl<T: Num> std::ops::Neg for Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn neg(self) -> Self::Output {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl<T: Num> std::ops::AddAssign for Vec2<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T: Num> std::ops::SubAssign for Vec2<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T: Num> std::ops::Mul<T> for Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl<T: Num + std::ops::Div<Output = T>> std::ops::Div<T> for Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn div(self, rhs: T) -> Self::Output {
+        Vec2::new(self.x / rhs, self.y / rhs)
+    }
+}
+
+impl<T: Num> std::ops::MulAssign<T> for Vec2<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
+    }
+}
+
+impl<T: Num + std::ops::Div<Output = T>> std::ops::DivAssign<T> for Vec2<T> {
+    fn div_assign(&mut self, rhs: T) {
+        *self = *self / rhs;
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -41,25 +152,25 @@ pub struct Vec3<T: Num> {
 impl<T: Num> Vec3<T> {
     pub fn new(x: T, y: T, z: T) -> Self {
         Vec3 {
-            x: x,
-            y: y,
-            z: z,
+            x,
+            y,
+            z,
         }
     }
     
     pub fn splat(d: T) -> Self {
         Vec3 {
-            x: d.clone(),
-            y: d.clone(),
-            z: d.clone(),
+            x: d,
+            y: d,
+            z: d,
         }
     }
 
     pub fn from_vec2(vec: &Vec2<T>, z: T) -> Self {
         Vec3 {
-            x: vec.x.clone(),
-            y: vec.y.clone(),
-            z: z.clone(),
+            x: vec.x,
+            y: vec.y,
+            z,
         }
     }
 
@@ -78,13 +189,13 @@ impl<T: Num> Vec3<T> {
     }
 
     // Returns vector 2 norm
-    pub fn len(&self) -> T where T: From<f32> + Into<f32> {
-       f32::sqrt((self.x * self.x + self.y * self.y + self.z * self.z).into()).into()
+    pub fn len(&self) -> T where T: FloatNum {
+       (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
 
     // Makes vector length 1
-    pub fn normalise(&mut self) where T: From<f32> + Into<f32> {
-        let normalisation_constant: T = (1.0 / self.len().into()).into();
+    pub fn normalise(&mut self) where T: FloatNum {
+        let normalisation_constant: T = T::from_f32(1.0) / self.len();
         
         self.x = self.x * normalisation_constant;
         self.y = self.y * normalisation_constant;
@@ -92,192 +203,2129 @@ impl<T: Num> Vec3<T> {
     }
 
     // Multiply matrices [1x3] x [3x3] = [1x3]
-    // The Matrix44 is treated as a 3x3 matrix to perform this multiplication
-    pub fn mult_matrix(&self, matrix: &Matrix44) -> Vec3<T> where T: From<f32> + Into<f32> {
-        let mut vec_array: [f32; 3] = [0.0; 3];
+    // Only the upper left 3x3 of the Matrix44 is used, the same as mult_matrix33 with Matrix33::from_matrix44_upper_left
+    pub fn mult_matrix(&self, matrix: &Matrix44) -> Vec3<T> where T: FloatNum {
+        self.mult_matrix33(&Matrix33::from_matrix44_upper_left(matrix))
+    }
+
+    // Multiply matrices [1x3] x [3x3] = [1x3]
+    pub fn mult_matrix33(&self, matrix: &Matrix33) -> Vec3<T> where T: FloatNum {
+        let m = |i: usize, j: usize| T::from_f32(matrix.0[i][j]);
+
+        let mut product = Vec3::splat(T::from_f32(0.0));
         for i in 0..3 {
-            vec_array[i] = self.x.into() * matrix.0[0][i] +
-                           self.y.into() * matrix.0[1][i] +
-                           self.z.into() * matrix.0[2][i];
+            for j in 0..3 {
+                product[i] = product[i] + self[j] * m(j, i);
+            }
         }
 
-        Vec3::new(vec_array[0].into(), vec_array[1].into(), vec_array[2].into())
+        product
     }
 
     // Multiply matrices [1x4] x [4x4] = [1x4]
     // The homogenous coordinate of the input vector is implied to be one
     // The homogeneous output coordinates are normalised so a Vec3 can be returned
-    pub fn homogeneous_mult_matrix(&self, matrix: &Matrix44) -> Vec3<T> where T: From<f32> + Into<f32> {
-        let mut vec_array: [f32; 4] = [0.0; 4];
-        for i in 0..4 {
-            vec_array[i] = self.x.into() * matrix.0[0][i] +
-                           self.y.into() * matrix.0[1][i] +
-                           self.z.into() * matrix.0[2][i] +
-                        /* implicit 1 * */ matrix.0[3][i];
-        }
+    pub fn homogeneous_mult_matrix(&self, matrix: &Matrix44) -> Vec3<T> where T: FloatNum {
+        let m = |i: usize, j: usize| T::from_f32(matrix.0[i][j]);
+
+        let vec_array: [T; 4] = std::array::from_fn(|i| {
+            self.x * m(0, i) + self.y * m(1, i) + self.z * m(2, i) + /* implicit 1 * */ m(3, i)
+        });
 
         // Convert homogeneous coordinates back to cartesian
-        vec_array[0] /= vec_array[3];
-        vec_array[1] /= vec_array[3];
-        vec_array[2] /= vec_array[3];
+        Vec3::new(vec_array[0] / vec_array[3], vec_array[1] / vec_array[3], vec_array[2] / vec_array[3])
+    }
+
+    // Returns the component wise minimum of two vectors
+    pub fn component_min(&self, other: &Vec3<T>) -> Vec3<T> {
+        let min = |a: T, b: T| if b < a { b } else { a };
+        Vec3::new(min(self.x, other.x), min(self.y, other.y), min(self.z, other.z))
+    }
+
+    // Returns the component wise maximum of two vectors
+    pub fn component_max(&self, other: &Vec3<T>) -> Vec3<T> {
+        let max = |a: T, b: T| if b > a { b } else { a };
+        Vec3::new(max(self.x, other.x), max(self.y, other.y), max(self.z, other.z))
+    }
+
+    // Returns the absolute value of each component
+    pub fn abs(&self) -> Vec3<T> {
+        let abs = |a: T| if a < -a { -a } else { a };
+        Vec3::new(abs(self.x), abs(self.y), abs(self.z))
+    }
+
+    // Restricts each component to the matching components of min and max, e.g. to keep a position inside a view volume
+    pub fn clamp(&self, min: &Vec3<T>, max: &Vec3<T>) -> Vec3<T> {
+        Vec3::new(
+            Num::clamp(self.x, min.x, max.x),
+            Num::clamp(self.y, min.y, max.y),
+            Num::clamp(self.z, min.z, max.z),
+        )
+    }
+
+    // Rounds each component down
+    pub fn floor(&self) -> Vec3<T> where T: FloatNum {
+        Vec3::new(self.x.floor(), self.y.floor(), self.z.floor())
+    }
+
+    // Rounds each component up
+    pub fn ceil(&self) -> Vec3<T> where T: FloatNum {
+        Vec3::new(self.x.ceil(), self.y.ceil(), self.z.ceil())
+    }
+
+    // Linearly interpolates between a and b, t is clamped to [0, 1] so the result always lies between a and b
+    // Prefer this for animation where t may overshoot
+    pub fn lerp_clamped(a: &Vec3<T>, b: &Vec3<T>, t: T) -> Vec3<T> where T: FloatNum {
+        let zero = T::from_f32(0.0);
+        let one = T::from_f32(1.0);
+
+        let t = if t < zero { zero } else if t > one { one } else { t };
+        Vec3::lerp_unclamped(a, b, t)
+    }
 
-        Vec3::new(vec_array[0].into(), vec_array[1].into(), vec_array[2].into())
+    // Linearly interpolates between a and b, t is not clamped so values outside [0, 1] extrapolate
+    // Weighting both ends rather than computing a + (b - a) * t means t = 0 and t = 1 return a and b exactly
+    pub fn lerp(a: &Vec3<T>, b: &Vec3<T>, t: T) -> Vec3<T> where T: FloatNum {
+        *a * (T::from_f32(1.0) - t) + *b * t
+    }
+
+    // Linearly interpolates between a and b without clamping t, the same as lerp
+    // Values of t outside [0, 1] extrapolate along the line through a and b, e.g. t = 2 returns b + (b - a)
+    pub fn lerp_unclamped(a: &Vec3<T>, b: &Vec3<T>, t: T) -> Vec3<T> where T: FloatNum {
+        Vec3::lerp(a, b, t)
     }
 }
 
-type MatrixArray = [[f32; 4]; 4];
-const ZERO_MATRIX: MatrixArray = [
-    [0.0, 0.0, 0.0, 0.0],
-    [0.0, 0.0, 0.0, 0.0],
-    [0.0, 0.0, 0.0, 0.0],
-    [0.0, 0.0, 0.0, 0.0],
-];
+impl Vec3<f32> {
+    // Returns a tangent and bitangent which form a right handed orthonormal basis with a unit length normal
+    // tangent x bitangent = normal
+    // Uses the branchless method from Duff et al. 2017, "Building an Orthonormal Basis, Revisited"
+    pub fn tangent_frame(normal: &Vec3<f32>) -> (Vec3<f32>, Vec3<f32>) {
+        let sign = 1.0f32.copysign(normal.z);
+        let a = -1.0 / (sign + normal.z);
+        let b = normal.x * normal.y * a;
 
-#[derive(Debug, PartialEq)]
-pub struct Matrix44(MatrixArray);
+        let tangent = Vec3::new(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x);
+        let bitangent = Vec3::new(b, sign + normal.y * normal.y * a, -normal.y);
 
-// Overload for matrix multiplication
-impl std::ops::Mul for Matrix44 {
-    type Output = Matrix44;
+        (tangent, bitangent)
+    }
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        let mut m: MatrixArray = ZERO_MATRIX;
+    // Reflects the incident direction about the surface normal
+    // Both vectors must be unit length, the incident direction points towards the surface
+    pub fn reflect(incident: &Vec3<f32>, normal: &Vec3<f32>) -> Vec3<f32> {
+        *incident - *normal * 2.0 * incident.dot(normal)
+    }
 
-        for i in 0..4 { 
-            for j in 0..4 {
-                m[i][j] = self.0[i][0] * rhs.0[0][j] +
-                          self.0[i][1] * rhs.0[1][j] +
-                          self.0[i][2] * rhs.0[2][j] +
-                          self.0[i][3] * rhs.0[3][j];
-            }
+    // Refracts the incident direction through a surface using Snell's law
+    // Both vectors must be unit length, the incident direction points towards the surface and the normal points against it
+    // eta is the ratio of refractive indices, the index on the incident side over the index on the far side
+    // Returns None when the angle is too shallow for light to pass through (total internal reflection)
+    pub fn refract(incident: &Vec3<f32>, normal: &Vec3<f32>, eta: f32) -> Option<Vec3<f32>> {
+        let cos_incident = normal.dot(incident);
+        let k = 1.0 - eta * eta * (1.0 - cos_incident * cos_incident);
+        if k < 0.0 {
+            return None;
         }
 
-        Matrix44::new(m)
+        Some(*incident * eta - *normal * (eta * cos_incident + k.sqrt()))
     }
-}
 
-impl Matrix44 {
-    // Construct matrix from matrix array
-    pub fn new(matrix_array: MatrixArray) -> Self {
-        Matrix44(matrix_array)
+    // Returns the angle between two vectors in radians, in the range [0, pi]
+    // Zero length vectors have no direction so the angle is reported as zero
+    pub fn angle_between(&self, v: &Vec3<f32>) -> f32 {
+        let lengths = self.len() * v.len();
+        if lengths == 0.0 {
+            return 0.0;
+        }
+
+        // Rounding can push the cosine slightly outside [-1, 1] for (anti)parallel vectors, which acos turns into NaN
+        (self.dot(v) / lengths).clamp(-1.0, 1.0).acos()
     }
 
-    // Return new identity matrix
-    pub fn identity() -> Self {
-        Matrix44 ([
-            [1.0, 0.0, 0.0, 0.0],
-            [0.0, 1.0, 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ])
+    // Projects this vector onto another
+    // Returns the signed length of the projection along v and the projected vector
+    // Projecting onto a zero length vector gives zero for both
+    pub fn project_onto(&self, v: &Vec3<f32>) -> (f32, Vec3<f32>) {
+        let v_len = v.len();
+        if v_len == 0.0 {
+            return (0.0, Vec3::splat(0.0));
+        }
+
+        let scalar = self.dot(v) / v_len;
+        (scalar, *v * (scalar / v_len))
     }
 
-    // Return the transpose of the current matrix
-    pub fn transpose(&self) -> Self {
-        let mut m: MatrixArray = ZERO_MATRIX;
-        for i in 0..4 {
-            for j in 0..4 {
-                m[i][j] = self.0[j][i];
-            }
+    // Returns the euclidean distance between two positions
+    pub fn distance(&self, v: &Vec3<f32>) -> f32 {
+        (*self - *v).len()
+    }
+
+    // Spherical coordinates use the physics convention
+    // theta is the polar angle measured from +z in [0, pi], phi is the azimuth measured from +x towards +y
+    // Returns the point at the given radius and angles
+    pub fn from_spherical(radius: f32, theta: f32, phi: f32) -> Vec3<f32> {
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        Vec3::new(radius * sin_theta * cos_phi, radius * sin_theta * sin_phi, radius * cos_theta)
+    }
+
+    // Returns (radius, theta, phi) using the same convention as from_spherical, phi is in [-pi, pi]
+    // The angles are undefined for the zero vector so it gives (0, 0, 0), points on the z axis have phi = 0
+    pub fn to_spherical(&self) -> (f32, f32, f32) {
+        let radius = self.len();
+        if radius == 0.0 {
+            return (0.0, 0.0, 0.0);
         }
-        Matrix44::new(m)
+
+        let theta = (self.z / radius).clamp(-1.0, 1.0).acos();
+        let phi = self.y.atan2(self.x);
+
+        (radius, theta, phi)
+    }
+
+    // Returns the component wise minimum of two vectors, see Vec3::component_min
+    pub fn min_component_pair(a: &Vec3<f32>, b: &Vec3<f32>) -> Vec3<f32> {
+        a.component_min(b)
+    }
+
+    // Returns the component wise maximum of two vectors, see Vec3::component_max
+    pub fn max_component_pair(a: &Vec3<f32>, b: &Vec3<f32>) -> Vec3<f32> {
+        a.component_max(b)
+    }
+
+    // Returns the smallest of x, y, and z
+    pub fn min_component(&self) -> f32 {
+        self.x.min(self.y).min(self.z)
+    }
+
+    // Returns the largest of x, y, and z
+    pub fn max_component(&self) -> f32 {
+        self.x.max(self.y).max(self.z)
+    }
+
+    // Returns the point with barycentric coordinates (l0, l1, l2) in the triangle v0, v1, v2
+    pub fn barycentric_to_cartesian(l0: f32, l1: f32, l2: f32, v0: &Vec3<f32>, v1: &Vec3<f32>, v2: &Vec3<f32>) -> Vec3<f32> {
+        Vec3::new(
+            l0 * v0.x + l1 * v1.x + l2 * v2.x,
+            l0 * v0.y + l1 * v1.y + l2 * v2.y,
+            l0 * v0.z + l1 * v1.z + l2 * v2.z,
+        )
+    }
+
+    // Returns the barycentric coordinates of p in the triangle v0, v1, v2
+    // Coordinates are found in the xy plane and z is ignored, so this works on raster space triangles where z holds depth
+    // The coordinates sum to one and are all positive when p is inside the triangle, degenerate triangles give non finite values
+    pub fn cartesian_to_barycentric(p: &Vec3<f32>, v0: &Vec3<f32>, v1: &Vec3<f32>, v2: &Vec3<f32>) -> (f32, f32, f32) {
+        // Twice the signed area of the triangle a, b, c
+        let double_area = |a: &Vec3<f32>, b: &Vec3<f32>, c: &Vec3<f32>| (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+
+        let total = double_area(v0, v1, v2);
+        let l0 = double_area(p, v1, v2) / total;
+        let l1 = double_area(v0, p, v2) / total;
+
+        (l0, l1, 1.0 - l0 - l1)
     }
 }
 
-#[cfg(test)]
-mod vec3_tests {
-    use super::*;
+// Component wise arithmetic, each operator is implemented for owned vectors and references
+impl<T: Num> std::ops::Add for Vec3<T> {
+    type Output = Vec3<T>;
 
-    #[test]
-    fn test_dot() {
-        let v1 = Vec3::new(3.0, 4.0, 5.0);
-        let v2 = Vec3::new(4.0, 2.0, 5.0);
+    fn add(self, rhs: Self) -> Self::Output {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
 
-        let dot = v1.dot(&v2);
-        assert_eq!(dot, 45.0);
+impl<T: Num> std::ops::Add for &Vec3<T> {
+    type Output = Vec3<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        *self + *rhs
     }
-    
-    #[test]
-    fn test_cross() {
-        let v1 = Vec3::new(1.0, 2.0, 3.0);
-        let v2 = Vec3::new(4.0, 5.0, 6.0);
+}
 
-        let cross = v1.cross(&v2);
-        assert_eq!(cross, Vec3::new(-3.0, 6.0, -3.0));
+impl<T: Num> std::ops::Sub for Vec3<T> {
+    type Output = Vec3<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
     }
-    
-    #[test]
-    fn test_len() {
-        let v = Vec3::new(1.0, 2.0, 3.0);
+}
+
+impl<T: Num> std::ops::Sub for &Vec3<T> {
+    type Output = Vec3<T>;
 
-        assert_eq!(v.len(), 3.741657387);
+    fn sub(self, rhs: Self) -> Self::Output {
+        *self - *rhs
     }
+}
 
-    #[test]
-    fn test_normalise() {
-        let mut v = Vec3::new(1.0, 2.0, 3.0);
-        v.normalise();
+impl<T: Num> std::ops::Neg for Vec3<T> {
+    type Output = Vec3<T>;
 
-        assert_eq!(v, Vec3::new(0.26726124, 0.5345225, 0.8017837));
+    fn neg(self) -> Self::Output {
+        Vec3::new(-self.x, -self.y, -self.z)
     }
+}
 
-    #[test]
-    fn test_mult_matrix() {
-        let vec = Vec3::new(3.0, 2.0, 4.0);
+impl<T: Num> std::ops::AddAssign for Vec3<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
 
-        let transformation = Matrix44::new([
-            [0.0, 1.0, 0.0, 0.0],
-            [1.0, 0.0, 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [0.0, 0.0, 0.0, 0.0],
-        ]);
+impl<T: Num> std::ops::SubAssign for Vec3<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
 
-        let transformed_vec = Vec3::new(2.0, 3.0, 4.0);
-        assert_eq!(vec.mult_matrix(&transformation), transformed_vec);
+// Scales every component, dividing by zero follows the semantics of T so floats give infinity or NaN
+impl<T: Num> std::ops::Mul<T> for Vec3<T> {
+    type Output = Vec3<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
     }
+}
 
-    #[test]
-    fn test_homogeneneous_mult_matrix() {
-        let vec = Vec3::new(3.0, 2.0, 4.0);
+impl<T: Num + std::ops::Div<Output = T>> std::ops::Div<T> for Vec3<T> {
+    type Output = Vec3<T>;
 
-        let transformation = Matrix44::new([
-            [1.0, 0.0, 0.0, 0.0],
-            [0.0, 1.0, 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [10.0, 3.0, 5.0, 1.0],
-        ]);
+    fn div(self, rhs: T) -> Self::Output {
+        Vec3::new(self.x / rhs, self.y / rhs, self.z / rhs)
+    }
+}
 
-        let transformed_vec = Vec3::new(13.0, 5.0, 9.0);
-        assert_eq!(vec.homogeneous_mult_matrix(&transformation), transformed_vec);
+impl<T: Num> std::ops::MulAssign<T> for Vec3<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
     }
 }
 
+impl<T: Num + std::ops::Div<Output = T>> std::ops::DivAssign<T> for Vec3<T> {
+    fn div_assign(&mut self, rhs: T) {
+        *self = *self / rhs;
+    }
+}
 
-#[cfg(test)]
-mod matrix44_tests {
-    use super::*;
+// Components are indexed in the order x, y, z
+impl<T: Num> std::ops::Index<usize> for Vec3<T> {
+    type Output = T;
 
-    #[test]
-    fn test_matrix_multiplication() {
-        let a = Matrix44::new([
-            [1.0, 0.0, 3.0, 4.0],
-            [5.0, 2.0, 1.0, 2.0],
-            [2.0, 1.0, 5.0, 6.0],
-            [1.0, 2.0, 0.0, 4.0],
-        ]);
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index {} out of range, expected 0, 1, or 2", index),
+        }
+    }
+}
 
-        let b = a.transpose();
+impl<T: Num> std::ops::IndexMut<usize> for Vec3<T> {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3 index {} out of range, expected 0, 1, or 2", index),
+        }
+    }
+}
 
-        let c = Matrix44::new([
-            [26.0, 16.0, 41.0, 17.0],
-            [16.0, 34.0, 29.0, 17.0],
-            [41.0, 29.0, 66.0, 28.0],
-            [17.0, 17.0, 28.0, 21.0],
-        ]);
+// Homogeneous coordinates, points have w = 1 and directions have w = 0
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Vec4<T: Num> {
+   pub x: T,
+   pub y: T,
+   pub z: T,
+   pub w: T,
+}
 
-        assert_eq!(a * b, c);
+impl<T: Num> Vec4<T> {
+    pub fn new(x: T, y: T, z: T, w: T) -> Self {
+        Vec4 {
+            x,
+            y,
+            z,
+            w,
+        }
+    }
+
+    pub fn splat(d: T) -> Self {
+        Vec4 {
+            x: d,
+            y: d,
+            z: d,
+            w: d,
+        }
+    }
+
+    pub fn from_vec3(vec: &Vec3<T>, w: T) -> Self {
+        Vec4 {
+            x: vec.x,
+            y: vec.y,
+            z: vec.z,
+            w,
+        }
+    }
+
+    // Does vector dot product with another vector, w is included
+    pub fn dot(&self, v: &Vec4<T>) -> T {
+        self.x * v.x + self.y * v.y + self.z * v.z + self.w * v.w
+    }
+
+    // Converts back to cartesian coordinates, x, y, and z are divided by w (the perspective divide)
+    // Directions (w = 0) have no position to divide into so x, y, and z are returned unchanged
+    pub fn to_vec3(&self) -> Vec3<T> where T: FloatNum {
+        let zero = T::from_f32(0.0);
+        let one = T::from_f32(1.0);
+
+        if self.w == zero || self.w == one {
+            Vec3::new(self.x, self.y, self.z)
+        } else {
+            Vec3::new(self.x / self.w, self.y / self.w, self.z / self.w)
+        }
+    }
+}
+
+// Component wise arithmetic, w is treated like any other component
+impl<T: Num> std::ops::Add for Vec4<T> {
+    type Output = Vec4<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Vec4::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z, self.w + rhs.w)
     }
 }
 
+impl<T: Num> std::ops::Sub for Vec4<T> {
+    type Output = Vec4<T>;
 
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec4::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z, self.w - rhs.w)
+    }
+}
+
+impl<T: Num> std::ops::Neg for Vec4<T> {
+    type Output = Vec4<T>;
+
+    fn neg(self) -> Self::Output {
+        Vec4::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
 
+impl<T: Num> std::ops::Mul<T> for Vec4<T> {
+    type Output = Vec4<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Vec4::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+impl<T: Num + std::ops::Div<Output = T>> std::ops::Div<T> for Vec4<T> {
+    type Output = Vec4<T>;
+
+    fn div(self, rhs: T) -> Self::Output {
+        Vec4::new(self.x / rhs, self.y / rhs, self.z / rhs, self.w / rhs)
+    }
+}
+
+type MatrixArray = [[f32; 4]; 4];
+
+#[derive(PartialEq)]
+pub struct Matrix44(pub(crate) MatrixArray);
+
+// Prints one row per line with each element right aligned, two decimal places are used unless a precision is given
+// e.g. println!("{:.4}", m)
+impl std::fmt::Display for Matrix44 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let precision = f.precision().unwrap_or(2);
+
+        for (i, row) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "[")?;
+            for (j, element) in row.iter().enumerate() {
+                let separator = if j > 0 { " " } else { "" };
+                write!(f, "{}{:>width$.precision$}", separator, element, width = precision + 6, precision = precision)?;
+            }
+            write!(f, "]")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Like the derived Debug but with one row per line and every element at full precision
+impl std::fmt::Debug for Matrix44 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Matrix44([")?;
+        for row in self.0.iter() {
+            writeln!(f, "    {:?},", row)?;
+        }
+        write!(f, "])")
+    }
+}
+
+// Overload for matrix multiplication
+// Multiplying references avoids moving either matrix, e.g. &view * &projection
+impl std::ops::Mul for &Matrix44 {
+    type Output = Matrix44;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let m: MatrixArray = std::array::from_fn(|i| std::array::from_fn(|j| {
+            self.0[i][0] * rhs.0[0][j] +
+            self.0[i][1] * rhs.0[1][j] +
+            self.0[i][2] * rhs.0[2][j] +
+            self.0[i][3] * rhs.0[3][j]
+        }));
+
+        Matrix44::new(m)
+    }
+}
+
+impl std::ops::Mul for Matrix44 {
+    type Output = Matrix44;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        std::ops::Mul::mul(&self, &rhs)
+    }
+}
+
+// m *= rhs is m = m * rhs, so rhs is applied after m with row vectors
+impl std::ops::MulAssign for Matrix44 {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = std::ops::Mul::mul(&*self, &rhs);
+    }
+}
+
+// Elements are indexed by (row, column)
+impl std::ops::Index<(usize, usize)> for Matrix44 {
+    type Output = f32;
+
+    fn index(&self, (row, column): (usize, usize)) -> &Self::Output {
+        &self.0[row][column]
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for Matrix44 {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut Self::Output {
+        &mut self.0[row][column]
+    }
+}
+
+impl Matrix44 {
+    // Construct matrix from matrix array
+    pub fn new(matrix_array: MatrixArray) -> Self {
+        Matrix44(matrix_array)
+    }
+
+    // Return new identity matrix
+    pub fn identity() -> Self {
+        Matrix44 ([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Return a matrix which moves points by (tx, ty, tz), the translation is stored in the last row
+    pub fn translation(tx: f32, ty: f32, tz: f32) -> Self {
+        Matrix44 ([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [tx, ty, tz, 1.0],
+        ])
+    }
+
+    // Rotations are counterclockwise by angle radians when looking down the axis towards the origin
+    // rotation_x takes +y towards +z
+    pub fn rotation_x(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix44 ([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, sin, 0.0],
+            [0.0, -sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // rotation_y takes +z towards +x
+    pub fn rotation_y(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix44 ([
+            [cos, 0.0, -sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // rotation_z takes +x towards +y
+    pub fn rotation_z(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix44 ([
+            [cos, sin, 0.0, 0.0],
+            [-sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Return a matrix which scales along each axis
+    pub fn scale(sx: f32, sy: f32, sz: f32) -> Self {
+        Matrix44 ([
+            [sx, 0.0, 0.0, 0.0],
+            [0.0, sy, 0.0, 0.0],
+            [0.0, 0.0, sz, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Return a matrix which shears each axis by the other two, e.g. xy is how far x moves per unit of y
+    // x' = x + xy * y + xz * z, y' = y + yx * x + yz * z, z' = z + zx * x + zy * y
+    pub fn shear(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        Matrix44 ([
+            [1.0, yx, zx, 0.0],
+            [xy, 1.0, zy, 0.0],
+            [xz, yz, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Return the world to camera matrix for a camera at eye looking towards target
+    // Camera space matches Camera, the forward direction becomes +z so target ends up at a positive z distance from the eye
+    // Camera x is up x forward, which point_to_screen flips so points to the right of the view appear on the right of the image
+    // If up is parallel to the view direction another up vector is picked, if eye and target coincide the identity is returned
+    pub fn look_at(eye: &Vec3<f32>, target: &Vec3<f32>, up: &Vec3<f32>) -> Matrix44 {
+        let mut forward = *target - *eye;
+        if forward.len() < 1e-6 {
+            return Matrix44::identity();
+        }
+        forward.normalise();
+
+        let mut x = up.cross(&forward);
+        if x.len() < 1e-6 {
+            // Any up vector which isn't parallel to forward gives a valid basis
+            let alternate_up = if forward.y.abs() < 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(0.0, 0.0, 1.0) };
+            x = alternate_up.cross(&forward);
+        }
+        x.normalise();
+        let y = forward.cross(&x);
+        let z = forward;
+
+        Matrix44::new([
+            [x.x, y.x, z.x, 0.0],
+            [x.y, y.y, z.y, 0.0],
+            [x.z, y.z, z.z, 0.0],
+            [-eye.dot(&x), -eye.dot(&y), -eye.dot(&z), 1.0],
+        ])
+    }
+
+    // Projection matrices take camera space to clip space, dividing by w (Vec4::to_vec3) then gives normalised device coordinates
+    // Camera space has +z forward like Camera, so z_near maps to an NDC z of -1 and z_far maps to 1
+    // Unlike Camera::point_to_screen x is not mirrored, an NDC x of 1 is along camera space +x
+
+    // Symmetric perspective projection, fovy is the vertical field of view in radians and aspect is width / height
+    // The frustum edges map to -1 and 1 in x and y, w is set to the camera space z
+    pub fn perspective(fovy: f32, aspect: f32, z_near: f32, z_far: f32) -> Matrix44 {
+        let f = 1.0 / (fovy / 2.0).tan();
+        let depth = z_far - z_near;
+
+        Matrix44::new([
+            [f / aspect, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, (z_far + z_near) / depth, 1.0],
+            [0.0, 0.0, -2.0 * z_far * z_near / depth, 0.0],
+        ])
+    }
+
+    // Orthographic projection which maps the box [left, right] x [bottom, top] x [near, far] onto [-1, 1] on every axis
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix44 {
+        let width = right - left;
+        let height = top - bottom;
+        let depth = far - near;
+
+        Matrix44::new([
+            [2.0 / width, 0.0, 0.0, 0.0],
+            [0.0, 2.0 / height, 0.0, 0.0],
+            [0.0, 0.0, 2.0 / depth, 0.0],
+            [-(right + left) / width, -(top + bottom) / height, -(far + near) / depth, 1.0],
+        ])
+    }
+
+    // Construct a rigid transformation which rotates by q then translates by t
+    // The upper left 3x3 is the rotation and the last row is the translation
+    pub fn from_quaternion_translation(q: &Quaternion, t: &Vec3<f32>) -> Self {
+        let mut m = q.to_matrix44().0;
+        m[3] = [t.x, t.y, t.z, 1.0];
+        Matrix44::new(m)
+    }
+
+    // Return the transpose of the current matrix
+    pub fn transpose(&self) -> Self {
+        let m: MatrixArray = std::array::from_fn(|i| std::array::from_fn(|j| self.0[j][i]));
+        Matrix44::new(m)
+    }
+
+    // Returns the rotation which takes the direction of from onto the direction of to
+    // Uses the Rodrigues rotation formula, from and to don't need to be unit length
+    // When the vectors point in opposite directions the rotation is 180 degrees about a perpendicular axis
+    pub fn rotation_from_to(from: &Vec3<f32>, to: &Vec3<f32>) -> Matrix44 {
+        let (mut from, mut to) = (*from, *to);
+        from.normalise();
+        to.normalise();
+
+        let cos = from.dot(&to);
+        let mut m = Matrix44::identity();
+
+        if cos < -1.0 + 1e-6 {
+            // R = 2kk^T - I, which is symmetric so the row vector form is the same
+            let (axis, _) = Vec3::tangent_frame(&from);
+            let axis = [axis.x, axis.y, axis.z];
+            for i in 0..3 {
+                for j in 0..3 {
+                    m[(i, j)] = 2.0 * axis[i] * axis[j] - if i == j {1.0} else {0.0};
+                }
+            }
+            return m;
+        }
+
+        // R = I + [v]x + [v]x^2 / (1 + cos) where v = from x to is the unnormalised axis scaled by sin
+        let v = from.cross(&to);
+        let skew = [
+            [0.0, -v.z, v.y],
+            [v.z, 0.0, -v.x],
+            [-v.y, v.x, 0.0],
+        ];
+        let k = 1.0 / (1.0 + cos);
+
+        for i in 0..3 {
+            for j in 0..3 {
+                let skew_squared = (0..3).map(|n| skew[i][n] * skew[n][j]).sum::<f32>();
+
+                // Transposed since points are row vectors multiplied on the left
+                m[(j, i)] += skew[i][j] + skew_squared * k;
+            }
+        }
+
+        m
+    }
+
+    // Makes a rotation from ZYX Euler angles in radians
+    // Points are rotated by roll about the x axis, then by pitch about the y axis, then by yaw about the z axis
+    pub fn from_euler_zyx(roll: f32, pitch: f32, yaw: f32) -> Matrix44 {
+        let (sin_r, cos_r) = roll.sin_cos();
+        let (sin_p, cos_p) = pitch.sin_cos();
+        let (sin_y, cos_y) = yaw.sin_cos();
+
+        // Transpose of Rz * Ry * Rx since points are row vectors
+        Matrix44::new([
+            [cos_y * cos_p, sin_y * cos_p, -sin_p, 0.0],
+            [cos_y * sin_p * sin_r - sin_y * cos_r, sin_y * sin_p * sin_r + cos_y * cos_r, cos_p * sin_r, 0.0],
+            [cos_y * sin_p * cos_r + sin_y * sin_r, sin_y * sin_p * cos_r - cos_y * sin_r, cos_p * cos_r, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Returns the (roll, pitch, yaw) ZYX Euler angles of the upper left 3x3 rotation, the inverse of from_euler_zyx
+    // Pitch is in [-pi / 2, pi / 2]
+    // At gimbal lock (pitch = +-pi / 2) roll and yaw rotate about the same axis, roll is set to 0 and yaw holds the whole rotation
+    pub fn to_euler_zyx(&self) -> (f32, f32, f32) {
+        let m = &self.0;
+        let pitch = (-m[0][2]).clamp(-1.0, 1.0).asin();
+
+        if pitch.cos() > 1e-4 {
+            let roll = m[1][2].atan2(m[2][2]);
+            let yaw = m[0][1].atan2(m[0][0]);
+            (roll, pitch, yaw)
+        } else {
+            let yaw = (-m[1][0]).atan2(m[1][1]);
+            (0.0, pitch, yaw)
+        }
+    }
+
+    // Multiply matrices [1x4] x [4x4] = [1x4]
+    // Unlike Vec3::homogeneous_mult_matrix w is kept, so the perspective divide can happen later with Vec4::to_vec3
+    pub fn mul_vec4(&self, v: &Vec4<f32>) -> Vec4<f32> {
+        let column = |j: usize| v.x * self.0[0][j] + v.y * self.0[1][j] + v.z * self.0[2][j] + v.w * self.0[3][j];
+
+        Vec4::new(column(0), column(1), column(2), column(3))
+    }
+
+    // Returns every element in row major order
+    pub fn iter_elements(&self) -> impl Iterator<Item = f32> + '_ {
+        self.0.iter().flat_map(|row| row.iter().copied())
+    }
+
+    // Determinants of the 2x2 submatrices in rows 0 and 1 and in rows 2 and 3, which both determinant and inverse are built from
+    // Pairs are ordered by column (0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)
+    fn subfactors(&self) -> ([f32; 6], [f32; 6]) {
+        let a = &self.0;
+        let pair = |r0: usize, r1: usize, c0: usize, c1: usize| a[r0][c0] * a[r1][c1] - a[r1][c0] * a[r0][c1];
+
+        let upper = [pair(0, 1, 0, 1), pair(0, 1, 0, 2), pair(0, 1, 0, 3), pair(0, 1, 1, 2), pair(0, 1, 1, 3), pair(0, 1, 2, 3)];
+        let lower = [pair(2, 3, 0, 1), pair(2, 3, 0, 2), pair(2, 3, 0, 3), pair(2, 3, 1, 2), pair(2, 3, 1, 3), pair(2, 3, 2, 3)];
+
+        (upper, lower)
+    }
+
+    // Returns the determinant using Laplace expansion over the first two rows
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.subfactors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // Returns the inverse matrix (the adjugate divided by the determinant), works for any invertible matrix not just rotations
+    // Returns None if the matrix is singular, i.e. the determinant is (almost) zero or not finite
+    pub fn inverse(&self) -> Option<Matrix44> {
+        const SINGULAR_EPSILON: f32 = 1e-12;
+
+        let a = &self.0;
+        let (s, c) = self.subfactors();
+
+        let det = self.determinant();
+        if det.abs() < SINGULAR_EPSILON || !det.is_finite() {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let adjugate = [
+            [
+                a[1][1] * c[5] - a[1][2] * c[4] + a[1][3] * c[3],
+                -a[0][1] * c[5] + a[0][2] * c[4] - a[0][3] * c[3],
+                a[3][1] * s[5] - a[3][2] * s[4] + a[3][3] * s[3],
+                -a[2][1] * s[5] + a[2][2] * s[4] - a[2][3] * s[3],
+            ],
+            [
+                -a[1][0] * c[5] + a[1][2] * c[2] - a[1][3] * c[1],
+                a[0][0] * c[5] - a[0][2] * c[2] + a[0][3] * c[1],
+                -a[3][0] * s[5] + a[3][2] * s[2] - a[3][3] * s[1],
+                a[2][0] * s[5] - a[2][2] * s[2] + a[2][3] * s[1],
+            ],
+            [
+                a[1][0] * c[4] - a[1][1] * c[2] + a[1][3] * c[0],
+                -a[0][0] * c[4] + a[0][1] * c[2] - a[0][3] * c[0],
+                a[3][0] * s[4] - a[3][1] * s[2] + a[3][3] * s[0],
+                -a[2][0] * s[4] + a[2][1] * s[2] - a[2][3] * s[0],
+            ],
+            [
+                -a[1][0] * c[3] + a[1][1] * c[1] - a[1][2] * c[0],
+                a[0][0] * c[3] - a[0][1] * c[1] + a[0][2] * c[0],
+                -a[3][0] * s[3] + a[3][1] * s[1] - a[3][2] * s[0],
+                a[2][0] * s[3] - a[2][1] * s[1] + a[2][2] * s[0],
+            ],
+        ];
+
+        Some(Matrix44(adjugate.map(|row| row.map(|element| element * inv_det))))
+    }
+
+    // Return the sum of the diagonal elements
+    pub fn trace(&self) -> f32 {
+        self.0[0][0] + self.0[1][1] + self.0[2][2] + self.0[3][3]
+    }
+
+    // Return true if the matrix multiplied by its transpose is the identity matrix
+    // Each element is compared with a tolerance of epsilon
+    pub fn is_orthogonal(&self, epsilon: f32) -> bool {
+        let product = self * &self.transpose();
+        let identity = Matrix44::identity();
+
+        for i in 0..4 {
+            for j in 0..4 {
+                if (product.0[i][j] - identity.0[i][j]).abs() > epsilon {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    // Element-wise linear interpolation between two matrices, t is not clamped
+    // This is only meaningful for blending affine transformations such as translations and scales
+    // Interpolating rotations this way shears and shrinks the result, use decompose_lerp instead
+    pub fn lerp(a: &Matrix44, b: &Matrix44, t: f32) -> Matrix44 {
+        let m: MatrixArray = std::array::from_fn(|i| std::array::from_fn(|j| a.0[i][j] * (1.0 - t) + b.0[i][j] * t));
+        Matrix44::new(m)
+    }
+
+    // Interpolates between two transformations by decomposing them into translation, rotation and scale
+    // Translation and scale are linearly interpolated and rotation is spherically interpolated
+    // The matrices are assumed to be affine with positive scale and no shear
+    pub fn decompose_lerp(a: &Matrix44, b: &Matrix44, t: f32) -> Matrix44 {
+        let (translation_a, rotation_a, scale_a) = a.decompose();
+        let (translation_b, rotation_b, scale_b) = b.decompose();
+
+        let translation = Vec3::lerp_unclamped(&translation_a, &translation_b, t);
+        let scale = Vec3::lerp_unclamped(&scale_a, &scale_b, t);
+        let rotation = Quaternion::slerp(&rotation_a, &rotation_b, t).to_matrix44();
+
+        // Scale then rotate then translate
+        let mut m = rotation.0;
+        for (row, scale) in m.iter_mut().zip([scale.x, scale.y, scale.z]) {
+            for element in row.iter_mut().take(3) {
+                *element *= scale;
+            }
+        }
+        m[3] = [translation.x, translation.y, translation.z, 1.0];
+
+        Matrix44::new(m)
+    }
+
+    // Splits an affine matrix into its translation, rotation and scale
+    fn decompose(&self) -> (Vec3<f32>, Quaternion, Vec3<f32>) {
+        (self.extract_translation(), Quaternion::from_matrix44(&self.extract_rotation_matrix()), self.extract_scale())
+    }
+
+    // The extract functions assume an affine matrix built as scale * rotation * translation, so points are scaled first
+    // Returns the translation, stored in the last row
+    pub fn extract_translation(&self) -> Vec3<f32> {
+        Vec3::new(self.0[3][0], self.0[3][1], self.0[3][2])
+    }
+
+    // Returns the scale along each axis
+    // Each of the first three rows is a rotated basis vector multiplied by its scale, so the scale is the row length
+    // Negative scales can't be told apart from rotations and come out positive
+    pub fn extract_scale(&self) -> Vec3<f32> {
+        let row = |i: usize| Vec3::new(self.0[i][0], self.0[i][1], self.0[i][2]);
+        Vec3::new(row(0).len(), row(1).len(), row(2).len())
+    }
+
+    // Returns the rotation with the scale divided out and the translation removed
+    pub fn extract_rotation_matrix(&self) -> Matrix44 {
+        let scale = self.extract_scale();
+
+        let mut rotation = Matrix44::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                rotation.0[i][j] = self.0[i][j] / scale[i];
+            }
+        }
+
+        rotation
+    }
+}
+
+// 3x3 matrix for transformations without translation, such as transforming normals or 2D transforms
+// Follows the same row vector convention as Matrix44
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Matrix33(pub(crate) [[f32; 3]; 3]);
+
+impl std::ops::Mul for Matrix33 {
+    type Output = Matrix33;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let m = std::array::from_fn(|i| std::array::from_fn(|j| {
+            self.0[i][0] * rhs.0[0][j] +
+            self.0[i][1] * rhs.0[1][j] +
+            self.0[i][2] * rhs.0[2][j]
+        }));
+
+        Matrix33::new(m)
+    }
+}
+
+// Elements are indexed by (row, column)
+impl std::ops::Index<(usize, usize)> for Matrix33 {
+    type Output = f32;
+
+    fn index(&self, (row, column): (usize, usize)) -> &Self::Output {
+        &self.0[row][column]
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for Matrix33 {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut Self::Output {
+        &mut self.0[row][column]
+    }
+}
+
+impl Matrix33 {
+    pub fn new(matrix_array: [[f32; 3]; 3]) -> Self {
+        Matrix33(matrix_array)
+    }
+
+    pub fn identity() -> Self {
+        Matrix33([
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Returns the rotation and scale part of a Matrix44, the translation row and last column are dropped
+    pub fn from_matrix44_upper_left(m: &Matrix44) -> Self {
+        Matrix33(std::array::from_fn(|i| [m.0[i][0], m.0[i][1], m.0[i][2]]))
+    }
+
+    pub fn transpose(&self) -> Self {
+        Matrix33(std::array::from_fn(|i| std::array::from_fn(|j| self.0[j][i])))
+    }
+
+    // The rows are the basis vectors so the determinant is their scalar triple product
+    pub fn determinant(&self) -> f32 {
+        let row = |i: usize| Vec3::new(self.0[i][0], self.0[i][1], self.0[i][2]);
+        row(0).dot(&row(1).cross(&row(2)))
+    }
+
+    // Returns the inverse matrix, or None if the matrix is singular
+    // Normals are transformed by the inverse transpose so they stay perpendicular to non uniformly scaled surfaces
+    pub fn inverse(&self) -> Option<Matrix33> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 || !det.is_finite() {
+            return None;
+        }
+
+        // Columns of the inverse are the cross products of pairs of rows
+        let row = |i: usize| Vec3::new(self.0[i][0], self.0[i][1], self.0[i][2]);
+        let columns = [row(1).cross(&row(2)), row(2).cross(&row(0)), row(0).cross(&row(1))];
+
+        let mut m = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = columns[j][i] / det;
+            }
+        }
+
+        Some(Matrix33(m))
+    }
+}
+
+#[cfg(test)]
+mod vec2_tests {
+    use super::*;
+
+    #[test]
+    fn test_add_sub() {
+        let a = Vec2::new(1.0f32, -2.0);
+        let b = Vec2::new(4.0f32, 0.5);
+
+        assert_eq!(a + b, Vec2::new(5.0, -1.5));
+        assert_eq!(a + b, b + a);
+        assert_eq!(a - b, Vec2::new(-3.0, -2.5));
+        assert_eq!(a - a, Vec2::splat(0.0));
+        assert_eq!(-a, Vec2::new(-1.0, 2.0));
+
+        let (ra, rb) = (&a, &b);
+        assert_eq!(ra + rb, a + b);
+        assert_eq!(ra - rb, a - b);
+
+        let mut c = a;
+        c += b;
+        assert_eq!(c, a + b);
+        c -= b;
+        assert_eq!(c, a);
+
+        // Integer vectors work too
+        assert_eq!(Vec2::new(1, 2) + Vec2::new(3, -4), Vec2::new(4, -2));
+    }
+
+    #[test]
+    fn test_scalar_mul_div() {
+        let v = Vec2::new(2.0f32, -4.0);
+
+        assert_eq!(v * 0.5, Vec2::new(1.0, -2.0));
+        assert_eq!(v / 2.0, Vec2::new(1.0, -2.0));
+
+        let mut w = v;
+        w *= 3.0;
+        assert_eq!(w, Vec2::new(6.0, -12.0));
+        w /= 3.0;
+        assert_eq!(w, v);
+
+        let inf = v / 0.0;
+        assert!(inf.x.is_infinite() && inf.y.is_infinite());
+    }
+
+    #[test]
+    fn test_dot_len_normalise() {
+        let mut v = Vec2::new(3.0f32, 4.0);
+
+        assert_eq!(v.dot(&Vec2::new(1.0, 1.0)), 7.0);
+        assert_eq!(v.len(), 5.0);
+
+        v.normalise();
+        assert!((v.len() - 1.0).abs() < 1e-6);
+        assert_eq!(v, Vec2::new(0.6, 0.8));
+    }
+
+    #[test]
+    fn test_perp() {
+        let v = Vec2::new(2.0f32, 1.0);
+
+        assert_eq!(v.perp(), Vec2::new(-1.0, 2.0));
+        assert_eq!(v.dot(&v.perp()), 0.0);
+        assert_eq!(v.perp().perp(), -v);
+
+        // Points to the left of the direction have a positive 2D cross product
+        let direction = Vec2::new(1.0f32, 0.0);
+        assert!(Vec2::new(0.0, 1.0).dot(&direction.perp()) > 0.0);
+        assert!(Vec2::new(0.0, -1.0).dot(&direction.perp()) < 0.0);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a = Vec2::new(0.0f32, 10.0);
+        let b = Vec2::new(4.0f32, -2.0);
+
+        assert_eq!(Vec2::lerp(&a, &b, 0.0), a);
+        assert_eq!(Vec2::lerp(&a, &b, 1.0), b);
+        assert_eq!(Vec2::lerp(&a, &b, 0.5), Vec2::new(2.0, 4.0));
+        assert_eq!(Vec2::lerp(&a, &b, 2.0), Vec2::new(8.0, -14.0));
+    }
+}
+
+#[cfg(test)]
+mod vec3_tests {
+    use super::*;
+
+    #[test]
+    fn test_add_sub() {
+        let a = Vec3::new(1.0f32, -2.0, 3.5);
+        let b = Vec3::new(4.0f32, 0.5, -1.0);
+        let c = Vec3::new(-3.0f32, 2.0, 8.0);
+
+        assert_eq!(a + b, Vec3::new(5.0, -1.5, 2.5));
+        assert_eq!(a + b, b + a);
+        assert_eq!((a + b) + c, a + (b + c));
+        assert_eq!(b - a, Vec3::new(3.0, 2.5, -4.5));
+        assert_eq!((a - b) + b, a);
+
+        // Reference forms give the same results
+        let (ref_a, ref_b) = (&a, &b);
+        assert_eq!(ref_a + ref_b, a + b);
+        assert_eq!(ref_b - ref_a, b - a);
+
+        let a = Vec3::new(1, -2, 3);
+        let b = Vec3::new(4, 5, -6);
+        let c = Vec3::new(-7, 8, 9);
+
+        assert_eq!(a + b, b + a);
+        assert_eq!((a + b) + c, a + (b + c));
+        assert_eq!(a - b, Vec3::new(-3, -7, 9));
+        assert_eq!(a - b, -(b - a));
+    }
+
+    #[test]
+    fn test_scalar_mul_div() {
+        let mut unit = Vec3::new(2.0f32, -3.0, 6.0) / 7.0;
+        assert!((unit.len() - 1.0).abs() < f32::EPSILON);
+
+        let scaled = unit * 3.5;
+        assert!((scaled.len() - 3.5).abs() < 4.0 * f32::EPSILON);
+
+        let round_trip = scaled / 3.5;
+        for (a, b) in [(round_trip.x, unit.x), (round_trip.y, unit.y), (round_trip.z, unit.z)] {
+            assert!((a - b).abs() <= f32::EPSILON);
+        }
+
+        unit *= 2.0;
+        unit /= 4.0;
+        assert_eq!(unit, Vec3::new(2.0, -3.0, 6.0) / 14.0);
+
+        assert_eq!(Vec3::new(1, -2, 3) * 2, Vec3::new(2, -4, 6));
+    }
+
+    #[test]
+    fn test_div_by_zero() {
+        let v = Vec3::new(1.0f32, -1.0, 0.0) / 0.0;
+
+        assert_eq!(v.x, f32::INFINITY);
+        assert_eq!(v.y, f32::NEG_INFINITY);
+        assert!(v.z.is_nan());
+    }
+
+    #[test]
+    fn test_neg() {
+        assert_eq!(-Vec3::new(1.0, -2.0, 0.5), Vec3::new(-1.0, 2.0, -0.5));
+        assert_eq!(-Vec3::new(1, -2, 3), Vec3::new(-1, 2, -3));
+
+        let v = Vec3::new(3, 4, 5);
+        assert_eq!(v + -v, Vec3::splat(0));
+    }
+
+    #[test]
+    fn test_add_sub_assign() {
+        let mut v = Vec3::new(1.0, 2.0, 3.0);
+        v += Vec3::new(0.5, 0.5, 0.5);
+        assert_eq!(v, Vec3::new(1.5, 2.5, 3.5));
+        v -= Vec3::new(1.5, 0.5, 4.0);
+        assert_eq!(v, Vec3::new(0.0, 2.0, -0.5));
+
+        let mut v = Vec3::new(1, 2, 3);
+        v += Vec3::new(3, 2, 1);
+        v -= Vec3::new(1, 1, 1);
+        assert_eq!(v, Vec3::new(3, 3, 3));
+    }
+
+    #[test]
+    fn test_dot() {
+        let v1 = Vec3::new(3.0, 4.0, 5.0);
+        let v2 = Vec3::new(4.0, 2.0, 5.0);
+
+        let dot = v1.dot(&v2);
+        assert_eq!(dot, 45.0);
+    }
+    
+    #[test]
+    fn test_cross() {
+        let v1 = Vec3::new(1.0, 2.0, 3.0);
+        let v2 = Vec3::new(4.0, 5.0, 6.0);
+
+        let cross = v1.cross(&v2);
+        assert_eq!(cross, Vec3::new(-3.0, 6.0, -3.0));
+    }
+    
+    #[test]
+    fn test_len() {
+        let v = Vec3::new(1.0f32, 2.0, 3.0);
+
+        assert_eq!(v.len(), 14.0f32.sqrt());
+    }
+
+    #[test]
+    fn test_normalise() {
+        let mut v = Vec3::new(1.0f32, 2.0, 3.0);
+        v.normalise();
+
+        assert_eq!(v, Vec3::new(0.26726124, 0.5345225, 0.8017837));
+    }
+
+    #[test]
+    fn test_mult_matrix() {
+        let vec = Vec3::new(3.0, 2.0, 4.0);
+
+        let transformation = Matrix44::new([
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ]);
+
+        let transformed_vec = Vec3::new(2.0, 3.0, 4.0);
+        assert_eq!(vec.mult_matrix(&transformation), transformed_vec);
+    }
+
+    #[test]
+    fn test_homogeneneous_mult_matrix() {
+        let vec = Vec3::new(3.0, 2.0, 4.0);
+
+        let transformation = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [10.0, 3.0, 5.0, 1.0],
+        ]);
+
+        let transformed_vec = Vec3::new(13.0, 5.0, 9.0);
+        assert_eq!(vec.homogeneous_mult_matrix(&transformation), transformed_vec);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a = Vec3::new(0.1f32, -2.7, 3.3);
+        let b = Vec3::new(0.3f32, 5.9, -1.1);
+
+        assert_eq!(Vec3::lerp(&a, &b, 0.0), a);
+        assert_eq!(Vec3::lerp(&a, &b, 1.0), b);
+
+        let midpoint = Vec3::lerp(&a, &b, 0.5);
+        assert!((midpoint - (a + b) * 0.5).len() < 1e-6);
+
+        // Extrapolates past either end
+        let after = Vec3::lerp(&a, &b, 2.0);
+        assert!((after - (b + (b - a))).len() < 1e-5);
+        let before = Vec3::lerp(&a, &b, -1.0);
+        assert!((before - (a - (b - a))).len() < 1e-5);
+    }
+
+    #[test]
+    fn test_lerp_unclamped_extrapolates() {
+        let a = Vec3::new(1.0f32, 2.0, 3.0);
+        let b = Vec3::new(2.0f32, 4.0, 6.0);
+
+        // b + (b - a)
+        assert_eq!(Vec3::lerp_unclamped(&a, &b, 2.0), Vec3::new(3.0, 6.0, 9.0));
+        assert_eq!(Vec3::lerp_unclamped(&a, &b, 0.5), Vec3::new(1.5, 3.0, 4.5));
+    }
+
+    #[test]
+    fn test_lerp_clamped() {
+        let a = Vec3::new(1.0f32, 2.0, 3.0);
+        let b = Vec3::new(2.0f32, 4.0, 6.0);
+
+        assert_eq!(Vec3::lerp_clamped(&a, &b, -1.0), a);
+        assert_eq!(Vec3::lerp_clamped(&a, &b, 2.0), b);
+        assert_eq!(Vec3::lerp_clamped(&a, &b, 0.5), Vec3::new(1.5, 3.0, 4.5));
+    }
+
+    #[test]
+    fn test_reflect() {
+        let floor = Vec3::new(0.0, 1.0, 0.0);
+        let incident = Vec3::new(1.0, -1.0, 0.0) / 2.0f32.sqrt();
+
+        let reflected = Vec3::reflect(&incident, &floor);
+        assert!((reflected - Vec3::new(1.0, 1.0, 0.0) / 2.0f32.sqrt()).len() < 1e-6);
+
+        // Straight down bounces straight back up
+        assert_eq!(Vec3::reflect(&-floor, &floor), floor);
+    }
+
+    #[test]
+    fn test_refract() {
+        let surface = Vec3::new(0.0, 1.0, 0.0);
+
+        // Light hitting the surface head on passes straight through
+        let straight = Vec3::refract(&-surface, &surface, 1.0 / 1.5).unwrap();
+        assert!((straight + surface).len() < 1e-6);
+
+        // Air to glass bends towards the normal, sin(refracted) = sin(incident) / 1.5
+        let incident = Vec3::new(1.0, -1.0, 0.0) / 2.0f32.sqrt();
+        let refracted = Vec3::refract(&incident, &surface, 1.0 / 1.5).unwrap();
+        assert!((refracted.len() - 1.0).abs() < 1e-6);
+        assert!((refracted.x - std::f32::consts::FRAC_1_SQRT_2 / 1.5).abs() < 1e-6);
+
+        // Glass to air at a grazing angle is totally internally reflected
+        let mut grazing = Vec3::new(1.0, -0.1, 0.0);
+        grazing.normalise();
+        assert!(Vec3::refract(&grazing, &surface, 1.5).is_none());
+    }
+
+    #[test]
+    fn test_angle_between() {
+        use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 2.0, 0.0);
+
+        assert!((x.angle_between(&y) - FRAC_PI_2).abs() < 1e-6);
+        assert!((x.angle_between(&Vec3::new(1.0, 1.0, 0.0)) - FRAC_PI_4).abs() < 1e-6);
+        assert!((x.angle_between(&-x) - PI).abs() < 1e-6);
+
+        // Parallel vectors can round to a cosine just above one, which must not give NaN
+        let v = Vec3::new(0.1, 0.2, 0.3);
+        let angle = v.angle_between(&(v * 3.0));
+        assert!(angle.abs() < 1e-3);
+
+        let zero = Vec3::splat(0.0f32);
+        assert_eq!(zero.angle_between(&x), 0.0);
+        assert_eq!(x.angle_between(&zero), 0.0);
+    }
+
+    #[test]
+    fn test_project_onto() {
+        let v = Vec3::new(3.0, 4.0, 5.0);
+
+        let (scalar, projected) = v.project_onto(&Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(scalar, 3.0);
+        assert_eq!(projected, Vec3::new(3.0, 0.0, 0.0));
+
+        // Orthogonal vectors project to nothing
+        let (scalar, projected) = v.project_onto(&Vec3::new(0.0, 5.0, -4.0));
+        assert_eq!(scalar, 0.0);
+        assert_eq!(projected, Vec3::splat(0.0));
+
+        // Pointing away gives a negative length but the projection still lies along v
+        let (scalar, projected) = v.project_onto(&Vec3::new(-1.0, 0.0, 0.0));
+        assert_eq!(scalar, -3.0);
+        assert_eq!(projected, Vec3::new(3.0, 0.0, 0.0));
+
+        let (scalar, projected) = v.project_onto(&Vec3::splat(0.0));
+        assert_eq!(scalar, 0.0);
+        assert_eq!(projected, Vec3::splat(0.0));
+    }
+
+    #[test]
+    fn test_distance() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, 6.0, 3.0);
+
+        assert_eq!(a.distance(&b), 5.0);
+        assert_eq!(b.distance(&a), 5.0);
+        assert_eq!(a.distance(&a), 0.0);
+    }
+
+    #[test]
+    fn test_component_min_max() {
+        let a = Vec3::new(3.0, 1.0, 4.0);
+        let b = Vec3::new(1.0, 5.0, 9.0);
+
+        assert_eq!(Vec3::min_component_pair(&a, &b), Vec3::new(1.0, 1.0, 4.0));
+        assert_eq!(Vec3::max_component_pair(&a, &b), Vec3::new(3.0, 5.0, 9.0));
+        assert_eq!(a.min_component(), 1.0);
+        assert_eq!(a.max_component(), 4.0);
+    }
+
+    #[test]
+    fn test_component_min_max_methods() {
+        let a = Vec3::new(3.0, -1.0, 0.0);
+        let b = Vec3::new(-2.0, 5.0, 0.0);
+
+        assert_eq!(a.component_min(&b), Vec3::new(-2.0, -1.0, 0.0));
+        assert_eq!(a.component_max(&b), Vec3::new(3.0, 5.0, 0.0));
+        assert_eq!(b.component_min(&a), a.component_min(&b));
+
+        // Also works for integer vectors
+        assert_eq!(Vec3::new(1, -7, 3).component_max(&Vec3::new(2, -8, 0)), Vec3::new(2, -7, 3));
+    }
+
+    #[test]
+    fn test_spherical_axes() {
+        use std::f32::consts::{FRAC_PI_2, PI};
+
+        // (vector, theta, phi), phi is 0 on the z axis where it is undefined
+        let axes = [
+            (Vec3::new(1.0, 0.0, 0.0), FRAC_PI_2, 0.0),
+            (Vec3::new(-1.0, 0.0, 0.0), FRAC_PI_2, PI),
+            (Vec3::new(0.0, 1.0, 0.0), FRAC_PI_2, FRAC_PI_2),
+            (Vec3::new(0.0, -1.0, 0.0), FRAC_PI_2, -FRAC_PI_2),
+            (Vec3::new(0.0, 0.0, 1.0), 0.0, 0.0),
+            (Vec3::new(0.0, 0.0, -1.0), PI, 0.0),
+        ];
+
+        for (axis, theta, phi) in axes {
+            let (r, t, p) = axis.to_spherical();
+            assert!((r - 1.0).abs() < 1e-6);
+            assert!((t - theta).abs() < 1e-6, "theta of {:?} was {}", axis, t);
+            assert!((p - phi).abs() < 1e-6, "phi of {:?} was {}", axis, p);
+
+            assert!((Vec3::from_spherical(r, t, p) - axis).len() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_spherical_round_trip() {
+        let v = Vec3::new(-2.0, 3.0, 0.5);
+        let (r, theta, phi) = v.to_spherical();
+        assert!((Vec3::from_spherical(r, theta, phi) - v).len() < 1e-5);
+
+        assert_eq!(Vec3::splat(0.0).to_spherical(), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(v[0], 1.0);
+        assert_eq!(v[1], 2.0);
+        assert_eq!(v[2], 3.0);
+
+        for i in 0..3 {
+            v[i] *= 2.0;
+        }
+        assert_eq!(v, Vec3::new(2.0, 4.0, 6.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let v = Vec3::new(1, 2, 3);
+        let _ = v[3];
+    }
+
+    #[test]
+    fn test_abs() {
+        assert_eq!(Vec3::new(-1.5f32, 0.0, 2.5).abs(), Vec3::new(1.5, 0.0, 2.5));
+        assert_eq!(Vec3::new(-3, 0, 4).abs(), Vec3::new(3, 0, 4));
+    }
+
+    #[test]
+    fn test_clamp() {
+        let min = Vec3::new(-1.0, -1.0, 0.0);
+        let max = Vec3::new(1.0, 1.0, 10.0);
+
+        assert_eq!(Vec3::new(-5.0, 0.5, 20.0).clamp(&min, &max), Vec3::new(-1.0, 0.5, 10.0));
+        assert_eq!(Vec3::new(0.0, 2.0, -3.0).clamp(&min, &max), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_floor_ceil() {
+        let v = Vec3::new(-1.5f32, 0.0, 2.25);
+
+        assert_eq!(v.floor(), Vec3::new(-2.0, 0.0, 2.0));
+        assert_eq!(v.ceil(), Vec3::new(-1.0, 0.0, 3.0));
+        assert_eq!(Vec3::new(-0.5f64, 1.0, 7.9).floor(), Vec3::new(-1.0, 1.0, 7.0));
+    }
+
+    #[test]
+    fn test_barycentric_conversions() {
+        let v0 = Vec3::new(0.0, 0.0, 1.0);
+        let v1 = Vec3::new(6.0, 0.0, 2.0);
+        let v2 = Vec3::new(0.0, 3.0, 3.0);
+
+        let centroid = Vec3::new(2.0, 1.0, 2.0);
+        let (l0, l1, l2) = Vec3::cartesian_to_barycentric(&centroid, &v0, &v1, &v2);
+        for l in [l0, l1, l2] {
+            assert!((l - 1.0 / 3.0).abs() < 1e-6);
+        }
+
+        let p = Vec3::barycentric_to_cartesian(l0, l1, l2, &v0, &v1, &v2);
+        assert!((p.x - centroid.x).abs() < 1e-6 && (p.y - centroid.y).abs() < 1e-6 && (p.z - centroid.z).abs() < 1e-6);
+
+        // Vertices have a coordinate of one for themselves
+        assert_eq!(Vec3::cartesian_to_barycentric(&v1, &v0, &v1, &v2), (0.0, 1.0, 0.0));
+
+        // Points outside of the triangle have a negative coordinate
+        let (l0, _, _) = Vec3::cartesian_to_barycentric(&Vec3::new(6.0, 3.0, 0.0), &v0, &v1, &v2);
+        assert!(l0 < 0.0);
+    }
+
+    #[test]
+    fn test_tangent_frame() {
+        let mut normals = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.3, -0.5, 0.8),
+            Vec3::new(-0.2, 0.7, -0.4),
+        ];
+
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        for normal in normals.iter_mut() {
+            normal.normalise();
+            let (tangent, bitangent) = Vec3::tangent_frame(normal);
+
+            assert!(close(normal.dot(&tangent), 0.0));
+            assert!(close(normal.dot(&bitangent), 0.0));
+            assert!(close(tangent.dot(&bitangent), 0.0));
+
+            assert!(close(tangent.len(), 1.0));
+            assert!(close(bitangent.len(), 1.0));
+
+            let cross = tangent.cross(&bitangent);
+            assert!(close(cross.x, normal.x) && close(cross.y, normal.y) && close(cross.z, normal.z));
+        }
+    }
+
+    #[test]
+    fn test_normalise_f64() {
+        let mut v = Vec3::new(1.0f64, 2.0, 3.0);
+        v.normalise();
+
+        assert!((v.len() - 1.0).abs() < 1e-15);
+        assert!((v.x - 0.2672612419124244).abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_mult_matrix_f64() {
+        let vec = Vec3::new(3.0f64, 2.0, 4.0);
+
+        let transformation = Matrix44::new([
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ]);
+
+        assert_eq!(vec.mult_matrix(&transformation), Vec3::new(2.0f64, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_homogeneneous_mult_matrix_f64() {
+        // Components which can't be represented exactly as f32
+        let vec = Vec3::new(0.1f64, 1e-9, 4.0);
+
+        let transformation = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [10.0, 3.0, 5.0, 1.0],
+        ]);
+
+        let transformed_vec = vec.homogeneous_mult_matrix(&transformation);
+        assert!((transformed_vec.x - 10.1).abs() < 1e-12);
+        assert!((transformed_vec.y - (3.0 + 1e-9)).abs() < 1e-12);
+        assert!((transformed_vec.z - 9.0).abs() < 1e-12);
+    }
+}
+
+
+#[cfg(test)]
+mod vec4_tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Vec4::new(1.0f32, -2.0, 3.0, 1.0);
+        let b = Vec4::new(0.5f32, 4.0, -1.0, 0.0);
+
+        assert_eq!(a + b, Vec4::new(1.5, 2.0, 2.0, 1.0));
+        assert_eq!(a - b, Vec4::new(0.5, -6.0, 4.0, 1.0));
+        assert_eq!(-a, Vec4::new(-1.0, 2.0, -3.0, -1.0));
+        assert_eq!(a * 2.0, Vec4::new(2.0, -4.0, 6.0, 2.0));
+        assert_eq!(a / 2.0, Vec4::new(0.5, -1.0, 1.5, 0.5));
+        assert_eq!(a.dot(&b), 0.5 - 8.0 - 3.0);
+    }
+
+    #[test]
+    fn test_vec3_conversions() {
+        let v = Vec3::new(2.0f32, 4.0, -6.0);
+
+        assert_eq!(Vec4::from_vec3(&v, 1.0).to_vec3(), v);
+        assert_eq!(Vec4::from_vec3(&v, 2.0).to_vec3(), Vec3::new(1.0, 2.0, -3.0));
+
+        // Directions are not divided
+        assert_eq!(Vec4::from_vec3(&v, 0.0).to_vec3(), v);
+    }
+}
+
+#[cfg(test)]
+mod matrix33_tests {
+    use super::*;
+
+    fn assert_matrix33_close(a: &Matrix33, b: &Matrix33) {
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((a[(i, j)] - b[(i, j)]).abs() < 1e-5, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_matrix44_upper_left() {
+        let m = Matrix44::scale(2.0, 3.0, 4.0) * Matrix44::translation(5.0, 6.0, 7.0);
+        let upper_left = Matrix33::from_matrix44_upper_left(&m);
+
+        assert_eq!(upper_left, Matrix33::new([
+            [2.0, 0.0, 0.0],
+            [0.0, 3.0, 0.0],
+            [0.0, 0.0, 4.0],
+        ]));
+
+        // Matches Vec3::mult_matrix, which ignores the translation
+        let v = Vec3::new(1.0, -1.0, 2.0);
+        assert_eq!(v.mult_matrix33(&upper_left), v.mult_matrix(&m));
+    }
+
+    #[test]
+    fn test_multiplication_and_transpose() {
+        let a = Matrix33::new([
+            [1.0, 2.0, 0.0],
+            [0.0, 1.0, 3.0],
+            [4.0, 0.0, 1.0],
+        ]);
+
+        assert_eq!(a * Matrix33::identity(), a);
+        assert_eq!(a.transpose().transpose(), a);
+        assert_eq!(a.transpose()[(0, 2)], 4.0);
+        assert_eq!((a * a)[(0, 0)], 1.0);
+        assert_eq!((a * a)[(0, 2)], 6.0);
+    }
+
+    #[test]
+    fn test_determinant() {
+        assert_eq!(Matrix33::identity().determinant(), 1.0);
+        assert_eq!(Matrix33::from_matrix44_upper_left(&Matrix44::scale(2.0, 3.0, -1.0)).determinant(), -6.0);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let rotation = Matrix33::from_matrix44_upper_left(&Matrix44::from_euler_zyx(0.4, 1.1, -2.3));
+        assert_matrix33_close(&(rotation * rotation.inverse().unwrap()), &Matrix33::identity());
+
+        // A rotation's inverse is its transpose
+        assert_matrix33_close(&rotation.inverse().unwrap(), &rotation.transpose());
+
+        let skewed = Matrix33::new([
+            [2.0, 1.0, 0.0],
+            [0.0, 3.0, -1.0],
+            [1.0, 0.0, 0.5],
+        ]);
+        assert_matrix33_close(&(skewed * skewed.inverse().unwrap()), &Matrix33::identity());
+
+        let flat = Matrix33::new([
+            [1.0, 2.0, 3.0],
+            [2.0, 4.0, 6.0],
+            [0.0, 1.0, 0.0],
+        ]);
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn test_normal_transform() {
+        // Squashing a 45 degree slope in y makes it shallower, so its normal must tilt towards +y
+        let squash = Matrix33::from_matrix44_upper_left(&Matrix44::scale(1.0, 0.5, 1.0));
+        let normal_matrix = squash.inverse().unwrap().transpose();
+
+        let tangent = Vec3::new(1.0, 1.0, 0.0).mult_matrix33(&squash);
+        let normal = Vec3::new(-1.0, 1.0, 0.0).mult_matrix33(&normal_matrix);
+        assert_eq!(tangent.dot(&normal), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod matrix44_tests {
+    use super::*;
+
+    #[test]
+    fn test_mul_vec4() {
+        let translation = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [5.0, -1.0, 2.0, 1.0],
+        ]);
+
+        // Points move, directions don't
+        let point = Vec4::new(1.0, 2.0, 3.0, 1.0);
+        let direction = Vec4::new(1.0, 2.0, 3.0, 0.0);
+        assert_eq!(translation.mul_vec4(&point), Vec4::new(6.0, 1.0, 5.0, 1.0));
+        assert_eq!(translation.mul_vec4(&direction), direction);
+
+        // Dividing afterwards gives the same point as homogeneous_mult_matrix
+        let projection = Matrix44::new([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [0.0, 0.0, -1.0, 0.0],
+        ]);
+        let v = Vec3::new(1.0, -3.0, 4.0);
+        let clip = projection.mul_vec4(&Vec4::from_vec3(&v, 1.0));
+        assert_eq!(clip.w, 4.0);
+        assert_eq!(clip.to_vec3(), v.homogeneous_mult_matrix(&projection));
+    }
+
+    #[test]
+    fn test_matrix_multiplication() {
+        let a = Matrix44::new([
+            [1.0, 0.0, 3.0, 4.0],
+            [5.0, 2.0, 1.0, 2.0],
+            [2.0, 1.0, 5.0, 6.0],
+            [1.0, 2.0, 0.0, 4.0],
+        ]);
+
+        let b = a.transpose();
+
+        let c = Matrix44::new([
+            [26.0, 16.0, 41.0, 17.0],
+            [16.0, 34.0, 29.0, 17.0],
+            [41.0, 29.0, 66.0, 28.0],
+            [17.0, 17.0, 28.0, 21.0],
+        ]);
+
+        assert_eq!(a * b, c);
+    }
+
+    #[test]
+    fn test_matrix_multiplication_by_reference() {
+        let view = Matrix44::look_at(&Vec3::new(3.0, 2.0, -5.0), &Vec3::splat(0.0), &Vec3::new(0.0, 1.0, 0.0));
+        let projection = Matrix44::perspective(1.0, 1.5, 0.1, 50.0);
+
+        let by_reference = &view * &projection;
+        assert_eq!(by_reference, view * projection);
+    }
+
+    #[test]
+    fn test_matrix_mul_assign() {
+        let mut model = Matrix44::scale(2.0, 2.0, 2.0);
+        model *= Matrix44::translation(1.0, 0.0, 0.0);
+
+        // Scaled first, then translated
+        assert_eq!(Vec3::new(1.0, 1.0, 1.0).homogeneous_mult_matrix(&model), Vec3::new(3.0, 2.0, 2.0));
+        assert_eq!(model, Matrix44::scale(2.0, 2.0, 2.0) * Matrix44::translation(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_display() {
+        let identity = format!("{}", Matrix44::identity());
+        assert_eq!(identity.lines().count(), 4);
+        assert!(identity.contains("1.00"));
+        assert_eq!(identity.lines().next(), Some("[    1.00     0.00     0.00     0.00]"));
+        assert_eq!(identity.lines().nth(3), Some("[    0.00     0.00     0.00     1.00]"));
+
+        // Columns line up whatever the sign or size of the elements
+        let m = Matrix44::translation(-12.5, 3.0, 100.25);
+        let rows: Vec<String> = format!("{}", m).lines().map(String::from).collect();
+        assert_eq!(rows[3], "[  -12.50     3.00   100.25     1.00]");
+        assert!(rows.iter().all(|row| row.len() == rows[0].len()));
+
+        assert_eq!(format!("{:.1}", Matrix44::identity()).lines().next(), Some("[    1.0     0.0     0.0     0.0]"));
+    }
+
+    #[test]
+    fn test_debug() {
+        let debug = format!("{:?}", Matrix44::translation(0.1, 2.0, 3.0));
+        assert_eq!(debug.lines().count(), 6);
+        assert!(debug.starts_with("Matrix44(["));
+        assert!(debug.contains("[0.1, 2.0, 3.0, 1.0]"));
+    }
+
+    #[test]
+    fn test_index() {
+        let identity = Matrix44::identity();
+        assert_eq!(identity[(0, 0)], 1.0);
+        assert_eq!(identity[(0, 1)], 0.0);
+
+        let mut m = Matrix44::identity();
+        m[(2, 3)] = 5.0;
+        assert_eq!(m[(2, 3)], 5.0);
+        assert_eq!(m.0[2][3], 5.0);
+    }
+
+    #[test]
+    fn test_iter_elements() {
+        let mut m = Matrix44::identity();
+        m[(0, 1)] = 2.0;
+
+        let elements: Vec<f32> = m.iter_elements().collect();
+        assert_eq!(elements.len(), 16);
+        assert_eq!(elements[1], 2.0);
+        assert_eq!(m.iter_elements().sum::<f32>(), 6.0);
+    }
+
+    #[test]
+    fn test_trace() {
+        assert_eq!(Matrix44::identity().trace(), 4.0);
+    }
+
+    #[test]
+    fn test_is_orthogonal() {
+        let angle: f32 = 0.7;
+
+        // Rotate about the z axis
+        let rotation = Matrix44::new([
+            [angle.cos(), -angle.sin(), 0.0, 0.0],
+            [angle.sin(), angle.cos(), 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(rotation.is_orthogonal(1e-5));
+
+        let scale = Matrix44::new([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(!scale.is_orthogonal(1e-5));
+    }
+
+    #[test]
+    fn test_lerp() {
+        let translation = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0, 1.0],
+        ]);
+
+        let halfway = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.5, 0.0, 0.0, 1.0],
+        ]);
+
+        assert_eq!(Matrix44::lerp(&Matrix44::identity(), &translation, 0.5), halfway);
+    }
+
+    #[test]
+    fn test_decompose_lerp() {
+        // Rotate 90 degrees about the z axis, scale by 3 and translate
+        let transformation = Matrix44::new([
+            [0.0, 3.0, 0.0, 0.0],
+            [-3.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 3.0, 0.0],
+            [2.0, 4.0, 6.0, 1.0],
+        ]);
+
+        // Halfway should rotate 45 degrees, scale by 2 and translate half the distance
+        let m = Matrix44::decompose_lerp(&Matrix44::identity(), &transformation, 0.5);
+        let p = Vec3::new(1.0f32, 0.0, 0.0).homogeneous_mult_matrix(&m);
+
+        let expected = 2.0 * std::f32::consts::FRAC_1_SQRT_2;
+        assert!((p.x - (1.0 + expected)).abs() < 1e-5);
+        assert!((p.y - (2.0 + expected)).abs() < 1e-5);
+        assert!((p.z - 3.0).abs() < 1e-5);
+
+        // Rotation and scale are preserved at the end points
+        let end = Matrix44::decompose_lerp(&Matrix44::identity(), &transformation, 1.0);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((end.0[i][j] - transformation.0[i][j]).abs() < 1e-5);
+            }
+        }
+    }
+
+    fn assert_vec3_close(a: &Vec3<f32>, b: &Vec3<f32>) {
+        assert!((a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5 && (a.z - b.z).abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_rotation_from_to() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+
+        let m = Matrix44::rotation_from_to(&x, &y);
+        assert_vec3_close(&x.mult_matrix(&m), &y);
+
+        // Rotating there and back again is the identity
+        let round_trip = Matrix44::rotation_from_to(&x, &y) * Matrix44::rotation_from_to(&y, &x);
+        for (element, identity) in round_trip.iter_elements().zip(Matrix44::identity().iter_elements()) {
+            assert!((element - identity).abs() < 1e-5);
+        }
+
+        // Arbitrary directions which don't need to be unit length
+        let from = Vec3::new(1.0, 2.0, -3.0);
+        let mut to = Vec3::new(-4.0, 0.5, 2.0);
+        let rotated = from.mult_matrix(&Matrix44::rotation_from_to(&from, &to));
+        to.normalise();
+        assert_vec3_close(&rotated, &Vec3::new(to.x * from.len(), to.y * from.len(), to.z * from.len()));
+    }
+
+    #[test]
+    fn test_rotation_from_to_opposite() {
+        let from = Vec3::new(0.0, 0.0, 2.0);
+        let m = Matrix44::rotation_from_to(&from, &Vec3::new(0.0, 0.0, -1.0));
+
+        assert_vec3_close(&from.mult_matrix(&m), &Vec3::new(0.0, 0.0, -2.0));
+        assert!(m.is_orthogonal(1e-5));
+    }
+
+    fn assert_matrix_close(a: &Matrix44, b: &Matrix44) {
+        for (a, b) in a.iter_elements().zip(b.iter_elements()) {
+            assert!((a - b).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_factory_round_trips() {
+        assert_matrix_close(&(Matrix44::translation(1.0, -2.0, 3.5) * Matrix44::translation(-1.0, 2.0, -3.5)), &Matrix44::identity());
+        assert_matrix_close(&(Matrix44::rotation_x(0.7) * Matrix44::rotation_x(-0.7)), &Matrix44::identity());
+        assert_matrix_close(&(Matrix44::rotation_y(0.7) * Matrix44::rotation_y(-0.7)), &Matrix44::identity());
+        assert_matrix_close(&(Matrix44::rotation_z(0.7) * Matrix44::rotation_z(-0.7)), &Matrix44::identity());
+        assert_matrix_close(&(Matrix44::scale(2.0, 4.0, -0.5) * Matrix44::scale(0.5, 0.25, -2.0)), &Matrix44::identity());
+    }
+
+    #[test]
+    fn test_factory_transforms() {
+        use std::f32::consts::FRAC_PI_2;
+
+        let p = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(p.homogeneous_mult_matrix(&Matrix44::translation(1.0, -2.0, 3.0)), Vec3::new(2.0, 0.0, 6.0));
+        assert_eq!(p.homogeneous_mult_matrix(&Matrix44::scale(2.0, 0.5, -1.0)), Vec3::new(2.0, 1.0, -3.0));
+
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        assert_vec3_close(&y.mult_matrix(&Matrix44::rotation_x(FRAC_PI_2)), &z);
+        assert_vec3_close(&z.mult_matrix(&Matrix44::rotation_y(FRAC_PI_2)), &x);
+        assert_vec3_close(&x.mult_matrix(&Matrix44::rotation_z(FRAC_PI_2)), &y);
+
+        // Rotating about x, then y, then z is the same as the ZYX Euler angles
+        let euler = Matrix44::from_euler_zyx(0.3, -0.4, 1.2);
+        let composed = Matrix44::rotation_x(0.3) * Matrix44::rotation_y(-0.4) * Matrix44::rotation_z(1.2);
+        assert_matrix_close(&euler, &composed);
+
+        let shear = Matrix44::shear(2.0, 0.0, 0.0, 0.0, 0.0, -1.0);
+        assert_eq!(Vec3::new(1.0, 1.0, 1.0).mult_matrix(&shear), Vec3::new(3.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_look_at() {
+        let origin = Vec3::splat(0.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        // Forward is +z in camera space, so the origin is 5 units in front of the eye
+        let view = Matrix44::look_at(&Vec3::new(0.0, 0.0, 5.0), &origin, &up);
+        assert_vec3_close(&origin.homogeneous_mult_matrix(&view), &Vec3::new(0.0, 0.0, 5.0));
+        assert_vec3_close(&up.homogeneous_mult_matrix(&view), &Vec3::new(0.0, 1.0, 5.0));
+        // A rotation without reflection
+        assert!((view.determinant() - 1.0).abs() < 1e-6);
+
+        // Looking down +z from behind the origin is the same as the camera tests' translation
+        let view = Matrix44::look_at(&Vec3::new(0.0, 0.0, -10.0), &origin, &up);
+        assert_matrix_close(&view, &Matrix44::translation(0.0, 0.0, 10.0));
+    }
+
+    #[test]
+    fn test_look_at_degenerate() {
+        let eye = Vec3::new(0.0, 5.0, 0.0);
+        let origin = Vec3::splat(0.0);
+
+        assert_eq!(Matrix44::look_at(&eye, &eye, &Vec3::new(0.0, 1.0, 0.0)), Matrix44::identity());
+
+        // Looking straight down with up = +y still gives a valid view
+        let view = Matrix44::look_at(&eye, &origin, &Vec3::new(0.0, 1.0, 0.0));
+        assert!(view.iter_elements().all(f32::is_finite));
+        assert_vec3_close(&origin.homogeneous_mult_matrix(&view), &Vec3::new(0.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn test_perspective() {
+        let fovy = std::f32::consts::FRAC_PI_2;
+        let projection = Matrix44::perspective(fovy, 2.0, 0.1, 100.0);
+        let ndc = |v: Vec3<f32>| projection.mul_vec4(&Vec4::from_vec3(&v, 1.0)).to_vec3();
+
+        // The camera looks along +z, so the centre of the near plane is the closest visible point
+        assert_vec3_close(&ndc(Vec3::new(0.0, 0.0, 0.1)), &Vec3::new(0.0, 0.0, -1.0));
+        assert_vec3_close(&ndc(Vec3::new(0.0, 0.0, 100.0)), &Vec3::new(0.0, 0.0, 1.0));
+
+        // With a 90 degree field of view the top edge is at y = z, and the side edge is aspect times wider
+        assert_vec3_close(&ndc(Vec3::new(0.0, 10.0, 10.0)), &Vec3::new(0.0, 1.0, ndc(Vec3::new(0.0, 0.0, 10.0)).z));
+        assert!((ndc(Vec3::new(-20.0, 0.0, 10.0)).x + 1.0).abs() < 1e-5);
+
+        // Clip space w holds the depth
+        assert_eq!(projection.mul_vec4(&Vec4::new(1.0, 2.0, 7.0, 1.0)).w, 7.0);
+    }
+
+    #[test]
+    fn test_orthographic() {
+        let projection = Matrix44::orthographic(-4.0, 4.0, -2.0, 2.0, 1.0, 9.0);
+        assert_vec3_close(&Vec3::new(-4.0, -2.0, 1.0).homogeneous_mult_matrix(&projection), &Vec3::new(-1.0, -1.0, -1.0));
+        assert_vec3_close(&Vec3::new(4.0, 2.0, 9.0).homogeneous_mult_matrix(&projection), &Vec3::new(1.0, 1.0, 1.0));
+        assert_vec3_close(&Vec3::new(0.0, 0.0, 5.0).homogeneous_mult_matrix(&projection), &Vec3::splat(0.0));
+
+        // A box two units wide on every axis is only moved, so distances are preserved
+        let projection = Matrix44::orthographic(-1.0, 1.0, 0.0, 2.0, 0.0, 2.0);
+        let a = Vec3::new(0.25, 0.5, 1.5);
+        let b = Vec3::new(-0.5, 1.75, 0.5);
+        let projected_distance = a.homogeneous_mult_matrix(&projection).distance(&b.homogeneous_mult_matrix(&projection));
+        assert!((projected_distance - a.distance(&b)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_extract_components() {
+        let angle = 0.8;
+        let m = Matrix44::scale(2.0, 0.5, 3.0) * Matrix44::rotation_z(angle) * Matrix44::translation(1.0, -2.0, 4.0);
+
+        assert_eq!(m.extract_translation(), Vec3::new(1.0, -2.0, 4.0));
+        assert_vec3_close(&m.extract_scale(), &Vec3::new(2.0, 0.5, 3.0));
+        assert_matrix_close(&m.extract_rotation_matrix(), &Matrix44::rotation_z(angle));
+
+        // Recombining the parts gives back the original matrix
+        let s = m.extract_scale();
+        let t = m.extract_translation();
+        let recombined = Matrix44::scale(s.x, s.y, s.z) * m.extract_rotation_matrix() * Matrix44::translation(t.x, t.y, t.z);
+        assert_matrix_close(&recombined, &m);
+    }
+
+    #[test]
+    fn test_determinant() {
+        assert_eq!(Matrix44::identity().determinant(), 1.0);
+
+        let scale = Matrix44::new([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 3.0, 0.0, 0.0],
+            [0.0, 0.0, 4.0, 0.0],
+            [7.0, 8.0, 9.0, 1.0],
+        ]);
+        assert_eq!(scale.determinant(), 24.0);
+
+        // Swapping two rows flips the sign
+        let swapped = Matrix44::new([
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_eq!(swapped.determinant(), -1.0);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let rotation = Matrix44::from_euler_zyx(0.3, -1.1, 2.0);
+        let scale = Matrix44::new([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 0.5, 0.0, 0.0],
+            [0.0, 0.0, -4.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let affine = Matrix44::new([
+            [1.0, 2.0, 0.0, 0.0],
+            [0.5, 3.0, 1.0, 0.0],
+            [-1.0, 0.0, 2.0, 0.0],
+            [4.0, -2.0, 7.0, 1.0],
+        ]);
+
+        for m in [rotation, scale, affine] {
+            let inverse = m.inverse().unwrap();
+            assert_matrix_close(&(m * inverse), &Matrix44::identity());
+        }
+
+        // A general matrix with a non trivial last column
+        let general = Matrix44::new([
+            [1.0, 0.0, 3.0, 4.0],
+            [5.0, 2.0, 1.0, 2.0],
+            [2.0, 1.0, 5.0, 6.0],
+            [1.0, 2.0, 0.0, 4.0],
+        ]);
+        let inverse = general.inverse().unwrap();
+        assert_matrix_close(&(inverse * general), &Matrix44::identity());
+    }
+
+    #[test]
+    fn test_inverse_singular() {
+        let flattened = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_eq!(flattened.determinant(), 0.0);
+        assert!(flattened.inverse().is_none());
+
+        // Linearly dependent rows
+        let dependent = Matrix44::new([
+            [1.0, 2.0, 3.0, 4.0],
+            [2.0, 4.0, 6.0, 8.0],
+            [0.0, 1.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(dependent.inverse().is_none());
+    }
+
+    #[test]
+    fn test_euler_zyx() {
+        // Yaw alone rotates about the z axis
+        let yaw = Matrix44::from_euler_zyx(0.0, 0.0, std::f32::consts::FRAC_PI_2);
+        assert_vec3_close(&Vec3::new(1.0, 0.0, 0.0).mult_matrix(&yaw), &Vec3::new(0.0, 1.0, 0.0));
+
+        for (roll, pitch, yaw) in [(0.3, -0.4, 1.2), (-2.5, 1.1, -0.7), (3.0, 0.0, -3.0)] {
+            let m = Matrix44::from_euler_zyx(roll, pitch, yaw);
+            assert!(m.is_orthogonal(1e-5));
+
+            let angles = m.to_euler_zyx();
+            assert!((angles.0 - roll).abs() < 1e-4);
+            assert!((angles.1 - pitch).abs() < 1e-4);
+            assert!((angles.2 - yaw).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_euler_zyx_gimbal_lock() {
+        for pitch in [std::f32::consts::FRAC_PI_2, -std::f32::consts::FRAC_PI_2] {
+            let m = Matrix44::from_euler_zyx(0.3, pitch, 0.5);
+            let (roll, extracted_pitch, yaw) = m.to_euler_zyx();
+
+            assert!(!roll.is_nan() && !extracted_pitch.is_nan() && !yaw.is_nan());
+            assert_matrix_close(&Matrix44::from_euler_zyx(roll, extracted_pitch, yaw), &m);
+        }
+    }
+
+    #[test]
+    fn test_from_quaternion_translation() {
+        // 90 degree rotation about the z axis
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        let q = Quaternion::new(0.0, 0.0, half_sqrt2, half_sqrt2);
+        let t = Vec3::new(1.0, 2.0, 3.0);
+
+        let m = Matrix44::from_quaternion_translation(&q, &t);
+        assert_eq!(Vec3::new(0.0f32, 0.0, 0.0).homogeneous_mult_matrix(&m), t);
+
+        // (1, 0, 0) is rotated onto the y axis before being translated
+        let p = Vec3::new(1.0f32, 0.0, 0.0).homogeneous_mult_matrix(&m);
+        assert!((p.x - 1.0).abs() < 1e-6);
+        assert!((p.y - 3.0).abs() < 1e-6);
+        assert!((p.z - 3.0).abs() < 1e-6);
+    }
+}
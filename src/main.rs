@@ -3,12 +3,20 @@
 pub mod num;
 pub mod colour;
 pub mod frame_buffer;
+pub mod post_processing;
 
 pub mod linear_algebra;
 pub mod math_helpers;
+pub mod quaternion;
 
 pub mod camera;
 pub mod rasterisation;
+pub mod mesh;
+pub mod shadow_map;
+pub mod texture;
+pub mod sprite;
+pub mod text;
+pub mod animation;
 
 use colour::*;
 use linear_algebra::*;
@@ -18,28 +26,19 @@ use rasterisation::*;
 
 use minifb::{Key, Window, WindowOptions};
 
-const WINDING_ORDER: WindingOrder = WindingOrder::CCW;
 
 const DRAW_WIDTH: usize = 128;
 const DRAW_HEIGHT: usize = 128;
 
-// Convert pixel coordinates to array index
-fn convert_coordinates(px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<usize, FrameBufError> {
-    if (px_x >= width_px || px_y >= height_px) {
-        return Err(frame_buffer::FrameBufError::PixelOutsideBuf);
-    }
-
-    let write_y = height_px - px_y - 1;
-    let index = px_x + (write_y * width_px);
-    Ok(index)
-}
+// Draws triangle1 every frame and triangle2, which shares an edge with it, every other frame
+// Pixels on the shared edge should only ever be drawn by one of them
+const TOP_LEFT_CHECK: bool = false;
 
 impl<const L: usize> FrameBufferTrait for [u32; L] {
 
     fn write_buf(&mut self, px_x: usize, px_y: usize, colour: &Colour, width_px: usize, height_px: usize) -> Result<(), FrameBufError> {
         let index = convert_coordinates(px_x, px_y, width_px, height_px)?;
-        let colours = colour.to_bytes();
-        self[index] = u32::from_be_bytes([0, colours[0], colours[1], colours[2]]); // minifb doesn't use the alpha channel
+        self[index] = colour.to_colour8().to_u32();
 
         Ok(())
     }
@@ -47,17 +46,15 @@ impl<const L: usize> FrameBufferTrait for [u32; L] {
 
     fn read_buf(&self, px_x: usize, px_y: usize, width_px: usize, height_px: usize) -> Result<Colour, FrameBufError> {
         let index = convert_coordinates(px_x, px_y, width_px, height_px)?;
-        let colour = self[index];
-        let colour_bytes: [u8; 4] = u32::to_be_bytes(colour);
+        Ok(Colour8::from_u32(self[index]).to_colour())
+    }
 
-        let colour8 = Colour {
-            red: byte_to_normalised(colour_bytes[1]),
-            green: byte_to_normalised(colour_bytes[2]),
-            blue: byte_to_normalised(colour_bytes[3]),
-            alpha: 1.0,
-        };
+    fn pixel_capacity(&self) -> usize {
+        L
+    }
 
-        Ok(colour8)
+    fn fill(&mut self, colour: &Colour, width_px: usize, height_px: usize) {
+        self[..width_px * height_px].fill(colour.to_colour8().to_u32());
     }
 }
 
@@ -67,17 +64,17 @@ fn main() {
     // Yes ChatGPT made these test triangles
     let v0 = Vertex {
         vertex: Vec3::new(40.0, 8.0, 0.0),  // already Vec3
-        attributes: VertexAttributes { colour: RED },
+        attributes: ColourAttribute { colour: RED },
     };
 
     let v1 = Vertex {
         vertex: Vec3::new(100.0, 60.0, 0.0),  // already Vec3
-        attributes: VertexAttributes { colour: GREEN },
+        attributes: ColourAttribute { colour: GREEN },
     };
 
     let v2 = Vertex {
         vertex: Vec3::new(20.0, 100.0, 0.0),  // Convert Vec2 to Vec3
-        attributes: VertexAttributes { colour: BLUE },
+        attributes: ColourAttribute { colour: BLUE },
     };
 
     let triangle1 = Triangle {
@@ -88,17 +85,17 @@ fn main() {
 
     let v0 = Vertex {
         vertex: Vec3::new(40.0, 8.0, 0.0),  // Convert Vec2 to Vec3
-        attributes: VertexAttributes { colour: BLUE },
+        attributes: ColourAttribute { colour: BLUE },
     };
 
     let v2 = Vertex {
         vertex: Vec3::new(100.0, 60.0, 0.0),  // Convert Vec2 to Vec3
-        attributes: VertexAttributes { colour: RED },
+        attributes: ColourAttribute { colour: RED },
     };
 
     let v1 = Vertex {
         vertex: Vec3::new(120.0, 5.0, 0.0),  // Convert Vec2 to Vec3
-        attributes: VertexAttributes { colour: RED },
+        attributes: ColourAttribute { colour: RED },
     };
 
     let triangle2 = Triangle {
@@ -110,17 +107,17 @@ fn main() {
 
     let v0 = Vertex {
         vertex: Vec3::new(-40.0f32, -40.0, -10.0),
-        attributes: VertexAttributes {colour: RED},
+        attributes: ColourAttribute {colour: RED},
     };
 
     let v1 = Vertex {
         vertex: Vec3::new(60.0f32, 5.0, -5.0),
-        attributes: VertexAttributes {colour: GREEN},
+        attributes: ColourAttribute {colour: GREEN},
     };
 
     let v2 = Vertex {
         vertex: Vec3::new(-5.0f32, 50.0, -10.0),
-        attributes: VertexAttributes {colour: BLUE},
+        attributes: ColourAttribute {colour: BLUE},
     };
 
     let mut triangle3 = Triangle {
@@ -129,9 +126,11 @@ fn main() {
         v2,
     };
 
-    let mut window_options = WindowOptions::default();
-    window_options.scale_mode = minifb::ScaleMode::Stretch;
-    window_options.scale = minifb::Scale::X8;
+    let window_options = WindowOptions {
+        scale_mode: minifb::ScaleMode::Stretch,
+        scale: minifb::Scale::X8,
+        ..WindowOptions::default()
+    };
 
     let mut window = Window::new(
         "Test - ESC to exit",
@@ -147,34 +146,27 @@ fn main() {
 
     let angle: f32 = 0.03;
 
-    // Rotate about the z axis
-    let transformation_matrix =  Matrix44::new([
-        [angle.cos(), -angle.sin(), 0.0, 0.0],
-        [angle.sin(), angle.cos(), 0.0, 0.0],
-        [0.0, 0.0, 1.0, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ]);
+    // Rotate clockwise about the z axis
+    let transformation_matrix = Matrix44::rotation_z(-angle);
 
     let centre = Vec3::new(60.0, 60.0, 0.0);
-    let translation_matrix=  Matrix44::new([
-        [1.0, 0.0, 0.0, 0.0],
-        [0.0, 1.0, 0.0, 0.0],
-        [0.0, 0.0, 1.0, 0.0],
-        [centre.x, centre.y, centre.z, 1.0],
-    ]);
+    let translation_matrix = Matrix44::translation(centre.x, centre.y, centre.z);
+
+    let rasterise_options = RasteriseOptions::default();
 
     let mut count = 0;
     while window.is_open() && !window.is_key_down(Key::Escape) {
         frame_buffer.clear_buf();
 
         triangle3.transform_this_triangle(&transformation_matrix);
-        rasterise_triangle(&triangle3.transform_triangle(&translation_matrix), &mut frame_buffer, &WINDING_ORDER);
+        rasterise_triangle_with_options(&triangle3.transform_triangle(&translation_matrix), &mut frame_buffer, &rasterise_options);
 
-        // Top left check
-        // rasterise_triangle(&triangle1, &mut frame_buffer, &WINDING_ORDER);
-        // if count % 2 == 0 {
-        //     rasterise_triangle(&triangle2, &mut frame_buffer, &WINDING_ORDER);
-        // }
+        if TOP_LEFT_CHECK {
+            rasterise_triangle_with_options(&triangle1, &mut frame_buffer, &rasterise_options);
+            if count % 2 == 0 {
+                rasterise_triangle_with_options(&triangle2, &mut frame_buffer, &rasterise_options);
+            }
+        }
 
         count += 1;
 
@@ -190,12 +182,7 @@ fn main() {
 
 
 // fn main() {
-//     let camera_transformation_matrix =  Matrix44::new([
-//         [1.0, 0.0, 0.0, 0.0],
-//         [0.0, 1.0, 0.0, 0.0],
-//         [0.0, 0.0, 1.0, 0.0],
-//         [0.0, 0.0, -10.0, 1.0],
-//     ]);
+//     let camera_transformation_matrix = Matrix44::translation(0.0, 0.0, -10.0);
 
 //     let camera = camera::Camera::new(camera_transformation_matrix, Vec2::new(100, 100), 15.0, Vec2::new(36.0, 24.0), 0.1, 100.0, camera::FitResolutionGate::Fill);
 
@@ -1,2 +1,323 @@
-use crate::num::Num;
-use crate::linear_algebra::Vec2;
+use crate::linear_algebra::Vec3;
+
+// Linearly interpolates between two points, t is not clamped
+// Bezier construction relies on this to produce intermediate points
+pub fn lerp(a: &Vec3<f32>, b: &Vec3<f32>, t: f32) -> Vec3<f32> {
+    Vec3::lerp_unclamped(a, b, t)
+}
+
+// Cubic bezier curve defined by two end points (p0, p3) and two control points (p1, p2)
+// The curve is parameterised by t in [0, 1]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CubicBezier {
+    pub p0: Vec3<f32>,
+    pub p1: Vec3<f32>,
+    pub p2: Vec3<f32>,
+    pub p3: Vec3<f32>,
+}
+
+impl CubicBezier {
+    pub fn new(p0: Vec3<f32>, p1: Vec3<f32>, p2: Vec3<f32>, p3: Vec3<f32>) -> Self {
+        CubicBezier {p0, p1, p2, p3}
+    }
+
+    // Returns the point on the curve at t using the de Casteljau algorithm
+    pub fn evaluate(&self, t: f32) -> Vec3<f32> {
+        let (left, _) = self.split(t);
+        left.p3
+    }
+
+    // Returns the tangent (first derivative with respect to t) of the curve at t
+    pub fn derivative(&self, t: f32) -> Vec3<f32> {
+        let u = 1.0 - t;
+
+        // Weights of the quadratic bezier formed by the differences of the control points
+        let w0 = 3.0 * u * u;
+        let w1 = 6.0 * u * t;
+        let w2 = 3.0 * t * t;
+
+        Vec3::new(
+            w0 * (self.p1.x - self.p0.x) + w1 * (self.p2.x - self.p1.x) + w2 * (self.p3.x - self.p2.x),
+            w0 * (self.p1.y - self.p0.y) + w1 * (self.p2.y - self.p1.y) + w2 * (self.p3.y - self.p2.y),
+            w0 * (self.p1.z - self.p0.z) + w1 * (self.p2.z - self.p1.z) + w2 * (self.p3.z - self.p2.z),
+        )
+    }
+
+    // Approximates the length of the curve by summing the lengths of straight line segments between samples
+    // More samples gives a more accurate result
+    pub fn arc_length_approximation(&self, samples: usize) -> f32 {
+        let samples = samples.max(1);
+
+        let mut length = 0.0;
+        let mut previous = self.p0;
+        for i in 1..=samples {
+            let point = self.evaluate(i as f32 / samples as f32);
+            length += previous.distance(&point);
+            previous = point;
+        }
+
+        length
+    }
+
+    // Splits the curve at t into two curves which together trace the original curve
+    // The first curve covers [0, t] and the second covers [t, 1]
+    pub fn split(&self, t: f32) -> (CubicBezier, CubicBezier) {
+        // de Casteljau construction
+        let p01 = lerp(&self.p0, &self.p1, t);
+        let p12 = lerp(&self.p1, &self.p2, t);
+        let p23 = lerp(&self.p2, &self.p3, t);
+
+        let p012 = lerp(&p01, &p12, t);
+        let p123 = lerp(&p12, &p23, t);
+
+        let p0123 = lerp(&p012, &p123, t);
+
+        (
+            CubicBezier::new(self.p0, p01, p012, p0123),
+            CubicBezier::new(p0123, p123, p23, self.p3),
+        )
+    }
+}
+
+// Number of samples taken per segment when traversing a spline by distance
+const CATMULL_ROM_SAMPLES_PER_SEGMENT: usize = 32;
+
+// Uniform Catmull-Rom spline which passes through every control point
+// The spline is parameterised by t in [0, n - 1] where n is the number of control points, control point i is at t = i
+// The first and last control points are repeated to define the tangents at the ends of the spline
+// Panics if evaluated without any control points
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatmullRomSpline(pub Vec<Vec3<f32>>);
+
+impl CatmullRomSpline {
+    pub fn new(points: Vec<Vec3<f32>>) -> Self {
+        CatmullRomSpline(points)
+    }
+
+    // Returns the point on the spline at t, t is clamped to [0, n - 1]
+    pub fn evaluate(&self, t: f32) -> Vec3<f32> {
+        let last = self.0.len() - 1;
+        if last == 0 {
+            return self.0[0];
+        }
+
+        let t = t.clamp(0.0, last as f32);
+        let segment = (t.floor() as usize).min(last - 1);
+        let u = t - segment as f32;
+
+        // Control points surrounding the segment, the ends of the spline are repeated
+        let p0 = &self.0[segment.saturating_sub(1)];
+        let p1 = &self.0[segment];
+        let p2 = &self.0[segment + 1];
+        let p3 = &self.0[(segment + 2).min(last)];
+
+        let u2 = u * u;
+        let u3 = u2 * u;
+        let catmull_rom = |p0: f32, p1: f32, p2: f32, p3: f32| {
+            0.5 * (2.0 * p1 +
+                   (p2 - p0) * u +
+                   (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2 +
+                   (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3)
+        };
+
+        Vec3::new(
+            catmull_rom(p0.x, p1.x, p2.x, p3.x),
+            catmull_rom(p0.y, p1.y, p2.y, p3.y),
+            catmull_rom(p0.z, p1.z, p2.z, p3.z),
+        )
+    }
+
+    // Approximates the length of the spline by summing the lengths of straight line segments between samples
+    pub fn total_length(&self, samples_per_segment: usize) -> f32 {
+        match self.cumulative_lengths(samples_per_segment).last() {
+            Some((_, length)) => *length,
+            None => 0.0,
+        }
+    }
+
+    // Returns the point which is distance d along the spline, d is clamped to the length of the spline
+    // Stepping d by a constant amount traverses the spline at a uniform speed
+    pub fn evaluate_at_distance(&self, d: f32) -> Vec3<f32> {
+        let lengths = self.cumulative_lengths(CATMULL_ROM_SAMPLES_PER_SEGMENT);
+
+        // Find the pair of samples d lies between and interpolate t between them
+        for window in lengths.windows(2) {
+            let (t0, length0) = window[0];
+            let (t1, length1) = window[1];
+
+            if d <= length1 {
+                let segment_length = length1 - length0;
+                if segment_length <= 0.0 {
+                    return self.evaluate(t0);
+                }
+
+                let fraction = ((d - length0) / segment_length).clamp(0.0, 1.0);
+                return self.evaluate(t0 + (t1 - t0) * fraction);
+            }
+        }
+
+        self.evaluate((self.0.len() - 1) as f32)
+    }
+
+    // Samples the spline and returns (t, distance along the spline) for every sample
+    fn cumulative_lengths(&self, samples_per_segment: usize) -> Vec<(f32, f32)> {
+        if self.0.len() < 2 {
+            return Vec::new();
+        }
+
+        let samples = (self.0.len() - 1) * samples_per_segment.max(1);
+        let step = (self.0.len() - 1) as f32 / samples as f32;
+
+        let mut lengths = Vec::with_capacity(samples + 1);
+        let mut length = 0.0;
+        let mut previous = self.0[0];
+        lengths.push((0.0, 0.0));
+
+        for i in 1..=samples {
+            let t = i as f32 * step;
+            let point = self.evaluate(t);
+            length += previous.distance(&point);
+            lengths.push((t, length));
+            previous = point;
+        }
+
+        lengths
+    }
+}
+
+#[cfg(test)]
+mod cubic_bezier_tests {
+    use super::*;
+
+    fn symmetric_curve() -> CubicBezier {
+        CubicBezier::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_evaluate_end_points() {
+        let curve = symmetric_curve();
+
+        assert_eq!(curve.evaluate(0.0), curve.p0);
+        assert_eq!(curve.evaluate(1.0), curve.p3);
+    }
+
+    #[test]
+    fn test_evaluate_midpoint() {
+        let curve = symmetric_curve();
+
+        assert_eq!(curve.evaluate(0.5), Vec3::new(0.5, 0.75, 0.0));
+    }
+
+    #[test]
+    fn test_derivative() {
+        let curve = symmetric_curve();
+
+        // Tangents at the end points point towards the neighbouring control points
+        assert_eq!(curve.derivative(0.0), Vec3::new(0.0, 3.0, 0.0));
+        assert_eq!(curve.derivative(1.0), Vec3::new(0.0, -3.0, 0.0));
+        assert_eq!(curve.derivative(0.5), Vec3::new(1.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_arc_length_approximation() {
+        // A curve with evenly spaced collinear control points is a straight line
+        let line = CubicBezier::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(3.0, 0.0, 0.0),
+        );
+
+        assert!((line.arc_length_approximation(16) - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_split() {
+        let curve = symmetric_curve();
+        let (left, right) = curve.split(0.5);
+
+        assert_eq!(left.p0, curve.p0);
+        assert_eq!(left.p3, curve.evaluate(0.5));
+        assert_eq!(right.p0, curve.evaluate(0.5));
+        assert_eq!(right.p3, curve.p3);
+
+        // The first half of the split curve at 0.5 should match the original curve at 0.25
+        assert_eq!(left.evaluate(0.5), curve.evaluate(0.25));
+    }
+}
+
+#[cfg(test)]
+mod catmull_rom_tests {
+    use super::*;
+
+    fn assert_close(a: &Vec3<f32>, b: &Vec3<f32>) {
+        assert!(a.distance(b) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn spline() -> CatmullRomSpline {
+        CatmullRomSpline::new(vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 2.0, 0.0),
+            Vec3::new(3.0, 2.0, 1.0),
+            Vec3::new(4.0, 0.0, 1.0),
+        ])
+    }
+
+    #[test]
+    fn test_passes_through_control_points() {
+        let spline = spline();
+
+        for (i, point) in spline.0.iter().enumerate() {
+            assert_close(&spline.evaluate(i as f32), point);
+        }
+    }
+
+    #[test]
+    fn test_smooth_between_control_points() {
+        let spline = spline();
+
+        // Approaching a control point from either side should give the same direction of travel
+        let epsilon = 1e-2;
+        let before = spline.evaluate(1.0 - epsilon);
+        let at = spline.evaluate(1.0);
+        let after = spline.evaluate(1.0 + epsilon);
+
+        let mut incoming = Vec3::new(at.x - before.x, at.y - before.y, at.z - before.z);
+        let mut outgoing = Vec3::new(after.x - at.x, after.y - at.y, after.z - at.z);
+        incoming.normalise();
+        outgoing.normalise();
+
+        assert!(incoming.dot(&outgoing) > 0.99);
+    }
+
+    #[test]
+    fn test_total_length() {
+        // Evenly spaced collinear points form a straight line
+        let line = CatmullRomSpline::new(vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+        ]);
+
+        assert!((line.total_length(16) - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_evaluate_at_distance() {
+        let line = CatmullRomSpline::new(vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+        ]);
+
+        assert_close(&line.evaluate_at_distance(0.0), &Vec3::new(0.0, 0.0, 0.0));
+        assert!((line.evaluate_at_distance(0.5).x - 0.5).abs() < 1e-2);
+        assert!((line.evaluate_at_distance(1.5).x - 1.5).abs() < 1e-2);
+        assert_close(&line.evaluate_at_distance(10.0), &Vec3::new(2.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use crate::camera::Camera;
+use crate::linear_algebra::Vec3;
+use crate::rasterisation::{Triangle, VertexAttribute, ColourAttribute, FullAttribute};
+
+// Triangles with a smaller screen space area than this (in pixels) are considered too detailed when selecting a level of detail
+pub const LOD_MIN_TRIANGLE_AREA: f32 = 4.0;
+
+// Collection of triangles which make up a single object
+#[derive(Clone)]
+pub struct Mesh<A: VertexAttribute = ColourAttribute> {
+    pub triangles: Vec<Triangle<f32, A>>,
+}
+
+impl<A: VertexAttribute> Mesh<A> {
+    pub fn new(triangles: Vec<Triangle<f32, A>>) -> Self {
+        Mesh {triangles}
+    }
+
+    // Returns the smallest screen space area of the triangles which can be projected by the camera
+    // Returns None if no triangles can be projected
+    pub fn min_screen_space_area(&self, camera: &Camera) -> Option<f32> {
+        self.triangles.iter()
+            .filter_map(|triangle| triangle.screen_space_area(camera))
+            .reduce(f32::min)
+    }
+
+    // Picks a level of detail from lods which are ordered from highest to lowest resolution
+    // The highest resolution mesh whose smallest visible triangle covers more than LOD_MIN_TRIANGLE_AREA pixels is returned
+    // If no mesh satisfies this the lowest resolution mesh is returned
+    // Panics if lods is empty
+    pub fn select_lod<'a>(camera: &Camera, lods: &'a [Mesh<A>]) -> &'a Mesh<A> {
+        lods.iter()
+            .find(|lod| lod.min_screen_space_area(camera).is_some_and(|area| area > LOD_MIN_TRIANGLE_AREA))
+            .unwrap_or_else(|| lods.last().expect("Cannot select a level of detail without any meshes"))
+    }
+}
+
+impl Mesh<FullAttribute> {
+    // Replaces every vertex normal with the average of the face normals of the triangles which share its position
+    // Vertices closer together than weld_distance are treated as the same position
+    // Face normals are weighted by the angle of the triangle at the shared vertex so the result doesn't depend on how faces are triangulated
+    // Face normals follow the counter clockwise winding order
+    pub fn compute_smooth_normals(&mut self, weld_distance: f32) {
+        let faces: Vec<_> = self.triangles.iter()
+            .map(|triangle| {
+                let positions = triangle.vertices().map(|vertex| vertex.vertex);
+                let (normal, angles) = face_normal_and_angles(&positions);
+                (positions, normal, angles)
+            })
+            .collect();
+
+        // Corners are bucketed in a grid of cells at least weld_distance wide
+        // Positions within weld_distance of each other are then always in the same or neighbouring cells
+        let cell_size = if weld_distance > 0.0 {weld_distance} else {1.0};
+        let mut grid: HashMap<(i64, i64, i64), Vec<(usize, usize)>> = HashMap::new();
+        for (face, (positions, _, _)) in faces.iter().enumerate() {
+            for (corner, position) in positions.iter().enumerate() {
+                grid.entry(grid_cell(position, cell_size)).or_default().push((face, corner));
+            }
+        }
+
+        for (triangle, (positions, _, _)) in self.triangles.iter_mut().zip(faces.iter()) {
+            for (vertex, position) in triangle.vertices_mut().into_iter().zip(positions) {
+                let (cell_x, cell_y, cell_z) = grid_cell(position, cell_size);
+                let mut shared: Vec<(usize, usize)> = Vec::new();
+                for x in cell_x.saturating_sub(1)..=cell_x.saturating_add(1) {
+                    for y in cell_y.saturating_sub(1)..=cell_y.saturating_add(1) {
+                        for z in cell_z.saturating_sub(1)..=cell_z.saturating_add(1) {
+                            let Some(corners) = grid.get(&(x, y, z)) else {
+                                continue;
+                            };
+                            shared.extend(corners.iter().filter(|(face, corner)| (&faces[*face].0[*corner] - position).len() <= weld_distance));
+                        }
+                    }
+                }
+
+                // Each triangle contributes at most once, using its first corner at the shared position
+                shared.sort();
+                shared.dedup_by_key(|(face, _)| *face);
+
+                let mut normal = Vec3::splat(0.0);
+                for (face, corner) in shared {
+                    let (_, face_normal, angles) = &faces[face];
+                    normal = Vec3::new(
+                        normal.x + face_normal.x * angles[corner],
+                        normal.y + face_normal.y * angles[corner],
+                        normal.z + face_normal.z * angles[corner],
+                    );
+                }
+
+                // Degenerate triangles have no normal to contribute
+                if normal.len() > 0.0 {
+                    normal.normalise();
+                    vertex.attributes.normal = normal;
+                }
+            }
+        }
+    }
+}
+
+// Axis aligned bounding box
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoundingBox3D {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+impl BoundingBox3D {
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Self {
+        BoundingBox3D {min, max}
+    }
+
+    // Returns the smallest box containing every vertex of the triangles
+    // Without any triangles the box is empty, min is infinite and max is negative infinite
+    pub fn from_triangles<A: VertexAttribute>(triangles: &[Triangle<f32, A>]) -> Self {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for vertex in triangles.iter().flat_map(|triangle| triangle.vertices()) {
+            min = min.component_min(&vertex.vertex);
+            max = max.component_max(&vertex.vertex);
+        }
+
+        BoundingBox3D {min, max}
+    }
+
+    // Returns the 8 corners of the box, bit 0 of the index picks max.x, bit 1 max.y and bit 2 max.z
+    pub fn corners(&self) -> [Vec3<f32>; 8] {
+        let mut corners = [self.min; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            if i & 1 != 0 { corner.x = self.max.x; }
+            if i & 2 != 0 { corner.y = self.max.y; }
+            if i & 4 != 0 { corner.z = self.max.z; }
+        }
+        corners
+    }
+
+    // Returns true if any part of the triangle is inside the box, touching counts as intersecting
+    // Uses the separating axis test from Akenine-Möller 2001, "Fast 3D Triangle-Box Overlap Testing"
+    // The 13 candidate axes are the 3 box face normals, the triangle normal, and the 9 cross products of the box and triangle edges
+    pub fn intersects_triangle<A: VertexAttribute>(&self, tri: &Triangle<f32, A>) -> bool {
+        let centre = Vec3::new((self.min.x + self.max.x) * 0.5, (self.min.y + self.max.y) * 0.5, (self.min.z + self.max.z) * 0.5);
+        let half_size = self.max - centre;
+
+        // Move the triangle so the box is centred on the origin
+        let v = tri.vertices().map(|vertex| vertex.vertex - centre);
+        let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+
+        // The axis separates the shapes if the triangle's projection onto it doesn't overlap the box's projection
+        let separated = |axis: &Vec3<f32>| {
+            let p = v.map(|vertex| vertex.dot(axis));
+            let r = half_size.x * axis.x.abs() + half_size.y * axis.y.abs() + half_size.z * axis.z.abs();
+            p[0].min(p[1]).min(p[2]) > r || p[0].max(p[1]).max(p[2]) < -r
+        };
+
+        let box_normals = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+        let cross_axes = box_normals.iter().flat_map(|normal| edges.iter().map(|edge| normal.cross(edge)));
+        let triangle_normal = edges[0].cross(&edges[1]);
+
+        !box_normals.into_iter()
+            .chain(cross_axes)
+            .chain([triangle_normal])
+            .any(|axis| separated(&axis))
+    }
+}
+
+// Returns the grid cell containing position for cells of the given size
+fn grid_cell(position: &Vec3<f32>, cell_size: f32) -> (i64, i64, i64) {
+    let cell = |coordinate: f32| (coordinate / cell_size).floor() as i64;
+    (cell(position.x), cell(position.y), cell(position.z))
+}
+
+// Returns the unit face normal of a triangle and its interior angle at each vertex
+// Degenerate triangles return a zero normal and zero angles
+fn face_normal_and_angles(positions: &[Vec3<f32>; 3]) -> (Vec3<f32>, [f32; 3]) {
+    let mut normal = (positions[1] - positions[0]).cross(&(positions[2] - positions[0]));
+    if normal.len() == 0.0 {
+        return (normal, [0.0; 3]);
+    }
+    normal.normalise();
+
+    let mut angles = [0.0; 3];
+    for (i, angle) in angles.iter_mut().enumerate() {
+        let to_next = positions[(i + 1) % 3] - positions[i];
+        let to_previous = positions[(i + 2) % 3] - positions[i];
+        let cos_angle = to_next.dot(&to_previous) / (to_next.len() * to_previous.len());
+        *angle = cos_angle.clamp(-1.0, 1.0).acos();
+    }
+
+    (normal, angles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::FitResolutionGate;
+    use crate::colour::WHITE;
+    use crate::linear_algebra::{Matrix44, Vec2, Vec3};
+    use crate::rasterisation::Vertex;
+
+    fn camera() -> Camera {
+        Camera::new(Matrix44::identity(), Vec2::new(100, 100), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill)
+    }
+
+    fn triangle(x: f32, y: f32, size: f32, z: f32) -> Triangle<f32> {
+        let attributes = ColourAttribute { colour: WHITE };
+        Triangle::new(
+            Vertex::new(Vec3::new(x, y, z), attributes),
+            Vertex::new(Vec3::new(x + size, y, z), attributes),
+            Vertex::new(Vec3::new(x, y + size, z), attributes),
+        )
+    }
+
+    // Fine and coarse versions of the same object at distance z from the camera
+    fn lods(z: f32) -> [Mesh; 2] {
+        let fine = Mesh::new(vec![
+            triangle(0.0, 0.0, 0.05, z),
+            triangle(0.05, 0.0, 0.05, z),
+            triangle(0.0, 0.05, 0.05, z),
+        ]);
+        let coarse = Mesh::new(vec![triangle(0.0, 0.0, 0.1, z)]);
+
+        [fine, coarse]
+    }
+
+    #[test]
+    fn test_bounding_box_from_triangles() {
+        let bounding_box = BoundingBox3D::from_triangles(&[triangle(0.0, 1.0, 2.0, 5.0), triangle(-1.0, 0.0, 1.0, 3.0)]);
+        assert_eq!(bounding_box, BoundingBox3D::new(Vec3::new(-1.0, 0.0, 3.0), Vec3::new(2.0, 3.0, 5.0)));
+
+        let corners = bounding_box.corners();
+        assert_eq!(corners[0], bounding_box.min);
+        assert_eq!(corners[7], bounding_box.max);
+        assert_eq!(corners[5], Vec3::new(2.0, 0.0, 5.0));
+    }
+
+    fn unit_box() -> BoundingBox3D {
+        BoundingBox3D::new(Vec3::splat(-1.0), Vec3::splat(1.0))
+    }
+
+    #[test]
+    fn test_intersects_triangle_inside_and_outside() {
+        let bounding_box = unit_box();
+
+        assert!(bounding_box.intersects_triangle(&triangle(-0.5, -0.5, 0.5, 0.0)));
+        assert!(!bounding_box.intersects_triangle(&triangle(2.0, 2.0, 1.0, 0.0)));
+        assert!(!bounding_box.intersects_triangle(&triangle(-0.5, -0.5, 0.5, 1.5)));
+    }
+
+    #[test]
+    fn test_intersects_triangle_straddling_face() {
+        let bounding_box = unit_box();
+
+        // Triangle passing through the +x face
+        assert!(bounding_box.intersects_triangle(&triangle(0.5, -0.5, 1.0, 0.0)));
+
+        // Large triangle whose vertices are all outside but which cuts through the box
+        assert!(bounding_box.intersects_triangle(&triangle(-5.0, -5.0, 12.0, 0.5)));
+    }
+
+    #[test]
+    fn test_intersects_triangle_separated_by_edge_axis() {
+        // Triangle diagonally beyond the corner of the box, the bounding boxes overlap but the shapes don't
+        let attributes = ColourAttribute { colour: WHITE };
+        let triangle = Triangle::new(
+            Vertex::new(Vec3::new(2.5, 0.0, 0.0), attributes),
+            Vertex::new(Vec3::new(0.0, 2.5, 0.0), attributes),
+            Vertex::new(Vec3::new(2.5, 2.5, 0.0), attributes),
+        );
+        assert!(!unit_box().intersects_triangle(&triangle));
+
+        // Moving it towards the corner makes it touch
+        let mut touching = triangle;
+        for vertex in touching.vertices_mut() {
+            vertex.vertex = Vec3::new(vertex.vertex.x - 0.5, vertex.vertex.y - 0.5, 0.0);
+        }
+        assert!(unit_box().intersects_triangle(&touching));
+    }
+
+    #[test]
+    fn test_select_lod_near() {
+        let camera = camera();
+        let lods = lods(1.0);
+
+        let selected = Mesh::select_lod(&camera, &lods);
+        assert_eq!(selected.triangles.len(), lods[0].triangles.len());
+    }
+
+    #[test]
+    fn test_select_lod_far() {
+        let camera = camera();
+        let lods = lods(50.0);
+
+        let selected = Mesh::select_lod(&camera, &lods);
+        assert_eq!(selected.triangles.len(), lods[1].triangles.len());
+    }
+
+    // Cube centred on the origin with each face split into two triangles
+    fn cube() -> Mesh<FullAttribute> {
+        let attributes = FullAttribute { colour: WHITE, uv: Vec2::splat(0.0), normal: Vec3::splat(0.0) };
+        let faces = [
+            [(1.0, -1.0, -1.0), (1.0, 1.0, -1.0), (1.0, 1.0, 1.0), (1.0, -1.0, 1.0)],
+            [(-1.0, -1.0, -1.0), (-1.0, -1.0, 1.0), (-1.0, 1.0, 1.0), (-1.0, 1.0, -1.0)],
+            [(-1.0, 1.0, -1.0), (-1.0, 1.0, 1.0), (1.0, 1.0, 1.0), (1.0, 1.0, -1.0)],
+            [(-1.0, -1.0, -1.0), (1.0, -1.0, -1.0), (1.0, -1.0, 1.0), (-1.0, -1.0, 1.0)],
+            [(-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (1.0, 1.0, 1.0), (-1.0, 1.0, 1.0)],
+            [(-1.0, -1.0, -1.0), (-1.0, 1.0, -1.0), (1.0, 1.0, -1.0), (1.0, -1.0, -1.0)],
+        ];
+
+        let vertex = |(x, y, z): (f32, f32, f32)| Vertex::new(Vec3::new(x, y, z), attributes);
+        let mut triangles = Vec::new();
+        for [a, b, c, d] in faces {
+            triangles.push(Triangle::new(vertex(a), vertex(b), vertex(c)));
+            triangles.push(Triangle::new(vertex(a), vertex(c), vertex(d)));
+        }
+
+        Mesh::new(triangles)
+    }
+
+    #[test]
+    fn test_compute_smooth_normals_cube() {
+        let mut cube = cube();
+        cube.compute_smooth_normals(1e-4);
+
+        // Each corner normal points diagonally out of the cube, making the same angle with all three faces
+        for triangle in cube.triangles.iter() {
+            for vertex in triangle.vertices() {
+                let mut expected = vertex.vertex;
+                expected.normalise();
+
+                let normal = vertex.attributes.normal;
+                assert!((normal.dot(&expected) - 1.0).abs() < 1e-5, "{:?} != {:?}", normal, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_smooth_normals_weld_distance() {
+        // Two faces meeting at a right angle whose shared edge is slightly separated
+        let attributes = FullAttribute { colour: WHITE, uv: Vec2::splat(0.0), normal: Vec3::splat(0.0) };
+        let vertex = |x: f32, y: f32, z: f32| Vertex::new(Vec3::new(x, y, z), attributes);
+        let floor = Triangle::new(vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 0.0, -1.0));
+        let wall = Triangle::new(vertex(0.0, 0.001, 0.0), vertex(0.0, 1.0, 0.0), vertex(1.0, 0.001, 0.0));
+
+        // Not welded so the floor keeps its face normal
+        let mut mesh = Mesh::new(vec![floor, wall]);
+        mesh.compute_smooth_normals(1e-4);
+        assert_eq!(mesh.triangles[0].v0.attributes.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // Welded so the shared vertices average the two faces
+        let mut mesh = Mesh::new(vec![floor, wall]);
+        mesh.compute_smooth_normals(1e-2);
+        let normal = mesh.triangles[0].v0.attributes.normal;
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((normal.y - half_sqrt2).abs() < 1e-2 && (normal.z + half_sqrt2).abs() < 1e-2, "{:?}", normal);
+    }
+
+    #[test]
+    fn test_compute_smooth_normals_across_grid_cells() {
+        // The shared edge straddles y = 0 so the two sides are bucketed in different grid cells
+        let attributes = FullAttribute { colour: WHITE, uv: Vec2::splat(0.0), normal: Vec3::splat(0.0) };
+        let vertex = |x: f32, y: f32, z: f32| Vertex::new(Vec3::new(x, y, z), attributes);
+        let floor = Triangle::new(vertex(0.0, -0.004, 0.0), vertex(1.0, -0.004, 0.0), vertex(0.0, -0.004, -1.0));
+        let wall = Triangle::new(vertex(0.0, 0.004, 0.0), vertex(0.0, 1.0, 0.0), vertex(1.0, 0.004, 0.0));
+
+        let mut mesh = Mesh::new(vec![floor, wall]);
+        mesh.compute_smooth_normals(1e-2);
+        let normal = mesh.triangles[0].v0.attributes.normal;
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((normal.y - half_sqrt2).abs() < 1e-2 && (normal.z + half_sqrt2).abs() < 1e-2, "{:?}", normal);
+
+        // The far corner of the floor isn't near the wall so it keeps the floor's normal
+        assert_eq!(mesh.triangles[0].v2.attributes.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+}
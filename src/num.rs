@@ -1,6 +1,74 @@
-use std::ops::{Add, Sub, Mul, Neg};
+use std::ops::{Add, Sub, Mul, Neg, Div};
 
-pub trait Num: Copy + Mul<Output = Self> + Neg<Output = Self> + Add<Output = Self> + Sub<Output = Self> + PartialEq + PartialOrd {}
+pub trait Num: Copy + Mul<Output = Self> + Neg<Output = Self> + Add<Output = Self> + Sub<Output = Self> + PartialEq + PartialOrd {
+    // Restricts self to [min, max]
+    // Unlike f32::clamp this doesn't panic if min > max, min is checked first so min is returned
+    // Call as Num::clamp(x, min, max) where the inherent or Ord clamp would otherwise be picked
+    fn clamp(self, min: Self, max: Self) -> Self {
+        if self < min {
+            min
+        } else if self > max {
+            max
+        } else {
+            self
+        }
+    }
+}
+
+// Floating point numbers, used for operations which need division or square roots
+pub trait FloatNum: Num + Div<Output = Self> {
+    fn sqrt(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+
+    // Conversions to and from f32, Matrix44 stores its elements as f32
+    fn from_f32(value: f32) -> Self;
+    fn to_f32(self) -> f32;
+}
+
+impl FloatNum for f64 {
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn floor(self) -> Self {
+        f64::floor(self)
+    }
+
+    fn ceil(self) -> Self {
+        f64::ceil(self)
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+}
+
+impl FloatNum for f32 {
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+
+    fn floor(self) -> Self {
+        f32::floor(self)
+    }
+
+    fn ceil(self) -> Self {
+        f32::ceil(self)
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
 
 impl Num for f64{}
 impl Num for f32 {}
@@ -15,4 +83,26 @@ impl Num for i128{}
 impl Num for i64 {}
 impl Num for i32 {}
 impl Num for i16 {}
-impl Num for i8 {}
\ No newline at end of file
+impl Num for i8 {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp_integer() {
+        assert_eq!(Num::clamp(-3i32, 0, 10), 0);
+        assert_eq!(Num::clamp(0i32, 0, 10), 0);
+        assert_eq!(Num::clamp(4i32, 0, 10), 4);
+        assert_eq!(Num::clamp(10i32, 0, 10), 10);
+        assert_eq!(Num::clamp(12i32, 0, 10), 10);
+    }
+
+    #[test]
+    fn test_clamp_float() {
+        assert_eq!(Num::clamp(-0.5f32, 0.0, 1.0), 0.0);
+        assert_eq!(Num::clamp(0.0f32, 0.0, 1.0), 0.0);
+        assert_eq!(Num::clamp(0.25f32, 0.0, 1.0), 0.25);
+        assert_eq!(Num::clamp(1.0f32, 0.0, 1.0), 1.0);
+        assert_eq!(Num::clamp(1.5f64, 0.0, 1.0), 1.0);
+    }
+}
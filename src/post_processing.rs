@@ -0,0 +1,819 @@
+// This file contains post processing effects which are applied to a finished frame buffer
+// Each effect returns a new buffer and leaves the source untouched
+
+use crate::camera::Camera;
+use crate::colour::{Colour, ColourEncoding};
+use crate::frame_buffer::{FrameBuffer, FrameBufferTrait};
+use crate::linear_algebra::{Vec2, Vec3};
+
+// How quickly fog thickens with distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    Linear, // No fog at fog_start increasing linearly to full fog at fog_end
+    Exponential {density: f32}, // 1 - e^(-density * d) where d is the distance past fog_start
+    ExponentialSquared {density: f32}, // 1 - e^(-(density * d)^2), stays clearer near fog_start then thickens faster
+}
+
+impl FogMode {
+    // Returns the amount of fog in [0, 1] at depth
+    pub fn factor(&self, depth: f32, fog_start: f32, fog_end: f32) -> f32 {
+        let distance = (depth - fog_start).max(0.0);
+
+        let factor = match self {
+            // With no room to fade in, fog switches on as soon as depth passes fog_start
+            FogMode::Linear if fog_end <= fog_start => if depth > fog_start {1.0} else {0.0},
+            FogMode::Linear => distance / (fog_end - fog_start),
+            FogMode::Exponential {density} => 1.0 - (-density * distance).exp(),
+            FogMode::ExponentialSquared {density} => 1.0 - (-(density * distance).powi(2)).exp(),
+        };
+
+        factor.clamp(0.0, 1.0)
+    }
+}
+
+// FXAA tuning values from FXAA 3.11
+const FXAA_EDGE_THRESHOLD: f32 = 0.125; // Minimum contrast relative to the brightest neighbour to be treated as an edge
+const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0312; // Minimum absolute contrast, skips dark areas
+const FXAA_SUBPIXEL_QUALITY: f32 = 0.75; // Amount of sub pixel aliasing removal
+const FXAA_SEARCH_STEPS: [f32; 12] = [1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0]; // Distances stepped along an edge while searching for its ends
+
+// Largest circle of confusion radius in pixels used by depth_of_field, stops pixels near the camera from building huge kernels
+const DOF_MAX_CIRCLE_OF_CONFUSION: f32 = 32.0;
+
+// Depth difference below which an SSAO sample is not considered occluded, avoids surfaces occluding themselves
+const SSAO_BIAS: f32 = 0.025;
+
+impl<T: FrameBufferTrait> FrameBuffer<T> {
+    // Blurs the frame buffer with a separable gaussian kernel of radius ceil(3 * sigma)
+    // Pixels outside the buffer are treated as copies of the nearest edge pixel
+    // A sigma of zero returns an exact copy
+    pub fn gaussian_blur(&self, sigma: f32) -> FrameBuffer<Vec<u32>> {
+        if sigma <= 0.0 {
+            return self.crop(0, 0, self.width_px, self.height_px).expect("Every pixel in the buffer can be read");
+        }
+
+        let kernel = gaussian_kernel(sigma);
+        let colours = self.read_colours();
+        let horizontal = convolve(&colours, self.width_px, self.height_px, &kernel, (1, 0));
+        let blurred = convolve(&horizontal, self.width_px, self.height_px, &kernel, (0, 1));
+
+        colours_to_frame_buffer(&blurred, self.width_px, self.height_px, self.encoding)
+    }
+
+    // Simulates a camera lens by blurring pixels which are out of focus
+    // depth_buf holds the camera space depth of every pixel indexed by px_x + px_y * width_px
+    // The circle of confusion radius in pixels is |depth - focal_distance| * aperture / depth
+    // Each pixel is blurred with a gaussian whose radius matches its circle of confusion, pixels in focus are copied unchanged
+    // The radius is limited to DOF_MAX_CIRCLE_OF_CONFUSION, pixels with a depth which isn't positive are copied unchanged
+    // Panics if depth_buf doesn't have one depth per pixel
+    pub fn depth_of_field(&self, depth_buf: &[f32], focal_distance: f32, aperture: f32) -> FrameBuffer<Vec<u32>> {
+        assert_eq!(depth_buf.len(), self.width_px * self.height_px, "Depth buffer must have one depth per pixel");
+
+        let colours = self.read_colours();
+        let mut result = self.crop(0, 0, self.width_px, self.height_px).expect("Every pixel in the buffer can be read");
+
+        for y in 0..self.height_px {
+            for x in 0..self.width_px {
+                let depth = depth_buf[x + y * self.width_px];
+                if depth <= 0.0 || depth.is_nan() {
+                    continue;
+                }
+
+                let circle_of_confusion = ((depth - focal_distance).abs() * aperture / depth).min(DOF_MAX_CIRCLE_OF_CONFUSION);
+
+                // Circles smaller than half a pixel are in focus
+                if circle_of_confusion.is_nan() || circle_of_confusion < 0.5 {
+                    continue;
+                }
+
+                // The kernel radius is 3 sigma
+                let colour = blur_pixel(&colours, self.width_px, self.height_px, x, y, circle_of_confusion / 3.0);
+                let _ = result.write_buf(x, y, &colour);
+            }
+        }
+
+        result
+    }
+
+    // Screen space ambient occlusion, returns a greyscale buffer where white is unoccluded and black is fully occluded
+    // depth_buf and normal_buf hold the camera space depth and unit normal of every pixel indexed by px_x + px_y * width_px
+    // The camera must have the same image size as the frame buffer
+    // num_samples points are placed in a hemisphere of the given radius around each pixel and tested against the depth buffer
+    // The result is blurred to hide the pattern left by the fixed sample positions
+    // Panics if depth_buf or normal_buf don't have one value per pixel
+    pub fn ssao(&self, depth_buf: &[f32], normal_buf: &[Vec3<f32>], camera: &Camera, num_samples: usize, radius: f32) -> FrameBuffer<Vec<u32>> {
+        let pixel_count = self.width_px * self.height_px;
+        assert_eq!(depth_buf.len(), pixel_count, "Depth buffer must have one depth per pixel");
+        assert_eq!(normal_buf.len(), pixel_count, "Normal buffer must have one normal per pixel");
+
+        let kernel = hemisphere_kernel(num_samples);
+        let mut occlusion = Vec::with_capacity(pixel_count);
+
+        for y in 0..self.height_px {
+            for x in 0..self.width_px {
+                let index = x + y * self.width_px;
+                let depth = depth_buf[index];
+                let normal = normal_buf[index];
+
+                // Reconstruct the camera space position by following the pixels ray to its depth
+                let direction = camera.generate_ray(&Vec2::new(x as i32, y as i32)).direction;
+                let position = scale(&direction, depth / direction.z);
+
+                let (tangent, bitangent) = Vec3::tangent_frame(&normal);
+                let mut occluded = 0;
+                for sample in kernel.iter() {
+                    // Orient the sample around the normal
+                    let offset = Vec3::new(
+                        tangent.x * sample.x + bitangent.x * sample.y + normal.x * sample.z,
+                        tangent.y * sample.x + bitangent.y * sample.y + normal.y * sample.z,
+                        tangent.z * sample.x + bitangent.z * sample.y + normal.z * sample.z,
+                    );
+                    let sample_position = Vec3::new(
+                        position.x + offset.x * radius,
+                        position.y + offset.y * radius,
+                        position.z + offset.z * radius,
+                    );
+
+                    // Same projection as Camera::point_to_screen without the world to camera transformation
+                    let screen_point = Vec3::new(
+                        sample_position.x / -sample_position.z * camera.z_near,
+                        sample_position.y / sample_position.z * camera.z_near,
+                        sample_position.z,
+                    );
+                    let Ok(raster) = camera.screen_to_raster(&screen_point) else {
+                        continue;
+                    };
+                    let sample_x = (raster.x as usize).min(self.width_px - 1);
+                    let sample_y = (raster.y as usize).min(self.height_px - 1);
+
+                    // Occluded if the visible surface is in front of the sample, surfaces much further in front are ignored
+                    let scene_depth = depth_buf[sample_x + sample_y * self.width_px];
+                    if scene_depth < sample_position.z - SSAO_BIAS && (depth - scene_depth).abs() < radius {
+                        occluded += 1;
+                    }
+                }
+
+                let factor = 1.0 - occluded as f32 / num_samples.max(1) as f32;
+                occlusion.push(Colour {red: factor, green: factor, blue: factor, alpha: 1.0});
+            }
+        }
+
+        colours_to_frame_buffer(&occlusion, self.width_px, self.height_px, ColourEncoding::Linear).gaussian_blur(1.0)
+    }
+
+    // Fast approximate anti-aliasing based on the simplified FXAA 3.11 quality algorithm
+    // Edges are found from the luminance contrast between each pixel and its neighbours
+    // Pixels on an edge are blended with their neighbour across the edge by an amount depending on their position along the edge
+    // Pixels which aren't on an edge are copied unchanged
+    pub fn fxaa(&self) -> FrameBuffer<Vec<u32>> {
+        let colours = self.read_colours();
+        let (width_px, height_px) = (self.width_px, self.height_px);
+        let luma = |x: i32, y: i32| {
+            let x = x.clamp(0, width_px as i32 - 1) as usize;
+            let y = y.clamp(0, height_px as i32 - 1) as usize;
+            colours[x + y * width_px].luminance()
+        };
+        let sample_luma = |x: f32, y: f32| sample_bilinear(&colours, width_px, height_px, x, y).luminance();
+
+        let mut result = self.crop(0, 0, width_px, height_px).expect("Every pixel in the buffer can be read");
+
+        for y in 0..height_px as i32 {
+            for x in 0..width_px as i32 {
+                let luma_m = luma(x, y);
+                let (luma_n, luma_s, luma_e, luma_w) = (luma(x, y + 1), luma(x, y - 1), luma(x + 1, y), luma(x - 1, y));
+
+                let luma_min = luma_m.min(luma_n).min(luma_s).min(luma_e).min(luma_w);
+                let luma_max = luma_m.max(luma_n).max(luma_s).max(luma_e).max(luma_w);
+                let range = luma_max - luma_min;
+                if range < FXAA_EDGE_THRESHOLD_MIN.max(luma_max * FXAA_EDGE_THRESHOLD) {
+                    continue;
+                }
+
+                let (luma_ne, luma_nw, luma_se, luma_sw) = (luma(x + 1, y + 1), luma(x - 1, y + 1), luma(x + 1, y - 1), luma(x - 1, y - 1));
+
+                // Estimate whether the edge runs horizontally or vertically
+                let edge_horizontal = (-2.0 * luma_w + luma_nw + luma_sw).abs() +
+                                      (-2.0 * luma_m + luma_n + luma_s).abs() * 2.0 +
+                                      (-2.0 * luma_e + luma_ne + luma_se).abs();
+                let edge_vertical = (-2.0 * luma_n + luma_nw + luma_ne).abs() +
+                                    (-2.0 * luma_m + luma_w + luma_e).abs() * 2.0 +
+                                    (-2.0 * luma_s + luma_sw + luma_se).abs();
+                let is_horizontal = edge_horizontal >= edge_vertical;
+
+                // Pick the side of the pixel with the largest change in luminance
+                let (luma1, luma2) = if is_horizontal {(luma_s, luma_n)} else {(luma_w, luma_e)};
+                let gradient1 = luma1 - luma_m;
+                let gradient2 = luma2 - luma_m;
+                let is1_steepest = gradient1.abs() >= gradient2.abs();
+                let gradient_scaled = 0.25 * gradient1.abs().max(gradient2.abs());
+
+                let (step_length, luma_local_average) = if is1_steepest {
+                    (-1.0, 0.5 * (luma1 + luma_m))
+                } else {
+                    (1.0, 0.5 * (luma2 + luma_m))
+                };
+
+                // Move half a pixel onto the edge then search along it in both directions for its ends
+                let (centre_x, centre_y) = (x as f32 + 0.5, y as f32 + 0.5);
+                let (edge_x, edge_y) = if is_horizontal {(centre_x, centre_y + step_length * 0.5)} else {(centre_x + step_length * 0.5, centre_y)};
+                let (offset_x, offset_y) = if is_horizontal {(1.0, 0.0)} else {(0.0, 1.0)};
+
+                let (mut uv1, mut uv2) = ((edge_x - offset_x, edge_y - offset_y), (edge_x + offset_x, edge_y + offset_y));
+                let mut luma_end1 = sample_luma(uv1.0, uv1.1) - luma_local_average;
+                let mut luma_end2 = sample_luma(uv2.0, uv2.1) - luma_local_average;
+                let mut reached1 = luma_end1.abs() >= gradient_scaled;
+                let mut reached2 = luma_end2.abs() >= gradient_scaled;
+
+                for step in FXAA_SEARCH_STEPS {
+                    if reached1 && reached2 {
+                        break;
+                    }
+
+                    if !reached1 {
+                        uv1 = (uv1.0 - offset_x * step, uv1.1 - offset_y * step);
+                        luma_end1 = sample_luma(uv1.0, uv1.1) - luma_local_average;
+                        reached1 = luma_end1.abs() >= gradient_scaled;
+                    }
+                    if !reached2 {
+                        uv2 = (uv2.0 + offset_x * step, uv2.1 + offset_y * step);
+                        luma_end2 = sample_luma(uv2.0, uv2.1) - luma_local_average;
+                        reached2 = luma_end2.abs() >= gradient_scaled;
+                    }
+                }
+
+                // Pixels nearer the end of an edge are blended more
+                let (distance1, distance2) = if is_horizontal {
+                    (centre_x - uv1.0, uv2.0 - centre_x)
+                } else {
+                    (centre_y - uv1.1, uv2.1 - centre_y)
+                };
+                let is_direction1 = distance1 < distance2;
+                let pixel_offset = 0.5 - distance1.min(distance2) / (distance1 + distance2);
+
+                // Only blend if the luminance at the nearest end of the edge changes in the right direction
+                let luma_end = if is_direction1 {luma_end1} else {luma_end2};
+                let correct_variation = (luma_end < 0.0) != (luma_m < luma_local_average);
+                let edge_offset = if correct_variation {pixel_offset} else {0.0};
+
+                // Sub pixel anti-aliasing for aliasing smaller than a pixel, e.g. thin lines
+                let luma_average = (2.0 * (luma_n + luma_s + luma_e + luma_w) + luma_ne + luma_nw + luma_se + luma_sw) / 12.0;
+                let subpixel1 = ((luma_average - luma_m).abs() / range).clamp(0.0, 1.0);
+                let subpixel2 = (-2.0 * subpixel1 + 3.0) * subpixel1 * subpixel1;
+                let subpixel_offset = subpixel2 * subpixel2 * FXAA_SUBPIXEL_QUALITY;
+
+                let final_offset = edge_offset.max(subpixel_offset) * step_length;
+                let colour = if is_horizontal {
+                    sample_bilinear(&colours, width_px, height_px, centre_x, centre_y + final_offset)
+                } else {
+                    sample_bilinear(&colours, width_px, height_px, centre_x + final_offset, centre_y)
+                };
+
+                let _ = result.write_buf(x as usize, y as usize, &colour);
+            }
+        }
+
+        result
+    }
+
+    // Temporal anti-aliasing, blends this frame with the accumulated history of previous frames
+    // motion_vectors holds the screen space movement in pixels of every pixel since the last frame, indexed by px_x + px_y * width_px
+    // alpha is the weight of the current frame, smaller values accumulate more frames
+    // The history is clamped to the colour variance of each pixels neighbourhood in the current frame to reduce ghosting
+    // Pass the result as the history for the next frame
+    // Panics if history is a different size or motion_vectors doesn't have one vector per pixel
+    pub fn temporal_accumulate(&self, history: &FrameBuffer<Vec<u32>>, motion_vectors: &[Vec2<f32>], alpha: f32) -> FrameBuffer<Vec<u32>> {
+        let (width_px, height_px) = (self.width_px, self.height_px);
+        assert!(history.width_px == width_px && history.height_px == height_px, "History must be the same size as the frame buffer");
+        assert_eq!(motion_vectors.len(), width_px * height_px, "Motion vectors must have one vector per pixel");
+
+        let colours = self.read_colours();
+        let history_colours = history.read_colours();
+        let mut accumulated = Vec::with_capacity(colours.len());
+
+        for y in 0..height_px {
+            for x in 0..width_px {
+                let current = colours[x + y * width_px];
+
+                // Follow the motion vector back to where this pixel was in the previous frame
+                let motion = motion_vectors[x + y * width_px];
+                let (previous_x, previous_y) = (x as f32 + 0.5 - motion.x, y as f32 + 0.5 - motion.y);
+                if previous_x < 0.0 || previous_y < 0.0 || previous_x >= width_px as f32 || previous_y >= height_px as f32 {
+                    // Newly visible pixels have no history
+                    accumulated.push(current);
+                    continue;
+                }
+                let previous = sample_bilinear(&history_colours, width_px, height_px, previous_x, previous_y);
+
+                // The clamp range is widened to include the current pixel so the history can always reach it
+                let (mean, deviation) = neighbourhood_statistics(&colours, width_px, height_px, x, y);
+                let clamp_channel = |channel: f32, mean: f32, deviation: f32, current: f32| {
+                    channel.clamp((mean - deviation).min(current), (mean + deviation).max(current))
+                };
+                let previous = Colour {
+                    red: clamp_channel(previous.red, mean.red, deviation.red, current.red),
+                    green: clamp_channel(previous.green, mean.green, deviation.green, current.green),
+                    blue: clamp_channel(previous.blue, mean.blue, deviation.blue, current.blue),
+                    alpha: clamp_channel(previous.alpha, mean.alpha, deviation.alpha, current.alpha),
+                };
+
+                let blended = previous.multiply_float(1.0 - alpha) + current.multiply_float(alpha);
+
+                // Channels are rounded to the nearest byte when they are stored, so a blend which moves less than half a step is lost
+                // Stepping at least one byte towards the current frame means a static image always converges
+                let towards = |channel: f32, target: f32| if channel < target {
+                    (channel + 0.999 / 255.0).min(target)
+                } else {
+                    (channel - 0.999 / 255.0).max(target)
+                };
+                accumulated.push(Colour {
+                    red: towards(blended.red, current.red),
+                    green: towards(blended.green, current.green),
+                    blue: towards(blended.blue, current.blue),
+                    alpha: towards(blended.alpha, current.alpha),
+                });
+            }
+        }
+
+        colours_to_frame_buffer(&accumulated, width_px, height_px, self.encoding)
+    }
+
+    // Adds film grain, uniform noise in [-strength, strength] scaled by each pixels luminance so highlights are grainier
+    // The same seed always produces the same grain, a strength of zero returns an exact copy
+    pub fn film_grain(&self, strength: f32, seed: u64) -> FrameBuffer<Vec<u32>> {
+        if strength == 0.0 {
+            return self.crop(0, 0, self.width_px, self.height_px).expect("Every pixel in the buffer can be read");
+        }
+
+        let mut state = seed;
+        let grainy: Vec<Colour> = self.read_colours().iter()
+            .map(|colour| {
+                // Map the top 24 bits to [-1, 1]
+                let noise = (split_mix64(&mut state) >> 40) as f32 / (1u64 << 23) as f32 - 1.0;
+                let grain = noise * strength * colour.luminance();
+
+                Colour {
+                    red: colour.red + grain,
+                    green: colour.green + grain,
+                    blue: colour.blue + grain,
+                    alpha: colour.alpha,
+                }
+            })
+            .collect();
+
+        colours_to_frame_buffer(&grainy, self.width_px, self.height_px, self.encoding)
+    }
+
+    // Blends every pixel towards fog_colour by the fog factor at its depth
+    // depth_buf holds the camera space depth of every pixel indexed by px_x + px_y * width_px
+    // Pixels closer than fog_start are unchanged
+    // Panics if depth_buf doesn't have one depth per pixel
+    pub fn apply_fog(&mut self, depth_buf: &[f32], fog_colour: Colour, fog_start: f32, fog_end: f32, mode: FogMode) {
+        assert_eq!(depth_buf.len(), self.width_px * self.height_px, "Depth buffer must have one depth per pixel");
+
+        for y in 0..self.height_px {
+            for x in 0..self.width_px {
+                let factor = mode.factor(depth_buf[x + y * self.width_px], fog_start, fog_end);
+                if factor <= 0.0 {
+                    continue;
+                }
+
+                if let Ok(colour) = self.read_buf(x, y) {
+                    let fogged = colour.multiply_float(1.0 - factor) + fog_colour.multiply_float(factor);
+                    let _ = self.write_buf(x, y, &fogged);
+                }
+            }
+        }
+    }
+
+    // Returns every pixel colour indexed by px_x + px_y * width_px
+    fn read_colours(&self) -> Vec<Colour> {
+        let mut colours = Vec::with_capacity(self.width_px * self.height_px);
+        for y in 0..self.height_px {
+            for x in 0..self.width_px {
+                colours.push(self.read_buf(x, y).unwrap_or_default());
+            }
+        }
+        colours
+    }
+}
+
+// Returns a normalised 1D gaussian kernel of radius ceil(3 * sigma), the centre of the kernel is at index radius
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil() as i32;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    // Normalise so the weights sum to one and the overall brightness is conserved
+    let sum: f32 = kernel.iter().sum();
+    for weight in kernel.iter_mut() {
+        *weight /= sum;
+    }
+
+    kernel
+}
+
+// Convolves colours with a 1D kernel along direction, which is (1, 0) for horizontal or (0, 1) for vertical
+fn convolve(colours: &[Colour], width_px: usize, height_px: usize, kernel: &[f32], direction: (i32, i32)) -> Vec<Colour> {
+    let radius = (kernel.len() / 2) as i32;
+    let mut result = Vec::with_capacity(colours.len());
+
+    for y in 0..height_px as i32 {
+        for x in 0..width_px as i32 {
+            let mut sum = Colour::new();
+            for (i, weight) in kernel.iter().enumerate() {
+                let offset = i as i32 - radius;
+                let sample_x = (x + offset * direction.0).clamp(0, width_px as i32 - 1) as usize;
+                let sample_y = (y + offset * direction.1).clamp(0, height_px as i32 - 1) as usize;
+                sum = sum + colours[sample_x + sample_y * width_px].multiply_float(*weight);
+            }
+            result.push(sum);
+        }
+    }
+
+    result
+}
+
+// Returns the colour of a single pixel blurred with a 2D gaussian kernel
+fn blur_pixel(colours: &[Colour], width_px: usize, height_px: usize, px_x: usize, px_y: usize, sigma: f32) -> Colour {
+    let kernel = gaussian_kernel(sigma);
+    let radius = (kernel.len() / 2) as i32;
+
+    // The 2D gaussian is the product of two 1D gaussians so the weights still sum to one
+    let mut sum = Colour::new();
+    for (j, weight_y) in kernel.iter().enumerate() {
+        let sample_y = (px_y as i32 + j as i32 - radius).clamp(0, height_px as i32 - 1) as usize;
+        for (i, weight_x) in kernel.iter().enumerate() {
+            let sample_x = (px_x as i32 + i as i32 - radius).clamp(0, width_px as i32 - 1) as usize;
+            sum = sum + colours[sample_x + sample_y * width_px].multiply_float(weight_x * weight_y);
+        }
+    }
+
+    sum
+}
+
+// Bilinearly interpolates colours at a continuous position where pixel centres are at half integer coordinates
+// Positions outside the buffer are clamped to the edge
+fn sample_bilinear(colours: &[Colour], width_px: usize, height_px: usize, x: f32, y: f32) -> Colour {
+    let (fx, fy) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (fx.floor(), fy.floor());
+    let (tx, ty) = (fx - x0, fy - y0);
+
+    let pixel = |x: f32, y: f32| {
+        let x = (x as i32).clamp(0, width_px as i32 - 1) as usize;
+        let y = (y as i32).clamp(0, height_px as i32 - 1) as usize;
+        colours[x + y * width_px]
+    };
+
+    let bottom = pixel(x0, y0).multiply_float(1.0 - tx) + pixel(x0 + 1.0, y0).multiply_float(tx);
+    let top = pixel(x0, y0 + 1.0).multiply_float(1.0 - tx) + pixel(x0 + 1.0, y0 + 1.0).multiply_float(tx);
+    bottom.multiply_float(1.0 - ty) + top.multiply_float(ty)
+}
+
+// Returns the per channel mean and standard deviation of the 3x3 neighbourhood around a pixel
+fn neighbourhood_statistics(colours: &[Colour], width_px: usize, height_px: usize, px_x: usize, px_y: usize) -> (Colour, Colour) {
+    let mut sum = Colour::new();
+    let mut sum_squares = Colour::new();
+    let mut count = 0.0;
+
+    for y in px_y.saturating_sub(1)..(px_y + 2).min(height_px) {
+        for x in px_x.saturating_sub(1)..(px_x + 2).min(width_px) {
+            let colour = colours[x + y * width_px];
+            sum = sum + colour;
+            sum_squares = sum_squares + Colour {
+                red: colour.red * colour.red,
+                green: colour.green * colour.green,
+                blue: colour.blue * colour.blue,
+                alpha: colour.alpha * colour.alpha,
+            };
+            count += 1.0;
+        }
+    }
+
+    let mean = sum.multiply_float(1.0 / count);
+    let mean_squares = sum_squares.multiply_float(1.0 / count);
+    let deviation = |mean: f32, mean_square: f32| (mean_square - mean * mean).max(0.0).sqrt();
+    let deviation = Colour {
+        red: deviation(mean.red, mean_squares.red),
+        green: deviation(mean.green, mean_squares.green),
+        blue: deviation(mean.blue, mean_squares.blue),
+        alpha: deviation(mean.alpha, mean_squares.alpha),
+    };
+
+    (mean, deviation)
+}
+
+// SplitMix64 pseudo random number generator, returns the next number and advances the state
+fn split_mix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn scale(v: &Vec3<f32>, s: f32) -> Vec3<f32> {
+    Vec3::new(v.x * s, v.y * s, v.z * s)
+}
+
+// Returns points inside a unit hemisphere around the z axis
+// Points are spread with a golden angle spiral so the result is deterministic
+// Later points are further from the centre so nearby geometry contributes more occlusion
+fn hemisphere_kernel(num_samples: usize) -> Vec<Vec3<f32>> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+
+    (0..num_samples)
+        .map(|i| {
+            let z = 1.0 - (i as f32 + 0.5) / num_samples as f32;
+            let r = (1.0 - z * z).sqrt();
+            let phi = i as f32 * golden_angle;
+
+            let t = (i + 1) as f32 / num_samples as f32;
+            let length = 0.1 + 0.9 * t * t;
+            scale(&Vec3::new(r * phi.cos(), r * phi.sin(), z), length)
+        })
+        .collect()
+}
+
+// Writes colours indexed by px_x + px_y * width_px into a new frame buffer
+fn colours_to_frame_buffer(colours: &[Colour], width_px: usize, height_px: usize, encoding: ColourEncoding) -> FrameBuffer<Vec<u32>> {
+    let mut frame_buffer = FrameBuffer::new(width_px, height_px, vec![0; width_px * height_px]);
+    frame_buffer.encoding = encoding;
+
+    for y in 0..height_px {
+        for x in 0..width_px {
+            let _ = frame_buffer.write_buf(x, y, &colours[x + y * width_px]);
+        }
+    }
+
+    frame_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::FitResolutionGate;
+    use crate::colour::{Colour8, BLACK, WHITE};
+    use crate::linear_algebra::Matrix44;
+
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 16;
+
+    fn frame_buffer() -> FrameBuffer<Vec<u32>> {
+        FrameBuffer::new(WIDTH, HEIGHT, vec![0; WIDTH * HEIGHT])
+    }
+
+    fn pixel(frame_buffer: &FrameBuffer<Vec<u32>>, x: usize, y: usize) -> Colour8 {
+        frame_buffer.read_buf(x, y).unwrap().to_colour8()
+    }
+
+    #[test]
+    fn test_gaussian_blur_single_pixel() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.write_buf(8, 8, &WHITE).unwrap();
+
+        let blurred = frame_buffer.gaussian_blur(1.5);
+
+        // Brightness falls off symmetrically with distance from the centre
+        let centre = pixel(&blurred, 8, 8).red;
+        let near = pixel(&blurred, 9, 8).red;
+        let far = pixel(&blurred, 11, 8).red;
+        assert!(centre > near && near > far && far > 0);
+        assert_eq!(pixel(&blurred, 7, 8), pixel(&blurred, 9, 8));
+        assert_eq!(pixel(&blurred, 8, 7), pixel(&blurred, 8, 9));
+        assert_eq!(pixel(&blurred, 9, 8), pixel(&blurred, 8, 9));
+
+        // Nothing reaches past the kernel radius
+        assert_eq!(pixel(&blurred, 0, 0).red, 0);
+    }
+
+    #[test]
+    fn test_gaussian_blur_zero_sigma() {
+        let mut frame_buffer = frame_buffer();
+        for (i, word) in frame_buffer.buf.iter_mut().enumerate() {
+            *word = (i as u32).wrapping_mul(0x9e3779b9);
+        }
+
+        let blurred = frame_buffer.gaussian_blur(0.0);
+        assert_eq!(blurred.buf, frame_buffer.buf);
+    }
+
+    #[test]
+    fn test_gaussian_kernel_normalised() {
+        let kernel = gaussian_kernel(2.0);
+
+        assert_eq!(kernel.len(), 13);
+        assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+
+    // Checkerboard so blurring is visible everywhere
+    fn checkerboard() -> FrameBuffer<Vec<u32>> {
+        let mut frame_buffer = frame_buffer();
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                if (x + y) % 2 == 0 {
+                    frame_buffer.write_buf(x, y, &WHITE).unwrap();
+                }
+            }
+        }
+        frame_buffer
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        let frame_buffer = checkerboard();
+
+        // Left half is in focus and the right half is far behind the focal plane
+        let focal_distance = 5.0;
+        let mut depth_buf = vec![focal_distance; WIDTH * HEIGHT];
+        for y in 0..HEIGHT {
+            for x in WIDTH / 2..WIDTH {
+                depth_buf[x + y * WIDTH] = 50.0;
+            }
+        }
+
+        let result = frame_buffer.depth_of_field(&depth_buf, focal_distance, 4.0);
+
+        // In focus pixels are unchanged
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH / 2 {
+                assert_eq!(pixel(&result, x, y), pixel(&frame_buffer, x, y));
+            }
+        }
+
+        // Out of focus pixels are blurred towards grey so neighbours become similar
+        let (white, black) = (pixel(&result, 12, 8).red, pixel(&result, 13, 8).red);
+        assert!(white < 255 && black > 0);
+        assert!(white.abs_diff(black) < 64);
+    }
+
+    #[test]
+    fn test_depth_of_field_near_camera() {
+        let frame_buffer = checkerboard();
+
+        // A depth of zero is skipped and depths just in front of the camera use the largest kernel rather than a huge one
+        let mut depth_buf = vec![1e-30; WIDTH * HEIGHT];
+        depth_buf[0] = 0.0;
+        depth_buf[1] = -1.0;
+        let result = frame_buffer.depth_of_field(&depth_buf, 5.0, 4.0);
+
+        assert_eq!(pixel(&result, 0, 0), pixel(&frame_buffer, 0, 0));
+        assert_eq!(pixel(&result, 1, 0), pixel(&frame_buffer, 1, 0));
+        assert_ne!(pixel(&result, 8, 8), pixel(&frame_buffer, 8, 8));
+    }
+
+    #[test]
+    fn test_ssao_two_planes() {
+        let camera = Camera::new(Matrix44::identity(), Vec2::new(WIDTH as i32, HEIGHT as i32), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill);
+
+        // A back plane facing the camera with a raised step covering the left half of the image
+        let mut depth_buf = vec![10.0; WIDTH * HEIGHT];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH / 2 {
+                depth_buf[x + y * WIDTH] = 9.5;
+            }
+        }
+        let normal_buf = vec![Vec3::new(0.0, 0.0, -1.0); WIDTH * HEIGHT];
+
+        let occlusion = frame_buffer().ssao(&depth_buf, &normal_buf, &camera, 32, 0.6);
+
+        // The back plane is occluded next to the step but not far away from it, the top of the step is never occluded
+        let corner = pixel(&occlusion, 9, 8).red;
+        let open = pixel(&occlusion, 15, 8).red;
+        let step = pixel(&occlusion, 2, 8).red;
+        assert!(corner < open, "{} >= {}", corner, open);
+        assert_eq!(step, open);
+    }
+
+    #[test]
+    fn test_fxaa_diagonal_edge() {
+        // White above a 45 degree diagonal, black below it
+        let mut frame_buffer = frame_buffer();
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                let colour = if y > x {WHITE} else {BLACK};
+                frame_buffer.write_buf(x, y, &colour).unwrap();
+            }
+        }
+
+        let result = frame_buffer.fxaa();
+
+        // The edge is smoothed so some pixels along it are between black and white
+        let intermediate = (1..WIDTH - 1)
+            .flat_map(|x| [(x, x), (x, x + 1)])
+            .filter(|(_, y)| *y < HEIGHT)
+            .map(|(x, y)| pixel(&result, x, y).red)
+            .filter(|red| *red > 0 && *red < 255)
+            .count();
+        assert!(intermediate > 0);
+
+        // Pixels away from the edge are untouched
+        assert_eq!(pixel(&result, 12, 2), pixel(&frame_buffer, 12, 2));
+        assert_eq!(pixel(&result, 2, 12), pixel(&frame_buffer, 2, 12));
+    }
+
+    #[test]
+    fn test_fxaa_flat_region() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&Colour8::new(30, 120, 200, 255));
+
+        assert_eq!(frame_buffer.fxaa().buf, frame_buffer.buf);
+    }
+
+    #[test]
+    fn test_temporal_accumulate_converges() {
+        let mut frame = frame_buffer();
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                let colour = Colour8::new((x * 16) as u8, (y * 16) as u8, ((x * y) % 256) as u8, 255);
+                frame.write_buf(x, y, &colour.to_colour()).unwrap();
+            }
+        }
+
+        let motion_vectors = vec![Vec2::new(0.0, 0.0); WIDTH * HEIGHT];
+        let mut history = frame_buffer();
+        for _ in 0..100 {
+            history = frame.temporal_accumulate(&history, &motion_vectors, 0.1);
+        }
+
+        assert_eq!(history.buf, frame.buf);
+    }
+
+    fn grey_frame_buffer() -> FrameBuffer<Vec<u32>> {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&Colour8::new(128, 128, 128, 255));
+        frame_buffer
+    }
+
+    #[test]
+    fn test_film_grain_zero_strength() {
+        let frame_buffer = grey_frame_buffer();
+
+        assert_eq!(frame_buffer.film_grain(0.0, 0).buf, frame_buffer.buf);
+    }
+
+    #[test]
+    fn test_film_grain_seeds() {
+        let frame_buffer = grey_frame_buffer();
+
+        assert_eq!(frame_buffer.film_grain(1.0, 7).buf, frame_buffer.film_grain(1.0, 7).buf);
+        assert_ne!(frame_buffer.film_grain(1.0, 7).buf, frame_buffer.film_grain(1.0, 8).buf);
+        assert_ne!(frame_buffer.film_grain(1.0, 7).buf, frame_buffer.buf);
+    }
+
+    #[test]
+    fn test_film_grain_black_unchanged() {
+        // Black has no luminance so it gets no grain
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&BLACK.to_colour8());
+
+        assert_eq!(frame_buffer.film_grain(1.0, 3).buf, frame_buffer.buf);
+    }
+
+    #[test]
+    fn test_apply_fog_linear() {
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&Colour8::new(200, 40, 90, 255));
+        let original = pixel(&frame_buffer, 0, 0);
+
+        let (fog_start, fog_end) = (10.0, 20.0);
+        let mut depth_buf = vec![fog_start; WIDTH * HEIGHT];
+        depth_buf[1] = fog_end;
+        depth_buf[2] = 15.0;
+        depth_buf[3] = 100.0;
+
+        frame_buffer.apply_fog(&depth_buf, WHITE, fog_start, fog_end, FogMode::Linear);
+
+        assert_eq!(pixel(&frame_buffer, 0, 0), original);
+        assert_eq!(pixel(&frame_buffer, 1, 0), WHITE.to_colour8());
+        assert_eq!(pixel(&frame_buffer, 3, 0), WHITE.to_colour8());
+
+        let halfway = pixel(&frame_buffer, 2, 0);
+        assert!(halfway.red > original.red && halfway.green > original.green && halfway.blue > original.blue);
+        assert!(halfway.green < 255);
+    }
+
+    #[test]
+    fn test_fog_factor_exponential() {
+        let exponential = FogMode::Exponential {density: 0.5};
+        let exponential_squared = FogMode::ExponentialSquared {density: 0.5};
+
+        assert_eq!(exponential.factor(5.0, 5.0, 10.0), 0.0);
+        assert!((exponential.factor(7.0, 5.0, 10.0) - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+        assert!((exponential_squared.factor(7.0, 5.0, 10.0) - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+
+        // Squared fog is thinner close to fog_start
+        assert!(exponential_squared.factor(6.0, 5.0, 10.0) < exponential.factor(6.0, 5.0, 10.0));
+    }
+
+    #[test]
+    fn test_fog_factor_linear_empty_range() {
+        let linear = FogMode::Linear;
+
+        assert_eq!(linear.factor(4.0, 5.0, 5.0), 0.0);
+        assert_eq!(linear.factor(5.0, 5.0, 5.0), 0.0);
+        assert_eq!(linear.factor(5.5, 5.0, 5.0), 1.0);
+        assert_eq!(linear.factor(5.0, 5.0, 3.0), 0.0);
+        assert_eq!(linear.factor(6.0, 5.0, 3.0), 1.0);
+    }
+}
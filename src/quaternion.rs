@@ -0,0 +1,324 @@
+// This file contains the definition and implementation of the quaternion type used for rotations
+
+use crate::linear_algebra::{Matrix44, Vec3};
+
+// Quaternion x*i + y*j + z*k + w
+// Rotations are represented by unit quaternions
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quaternion {x, y, z, w}
+    }
+
+    // Return the quaternion representing no rotation
+    pub fn identity() -> Self {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    // Returns the rotation of angle radians about an axis, counterclockwise when looking down the axis towards the origin
+    // The axis doesn't need to be unit length but must not be zero
+    pub fn from_axis_angle(axis: &Vec3<f32>, angle: f32) -> Self {
+        let mut axis = *axis;
+        axis.normalise();
+
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quaternion::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    // Negates the vector part, for unit quaternions this is the opposite rotation
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    // Returns the quaternion which multiplies with this one to give the identity
+    // Equal to the conjugate for unit quaternions, returns None for the zero quaternion
+    pub fn inverse(&self) -> Option<Quaternion> {
+        let len_sq = self.dot(self);
+        if len_sq == 0.0 {
+            return None;
+        }
+
+        let conjugate = self.conjugate();
+        Some(Quaternion::new(conjugate.x / len_sq, conjugate.y / len_sq, conjugate.z / len_sq, conjugate.w / len_sq))
+    }
+
+    // Does 4 dimensional dot product with another quaternion
+    pub fn dot(&self, q: &Quaternion) -> f32 {
+        self.x * q.x + self.y * q.y + self.z * q.z + self.w * q.w
+    }
+
+    // Returns quaternion 2 norm
+    pub fn len(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // Makes quaternion length 1
+    pub fn normalise(&mut self) {
+        let normalisation_constant = 1.0 / self.len();
+
+        self.x *= normalisation_constant;
+        self.y *= normalisation_constant;
+        self.z *= normalisation_constant;
+        self.w *= normalisation_constant;
+    }
+
+    // Spherical linear interpolation between two unit quaternions, t is not clamped
+    // Always interpolates along the shortest path between the two rotations
+    pub fn slerp(a: &Quaternion, b: &Quaternion, t: f32) -> Quaternion {
+        let mut b = *b;
+        let mut cos_theta = a.dot(&b);
+
+        // q and -q represent the same rotation, flip b so the shortest arc is taken
+        if cos_theta < 0.0 {
+            b = Quaternion::new(-b.x, -b.y, -b.z, -b.w);
+            cos_theta = -cos_theta;
+        }
+
+        // When the quaternions are very close sin(theta) approaches zero so linearly interpolate instead
+        let (weight_a, weight_b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+
+        let mut q = Quaternion::new(
+            a.x * weight_a + b.x * weight_b,
+            a.y * weight_a + b.y * weight_b,
+            a.z * weight_a + b.z * weight_b,
+            a.w * weight_a + b.w * weight_b,
+        );
+        q.normalise();
+        q
+    }
+
+    // Rotates a vector by a unit quaternion
+    pub fn rotate_vec3(&self, v: &Vec3<f32>) -> Vec3<f32> {
+        // Expanded form of q * v * q^-1
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+
+        *v + t * self.w + u.cross(&t)
+    }
+
+    // Returns the rotation matrix of a unit quaternion
+    // The matrix follows the row vector convention used by Matrix44, so v * m rotates v
+    pub fn to_matrix44(&self) -> Matrix44 {
+        let (x, y, z, w) = (self.x, self.y, self.z, self.w);
+
+        Matrix44::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w), 0.0],
+            [2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w), 0.0],
+            [2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Extracts the rotation from the upper left 3x3 of a matrix using Shepperd's method
+    // The upper left 3x3 must be a pure rotation (orthogonal with no scale)
+    pub fn from_matrix44(m: &Matrix44) -> Quaternion {
+        // Element of the equivalent column vector convention rotation matrix
+        let r = |i: usize, j: usize| m.0[j][i];
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+
+        // Pick the largest of w, x, y, z to divide by so the result stays numerically stable
+        let mut q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                (r(2, 1) - r(1, 2)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+                (r(1, 0) - r(0, 1)) / s,
+                0.25 * s,
+            )
+        } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                0.25 * s,
+                (r(0, 1) + r(1, 0)) / s,
+                (r(0, 2) + r(2, 0)) / s,
+                (r(2, 1) - r(1, 2)) / s,
+            )
+        } else if r(1, 1) > r(2, 2) {
+            let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (r(0, 1) + r(1, 0)) / s,
+                0.25 * s,
+                (r(1, 2) + r(2, 1)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+            )
+        } else {
+            let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+            Quaternion::new(
+                (r(0, 2) + r(2, 0)) / s,
+                (r(1, 2) + r(2, 1)) / s,
+                0.25 * s,
+                (r(1, 0) - r(0, 1)) / s,
+            )
+        };
+
+        q.normalise();
+        q
+    }
+}
+
+// Hamilton product, a * b rotates by b first and then by a
+// The matching matrices compose in the opposite order since they multiply row vectors, (a * b).to_matrix44() = b_matrix * a_matrix
+impl std::ops::Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Quaternion::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod quaternion_tests {
+    use super::*;
+
+    #[test]
+    fn test_normalise() {
+        let mut q = Quaternion::new(1.0, 1.0, 1.0, 1.0);
+        q.normalise();
+
+        assert_eq!(q, Quaternion::new(0.5, 0.5, 0.5, 0.5));
+    }
+
+    fn assert_quaternion_close(a: &Quaternion, b: &Quaternion) {
+        assert!((a.x - b.x).abs() < 1e-6 && (a.y - b.y).abs() < 1e-6 && (a.z - b.z).abs() < 1e-6 && (a.w - b.w).abs() < 1e-6, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_from_axis_angle() {
+        let q = Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 2.0), std::f32::consts::FRAC_PI_2);
+
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        assert_quaternion_close(&q, &Quaternion::new(0.0, 0.0, half_sqrt2, half_sqrt2));
+
+        let rotated = q.rotate_vec3(&Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-6);
+
+        assert_eq!(Quaternion::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), 0.0), Quaternion::identity());
+    }
+
+    #[test]
+    fn test_conjugate_inverse() {
+        let q = Quaternion::from_axis_angle(&Vec3::new(1.0, -2.0, 0.5), 1.3);
+
+        assert_quaternion_close(&(q * q.conjugate()), &Quaternion::identity());
+        assert_quaternion_close(&(q.conjugate() * q), &Quaternion::identity());
+        assert_quaternion_close(&q.inverse().unwrap(), &q.conjugate());
+
+        // Non unit quaternions still invert
+        let scaled = Quaternion::new(0.0, 0.0, 2.0, 2.0);
+        assert_quaternion_close(&(scaled * scaled.inverse().unwrap()), &Quaternion::identity());
+
+        assert!(Quaternion::new(0.0, 0.0, 0.0, 0.0).inverse().is_none());
+    }
+
+    #[test]
+    fn test_mul() {
+        let z_axis = Vec3::new(0.0, 0.0, 1.0);
+        let quarter = Quaternion::from_axis_angle(&z_axis, std::f32::consts::FRAC_PI_4);
+        let half = Quaternion::from_axis_angle(&z_axis, std::f32::consts::FRAC_PI_2);
+        assert_quaternion_close(&(quarter * quarter), &half);
+        assert_eq!(Quaternion::identity() * half, half);
+        assert_eq!(half * Quaternion::identity(), half);
+
+        // The right hand rotation is applied first
+        let a = Quaternion::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), 0.7);
+        let b = Quaternion::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), -1.1);
+        let v = Vec3::new(0.3, -1.2, 2.5);
+        assert!(((a * b).rotate_vec3(&v) - a.rotate_vec3(&b.rotate_vec3(&v))).len() < 1e-5);
+
+        let matrix = (a * b).to_matrix44();
+        let composed = b.to_matrix44() * a.to_matrix44();
+        for (x, y) in matrix.iter_elements().zip(composed.iter_elements()) {
+            assert!((x - y).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_slerp_same_rotation() {
+        let q = Quaternion::from_axis_angle(&Vec3::new(1.0, 1.0, 0.0), 2.0);
+
+        for t in [0.0, 0.25, 0.5, 1.0, 1.5] {
+            assert_quaternion_close(&Quaternion::slerp(&q, &q, t), &q);
+        }
+    }
+
+    #[test]
+    fn test_slerp() {
+        // 0 and 90 degree rotations about the z axis
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        let a = Quaternion::identity();
+        let b = Quaternion::new(0.0, 0.0, half_sqrt2, half_sqrt2);
+
+        // Halfway should be a 45 degree rotation about the z axis
+        let expected_angle = std::f32::consts::FRAC_PI_8;
+        let q = Quaternion::slerp(&a, &b, 0.5);
+        assert!((q.z - expected_angle.sin()).abs() < 1e-6);
+        assert!((q.w - expected_angle.cos()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_to_matrix44() {
+        // 90 degree rotation about the z axis
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        let q = Quaternion::new(0.0, 0.0, half_sqrt2, half_sqrt2);
+
+        let rotated = Vec3::new(1.0f32, 0.0, 0.0).mult_matrix(&q.to_matrix44());
+        assert!((rotated.x - 0.0).abs() < 1e-6);
+        assert!((rotated.y - 1.0).abs() < 1e-6);
+        assert!((rotated.z - 0.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rotate_vec3() {
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        let q = Quaternion::new(0.0, 0.0, half_sqrt2, half_sqrt2);
+
+        let rotated = q.rotate_vec3(&Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated.x - 0.0).abs() < 1e-6);
+        assert!((rotated.y - 1.0).abs() < 1e-6);
+        assert!((rotated.z - 0.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_from_matrix44_round_trip() {
+        // Rotations which exercise every branch of Shepperd's method
+        let mut rotations = [
+            Quaternion::new(0.1, 0.2, 0.3, 0.9), // Positive trace
+            Quaternion::new(0.9, 0.2, 0.1, 0.1), // x largest
+            Quaternion::new(0.2, 0.9, 0.1, 0.1), // y largest
+            Quaternion::new(0.1, 0.2, 0.9, 0.1), // z largest
+            Quaternion::new(0.0, 0.0, 1.0, 0.0), // 180 degrees about z
+        ];
+
+        let v = Vec3::new(0.3, -1.2, 2.5);
+        for q in rotations.iter_mut() {
+            q.normalise();
+            let extracted = Quaternion::from_matrix44(&q.to_matrix44());
+
+            // q and -q are the same rotation so compare the rotated vectors
+            let expected = q.rotate_vec3(&v);
+            let actual = extracted.rotate_vec3(&v);
+            assert!((expected.x - actual.x).abs() < 1e-5, "{:?} != {:?}", expected, actual);
+            assert!((expected.y - actual.y).abs() < 1e-5, "{:?} != {:?}", expected, actual);
+            assert!((expected.z - actual.z).abs() < 1e-5, "{:?} != {:?}", expected, actual);
+            assert!((q.dot(&extracted).abs() - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -1,87 +1,302 @@
+// Migrating from VertexAttributes
+// Vertex and Triangle are generic over the attributes stored with each vertex, any type implementing VertexAttribute can be used
+// The attribute type defaults to ColourAttribute which has the same single colour field as the old VertexAttributes struct
+// VertexAttributes is kept as an alias of ColourAttribute so existing code keeps compiling, new code should use ColourAttribute
+// Attributes are now interpolated with Lerp::lerp, custom attributes only need to implement Lerp and VertexAttribute to be rasterised
+
 use crate::num::Num;
-use crate::colour::Colour;
+use crate::colour::{Colour, ToneMapOp, WHITE};
 use crate::linear_algebra::*;
-use crate::frame_buffer::{FrameBuffer, FrameBufferTrait};
+use crate::frame_buffer::{DepthBuffer, FrameBuffer, FrameBufferTrait};
+use crate::camera::Camera;
+use crate::shadow_map::ShadowMap;
 
+// Vertex order of front facing triangles
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WindingOrder {
     CCW,
     CW
 }
 
+// Which triangles are skipped based on the direction they face
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingMode {
+    Smooth, // Attributes are interpolated across the triangle
+    Flat, // The whole triangle uses the attributes of v0
+}
+
+// How a pixel is combined with the pixel already in the frame buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Replace,
+    AlphaBlend, // Source over destination using the source alpha
+}
+
+// Settings used when rasterising a triangle
+pub struct RasteriseOptions<'a> {
+    pub winding: WindingOrder,
+    pub cull: CullMode,
+
+    // Pixels outside of the scissor rectangle are not drawn
+    // The minimum of each range is inclusive and the maximum is exclusive
+    // Pixel coordinates are i32 because BoundingBox needs a Num, which unsigned types can't implement as it requires Neg
+    pub scissor: Option<BoundingBox<i32>>,
+
+    pub shade_mode: ShadingMode,
+    pub blend: BlendMode,
+
+    // When false colours are clamped to [0, 1] before they are written
+    // Set to true when drawing to a HdrFrameBuffer to keep radiance values above 1
+    pub hdr: bool,
+
+    // Pixels which the shadow map says are in shadow are drawn black
+    // Only applies to attributes which provide a world position, see VertexAttribute::world_position
+    pub shadow: Option<&'a ShadowMap>,
+}
+
+impl Default for RasteriseOptions<'_> {
+    fn default() -> Self {
+        RasteriseOptions {
+            winding: WindingOrder::CCW,
+            cull: CullMode::Back,
+            scissor: None,
+            shade_mode: ShadingMode::Smooth,
+            blend: BlendMode::Replace,
+            hdr: false,
+            shadow: None,
+        }
+    }
+}
+
+// Values which can be linearly interpolated across a triangle
+pub trait Lerp: Copy {
+    // Returns the value t of the way from self to other
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self * (1.0 - t) + other * t
+    }
+}
+
+impl Lerp for Vec2<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec2::lerp(self, other, t)
+    }
+}
+
+impl Lerp for Vec3<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec3::lerp_unclamped(self, other, t)
+    }
+}
+
+impl Lerp for Colour {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Colour::lerp(*self, *other, t)
+    }
+}
+
+// Data stored with each vertex which is interpolated across the triangle when it is rasterised
+pub trait VertexAttribute: Lerp {
+    // Colour written to the frame buffer for a pixel with this attribute
+    fn colour(&self) -> Colour {
+        WHITE
+    }
+
+    // World space position of the pixel, used to look up shadows
+    fn world_position(&self) -> Option<Vec3<f32>> {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ColourAttribute {
+    pub colour: Colour,
+}
+
+impl Lerp for ColourAttribute {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        ColourAttribute {
+            colour: self.colour.lerp(&other.colour, t),
+        }
+    }
+}
+
+impl VertexAttribute for ColourAttribute {
+    fn colour(&self) -> Colour {
+        self.colour
+    }
+}
+
+// Texture coordinates
+#[derive(Clone, Copy)]
+pub struct UvAttribute {
+    pub uv: Vec2<f32>,
+}
+
+impl Lerp for UvAttribute {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        UvAttribute {
+            uv: self.uv.lerp(&other.uv, t),
+        }
+    }
+}
+
+impl VertexAttribute for UvAttribute {}
+
+// Colour, texture coordinates, and normal
+// The interpolated normal is not renormalised
+#[derive(Clone, Copy)]
+pub struct FullAttribute {
+    pub colour: Colour,
+    pub uv: Vec2<f32>,
+    pub normal: Vec3<f32>,
+}
+
+impl Lerp for FullAttribute {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        FullAttribute {
+            colour: self.colour.lerp(&other.colour, t),
+            uv: self.uv.lerp(&other.uv, t),
+            normal: self.normal.lerp(&other.normal, t),
+        }
+    }
+}
+
+impl VertexAttribute for FullAttribute {
+    fn colour(&self) -> Colour {
+        self.colour
+    }
+}
+
+// Colour and world space position, needed to receive shadows
 #[derive(Clone, Copy)]
-pub struct VertexAttributes {
+pub struct PositionAttribute {
     pub colour: Colour,
+    pub world_position: Vec3<f32>,
 }
 
-impl VertexAttributes {
-    fn new() -> Self {
-        VertexAttributes { 
-            colour: Colour::new(),
+impl Lerp for PositionAttribute {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        PositionAttribute {
+            colour: self.colour.lerp(&other.colour, t),
+            world_position: self.world_position.lerp(&other.world_position, t),
         }
     }
 }
 
+impl VertexAttribute for PositionAttribute {
+    fn colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn world_position(&self) -> Option<Vec3<f32>> {
+        Some(self.world_position)
+    }
+}
+
+// Kept so code written before attributes were generic still compiles, use ColourAttribute instead
+pub type VertexAttributes = ColourAttribute;
+
 #[derive(Clone, Copy)]
-pub struct Vertex<T: Num> {
+pub struct Vertex<T: Num, A: VertexAttribute = ColourAttribute> {
     pub vertex: Vec3<T>,
-    pub attributes: VertexAttributes,
+    pub attributes: A,
 }
 
-impl<T: Num> Vertex<T> {
-    pub fn new(vertex: Vec3<T>, attributes: VertexAttributes) -> Self {
+impl<T: Num, A: VertexAttribute> Vertex<T, A> {
+    pub fn new(vertex: Vec3<T>, attributes: A) -> Self {
         Vertex {vertex, attributes}
     }
 }
 
 #[derive(Clone, Copy)]
-pub struct Triangle<T: Num> {
-    pub v0: Vertex<T>,
-    pub v1: Vertex<T>,
-    pub v2: Vertex<T>,
+pub struct Triangle<T: Num, A: VertexAttribute = ColourAttribute> {
+    pub v0: Vertex<T, A>,
+    pub v1: Vertex<T, A>,
+    pub v2: Vertex<T, A>,
+}
+
+impl<T: Num, A: VertexAttribute> Triangle<T, A> {
+    pub fn new(v0: Vertex<T, A>, v1: Vertex<T, A>, v2: Vertex<T, A>) -> Self {
+        Triangle {v0, v1, v2}
+    }
+
+    // Returns the vertices in order v0, v1, v2
+    pub fn vertices(&self) -> [&Vertex<T, A>; 3] {
+        [&self.v0, &self.v1, &self.v2]
+    }
+
+    // Returns mutable references to the vertices in order v0, v1, v2
+    pub fn vertices_mut(&mut self) -> [&mut Vertex<T, A>; 3] {
+        [&mut self.v0, &mut self.v1, &mut self.v2]
+    }
 }
 
-#[derive(Debug)]
+impl<T: Num, A: VertexAttribute> IntoIterator for Triangle<T, A> {
+    type Item = Vertex<T, A>;
+    type IntoIter = std::array::IntoIter<Vertex<T, A>, 3>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        [self.v0, self.v1, self.v2].into_iter()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Range<T: Num> {
     min: T,
     max: T,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BoundingBox<T: Num> {
     x: Range<T>,
     y: Range<T>,
 }
 
+impl<T: Num> BoundingBox<T> {
+    pub fn new(x: Range<T>, y: Range<T>) -> Self {
+        BoundingBox {x, y}
+    }
+}
+
 impl<T: Num> Range<T> {
-    fn find_min_max<const L: usize>(array: [&T; L], type_min: T, type_max: T) -> Self {
-        let mut min = type_max; 
-        let mut max = type_min; 
+    pub fn new(min: T, max: T) -> Self {
+        Range {min, max}
+    }
 
-        for element in array {
-            if *element > max {
-                max = *element;
-            }
+    // Returns value restricted to [min, max]
+    pub fn clamp(&self, value: T) -> T {
+        Num::clamp(value, self.min, self.max)
+    }
 
-            if *element < min {
-                min = *element;
-            }
-        }
+    // Returns true if the ranges share any values, ranges which only touch at an end overlap
+    pub fn overlaps(&self, other: &Range<T>) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
 
-        Range {min, max}
+    pub fn length(&self) -> T {
+        self.max - self.min
     }
 }
 
-impl Triangle<f32> {
+impl<A: VertexAttribute> Triangle<f32, A> {
     pub fn transform_this_triangle(&mut self, transformation_matrix: &Matrix44) {
-        let vertices = [&mut self.v0.vertex, &mut self.v1.vertex, &mut self.v2.vertex];
-
-        for vertex in vertices {
-            *vertex = vertex.homogeneous_mult_matrix(transformation_matrix);
+        for vertex in self.vertices_mut() {
+            vertex.vertex = vertex.vertex.homogeneous_mult_matrix(transformation_matrix);
         }
-        
     }
 
-    pub fn transform_triangle(&self, transformation_matrix: &Matrix44) -> Triangle<f32> {
+    pub fn transform_triangle(&self, transformation_matrix: &Matrix44) -> Triangle<f32, A> {
         let vertices = [&self.v0.vertex, &self.v1.vertex, &self.v2.vertex];
 
         let mut new_triangle = Triangle {
@@ -100,30 +315,54 @@ impl Triangle<f32> {
     }
 
     pub fn get_bounding_box(&self) -> BoundingBox<f32> {
-        let vertices_x = [&self.v0.vertex.x, &self.v1.vertex.x, &self.v2.vertex.x];
-        let vertices_y = [&self.v0.vertex.y, &self.v1.vertex.y, &self.v2.vertex.y];
+        let min = self.v0.vertex.component_min(&self.v1.vertex).component_min(&self.v2.vertex);
+        let max = self.v0.vertex.component_max(&self.v1.vertex).component_max(&self.v2.vertex);
 
         BoundingBox {
-            x: Range::find_min_max(vertices_x, f32::NEG_INFINITY, f32::INFINITY),
-            y: Range::find_min_max(vertices_y, f32::NEG_INFINITY, f32::INFINITY),
+            x: Range::new(min.x, max.x),
+            y: Range::new(min.y, max.y),
         }
     }
 
-    // Divide vertex attributes by their z coordiante for perspective correct interpolation
-    fn divide_attributes(&self) -> [VertexAttributes; 3] {
-        let mut new_attributes = [VertexAttributes::new(), VertexAttributes::new(), VertexAttributes::new()];
-
-        for (i, vertex) in [&self.v0, &self.v1, &self.v2].iter().enumerate() {
-            let zdiv = 1.0 / vertex.vertex.z;
-            let colour = &vertex.attributes.colour;
+    // Returns true if the triangle has (almost) no area, which happens when two vertices coincide or all three are in a line
+    // epsilon is compared against the length of the cross product of two edges, which is twice the area
+    pub fn is_degenerate(&self, epsilon: f32) -> bool {
+        let edge = |a: &Vec3<f32>, b: &Vec3<f32>| Vec3::new(b.x - a.x, b.y - a.y, b.z - a.z);
+        let cross_product = edge(&self.v0.vertex, &self.v1.vertex).cross(&edge(&self.v0.vertex, &self.v2.vertex));
 
-            new_attributes[i].colour = colour.multiply_float(zdiv);
+        // Triangles with NaN vertices are also degenerate
+        let length = cross_product.len();
+        length.is_nan() || length < epsilon
+    }
 
+    // Returns the area of the triangle in pixels after it is projected into raster space by the camera
+    // Returns None if any vertex cannot be projected
+    pub fn screen_space_area(&self, camera: &Camera) -> Option<f32> {
+        let mut raster_points = [Vec2::splat(0.0f32); 3];
+        for (i, vertex) in self.vertices().iter().enumerate() {
+            let raster_point = camera.point_to_raster(&vertex.vertex).ok()?;
+            raster_points[i] = Vec2::new(raster_point.x as f32, raster_point.y as f32);
         }
 
-        new_attributes
+        let [p0, p1, p2] = raster_points;
+        let double_area = (p1.x - p0.x) * (p2.y - p0.y) - (p2.x - p0.x) * (p1.y - p0.y);
+        Some(double_area.abs() / 2.0)
     }
+}
+
+// Interpolates the value get_attr reads from each vertex using barycentric weights which sum to one
+// The weighted sum is built from two lerps so values only need to implement Lerp
+pub fn interpolate_attribute<A: VertexAttribute, L: Lerp>(tri: &Triangle<f32, A>, barycentric: (f32, f32, f32), get_attr: impl Fn(&Vertex<f32, A>) -> L) -> L {
+    let (l0, l1, l2) = barycentric;
+
+    let l01 = l0 + l1;
+    let value01 = if l01 == 0.0 {
+        get_attr(&tri.v0)
+    } else {
+        get_attr(&tri.v0).lerp(&get_attr(&tri.v1), l1 / l01)
+    };
 
+    value01.lerp(&get_attr(&tri.v2), l2)
 }
 
 // Return true if this edge is a top or left edge
@@ -145,13 +384,73 @@ fn edge_fn<T: Num>(v0: &Vec3<T>, v1: &Vec3<T>, p: &Vec3<T>, winding: &WindingOrd
     let result = ((p.x - v0.x) * (v1.y - v0.y)) - ((p.y - v0.y) * (v1.x - v0.x));
 
     match winding {
-        WindingOrder::CCW => return -result,
-        WindingOrder::CW => return result,
+        WindingOrder::CCW => -result,
+        WindingOrder::CW => result,
     }
 }
 
+// Triangles whose cross product is shorter than this are skipped by the rasteriser, see Triangle::is_degenerate
+const DEGENERATE_EPSILON: f32 = 1e-6;
+
 // Draws a traingle to the frame buffer
-pub fn rasterise_triangle<T: FrameBufferTrait>(triangle: &Triangle<f32>, frame_buffer: &mut FrameBuffer<T>, winding: &WindingOrder) {
+// Kept with its original winding order parameter so existing callers still compile, options go through rasterise_triangle_with_options
+#[deprecated(note = "use rasterise_triangle_with_options")]
+pub fn rasterise_triangle<T: FrameBufferTrait, A: VertexAttribute>(triangle: &Triangle<f32, A>, frame_buffer: &mut FrameBuffer<T>, winding: &WindingOrder) {
+    let options = RasteriseOptions {
+        winding: *winding,
+        ..RasteriseOptions::default()
+    };
+
+    rasterise_triangle_with_options(triangle, frame_buffer, &options);
+}
+
+// Draws a traingle to the frame buffer using the given options
+// Every covered pixel is written, so triangles must be drawn from back to front, see rasterise_triangle_with_depth
+pub fn rasterise_triangle_with_options<T: FrameBufferTrait, A: VertexAttribute>(triangle: &Triangle<f32, A>, frame_buffer: &mut FrameBuffer<T>, options: &RasteriseOptions) {
+    rasterise(triangle, frame_buffer, None, options);
+}
+
+// Draws a traingle to the frame buffer using the given options, skipping pixels which are behind what has already been drawn
+// A pixel is only written if its interpolated z is less than the depth stored for it, the depth buffer is then updated
+// Pixels outside of the depth buffer are not drawn
+pub fn rasterise_triangle_with_depth<T: FrameBufferTrait, A: VertexAttribute>(triangle: &Triangle<f32, A>, frame_buffer: &mut FrameBuffer<T>, depth_buffer: &mut DepthBuffer, options: &RasteriseOptions) {
+    rasterise(triangle, frame_buffer, Some(depth_buffer), options);
+}
+
+fn rasterise<T: FrameBufferTrait, A: VertexAttribute>(triangle: &Triangle<f32, A>, frame_buffer: &mut FrameBuffer<T>, depth_buffer: Option<&mut DepthBuffer>, options: &RasteriseOptions) {
+    // Degenerate triangles cover no pixels and would divide by zero when finding barycentric coordinates
+    if triangle.is_degenerate(DEGENERATE_EPSILON) {
+        return;
+    }
+
+    let winding = &options.winding;
+
+    // Twice the signed area, positive for front facing triangles
+    let double_triangle_area = edge_fn(&triangle.v0.vertex, &triangle.v1.vertex, &triangle.v2.vertex, winding);
+    let front_facing = double_triangle_area > 0.0;
+
+    let culled = match options.cull {
+        CullMode::None => false,
+        CullMode::Back => !front_facing,
+        CullMode::Front => front_facing,
+    };
+
+    if culled || double_triangle_area == 0.0 {
+        return;
+    }
+
+    // Back faces are drawn by reversing their winding so the edge functions are positive inside the triangle
+    if front_facing {
+        rasterise_front_face(triangle, frame_buffer, depth_buffer, options);
+    } else {
+        let flipped = Triangle::new(triangle.v0, triangle.v2, triangle.v1);
+        rasterise_front_face(&flipped, frame_buffer, depth_buffer, options);
+    }
+}
+
+// Draws a triangle whose vertices are in the winding order given by the options
+fn rasterise_front_face<T: FrameBufferTrait, A: VertexAttribute>(triangle: &Triangle<f32, A>, frame_buffer: &mut FrameBuffer<T>, mut depth_buffer: Option<&mut DepthBuffer>, options: &RasteriseOptions) {
+    let winding = &options.winding;
 
     // Add bias to corresponding edge function functions
     // This avoids calculating if edges are top / left multiple times
@@ -162,36 +461,49 @@ pub fn rasterise_triangle<T: FrameBufferTrait>(triangle: &Triangle<f32>, frame_b
 
     // Calculate delta w's 
     // This works because each edge function changes by the same amount across a row or a column
+    // The edge function changes sign with the winding order so the deltas do as well
     // https://youtu.be/k5wtuKWmV48?si=qOR57hqKZoHXAVYW&t=6290
-    let delta_w0_x = triangle.v0.vertex.y - triangle.v1.vertex.y;
-    let delta_w1_x = triangle.v1.vertex.y - triangle.v2.vertex.y;
-    let delta_w2_x = triangle.v2.vertex.y - triangle.v0.vertex.y;
+    let winding_sign = match winding {
+        WindingOrder::CCW => 1.0,
+        WindingOrder::CW => -1.0,
+    };
 
-    let delta_w0_y = triangle.v1.vertex.x - triangle.v0.vertex.x;
-    let delta_w1_y = triangle.v2.vertex.x - triangle.v1.vertex.x;
-    let delta_w2_y = triangle.v0.vertex.x - triangle.v2.vertex.x;
+    let delta_w0_x = (triangle.v0.vertex.y - triangle.v1.vertex.y) * winding_sign;
+    let delta_w1_x = (triangle.v1.vertex.y - triangle.v2.vertex.y) * winding_sign;
+    let delta_w2_x = (triangle.v2.vertex.y - triangle.v0.vertex.y) * winding_sign;
+
+    let delta_w0_y = (triangle.v1.vertex.x - triangle.v0.vertex.x) * winding_sign;
+    let delta_w1_y = (triangle.v2.vertex.x - triangle.v1.vertex.x) * winding_sign;
+    let delta_w2_y = (triangle.v0.vertex.x - triangle.v2.vertex.x) * winding_sign;
 
     let bounding_box = triangle.get_bounding_box();
-    let px_bounding_box = BoundingBox {
+    let mut px_bounding_box = BoundingBox {
         x: Range {min: bounding_box.x.min.floor() as i32, max: bounding_box.x.max.ceil() as i32},
         y: Range {min: bounding_box.y.min.floor() as i32, max: bounding_box.y.max.ceil() as i32},
     };
 
+    // Viewport clip, only iterate over pixels inside the frame buffer and the scissor rectangle
+    // Without this triangles reaching far outside the buffer would loop over billions of pixels which are never drawn
+    // Float to int casts saturate so vertices at huge or infinite coordinates are clamped correctly as well
+    let frame_rectangle = BoundingBox::new(Range::new(0, frame_buffer.width_px as i32), Range::new(0, frame_buffer.height_px as i32));
+    for clip in std::iter::once(frame_rectangle).chain(options.scissor) {
+        px_bounding_box.x.min = clip.x.clamp(px_bounding_box.x.min);
+        px_bounding_box.x.max = clip.x.clamp(px_bounding_box.x.max);
+        px_bounding_box.y.min = clip.y.clamp(px_bounding_box.y.min);
+        px_bounding_box.y.max = clip.y.clamp(px_bounding_box.y.max);
+    }
+
     // Add 0.5 to check pixel center
-    let start_point = Vec3::new(bounding_box.x.min.floor() + 0.5, bounding_box.y.min.floor() + 0.5, 0.0);
+    let start_point = Vec3::new(px_bounding_box.x.min as f32 + 0.5, px_bounding_box.y.min as f32 + 0.5, 0.0);
 
     // Calculate starting edge functions do apply deltas to as we move through the bounding box
     let mut col_w0 = edge_fn(&triangle.v0.vertex, &triangle.v1.vertex, &start_point, winding) + bias0;
     let mut col_w1 = edge_fn(&triangle.v1.vertex, &triangle.v2.vertex, &start_point, winding) + bias1;
     let mut col_w2 = edge_fn(&triangle.v2.vertex, &triangle.v0.vertex, &start_point, winding) + bias2;
-    let double_triangle_area = col_w0 + col_w1 + col_w2; 
 
     // Precompute 1/z's for perspective correct barycentric interpolation 
     let div_zs: [f32; 3] = [1.0 / triangle.v0.vertex.z, 1.0 / triangle.v1.vertex.z, 1.0 / triangle.v2.vertex.z];
 
-    // Divide 
-    let divided_attributes = triangle.divide_attributes();
-
     for x in px_bounding_box.x.min..px_bounding_box.x.max {
 
         let mut w0 = col_w0;
@@ -212,22 +524,60 @@ pub fn rasterise_triangle<T: FrameBufferTrait>(triangle: &Triangle<f32>, frame_b
                 continue;
             }
 
-            // Barycentric coordinates
-            let l0 = w1 / double_triangle_area;
-            let l1 = w2 / double_triangle_area;
-            let l2 = w0 / double_triangle_area;
+            // Barycentric coordinates of the pixel centre
+            // These are found without the top left bias which is only used to decide coverage
+            let pixel_centre = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+            let (l0, l1, l2) = Vec3::cartesian_to_barycentric(&pixel_centre, &triangle.v0.vertex, &triangle.v1.vertex, &triangle.v2.vertex);
 
             // Get perspective correct interpolated z
             let interpolated_z = 1.0 / (div_zs[0] * l0 + div_zs[1] * l1 + div_zs[2] * l2);
 
-            // Interpolate pixel colour using barycentric coorindates (perspective correct)
-            let pixel_colour = (
-                divided_attributes[0].colour.multiply_float(l0) +
-                divided_attributes[1].colour.multiply_float(l1) +
-                divided_attributes[2].colour.multiply_float(l2)
-            ).multiply_float(interpolated_z);
+            // Depth test before shading so hidden pixels aren't shaded
+            if let Some(depth_buffer) = depth_buffer.as_deref() {
+                match depth_buffer.read(x as usize, y as usize) {
+                    Ok(depth) if interpolated_z < depth => (),
+                    _ => continue,
+                }
+            }
+
+            let attributes = match options.shade_mode {
+                ShadingMode::Flat => triangle.v0.attributes,
+                ShadingMode::Smooth => {
+                    // Interpolate attributes using perspective correct barycentric coordinates
+                    let weights = (
+                        div_zs[0] * l0 * interpolated_z,
+                        div_zs[1] * l1 * interpolated_z,
+                        div_zs[2] * l2 * interpolated_z,
+                    );
+                    interpolate_attribute(triangle, weights, |vertex| vertex.attributes)
+                },
+            };
+
+            let mut pixel_colour = attributes.colour();
+            if let (Some(shadow_map), Some(world_position)) = (options.shadow, attributes.world_position()) {
+                let shadow = shadow_map.test(&world_position);
+                pixel_colour = Colour {alpha: pixel_colour.alpha, ..pixel_colour.multiply_float(shadow)};
+            }
 
-            let _ = frame_buffer.write_buf(x as usize, y as usize, &pixel_colour);
+            let pixel_colour = match options.blend {
+                BlendMode::Replace => pixel_colour,
+                BlendMode::AlphaBlend => {
+                    match frame_buffer.read_buf(x as usize, y as usize) {
+                        Ok(destination) => pixel_colour.multiply_float(pixel_colour.alpha) + destination.multiply_float(1.0 - pixel_colour.alpha),
+                        Err(_) => continue,
+                    }
+                },
+            };
+
+            let pixel_colour = if options.hdr {pixel_colour} else {ToneMapOp::Clamp.apply(&pixel_colour)};
+
+            // Degenerate geometry can produce NaN or infinite colours, skip them rather than corrupting the buffer
+            if pixel_colour.is_finite() {
+                let _ = frame_buffer.write_buf(x as usize, y as usize, &pixel_colour);
+                if let Some(depth_buffer) = depth_buffer.as_deref_mut() {
+                    let _ = depth_buffer.write(x as usize, y as usize, interpolated_z);
+                }
+            }
         }
 
         col_w0 += delta_w0_x;
@@ -239,6 +589,390 @@ pub fn rasterise_triangle<T: FrameBufferTrait>(triangle: &Triangle<f32>, frame_b
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::colour::{RED, GREEN, BLUE};
+    use crate::frame_buffer::HdrFrameBuffer;
+
+    fn triangle() -> Triangle<f32> {
+        Triangle::new(
+            Vertex::new(Vec3::new(0.0, 0.0, 1.0), ColourAttribute { colour: RED }),
+            Vertex::new(Vec3::new(4.0, 0.0, 1.0), ColourAttribute { colour: GREEN }),
+            Vertex::new(Vec3::new(0.0, 4.0, 1.0), ColourAttribute { colour: BLUE }),
+        )
+    }
 
-}
+    #[test]
+    fn test_into_iter() {
+        let triangle = triangle();
+        let vertices: Vec<Vertex<f32>> = triangle.into_iter().collect();
+
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(vertices[0].vertex, triangle.v0.vertex);
+        assert_eq!(vertices[1].vertex, triangle.v1.vertex);
+        assert_eq!(vertices[2].vertex, triangle.v2.vertex);
+    }
+
+    #[test]
+    fn test_interpolate_attributes() {
+        let triangle = triangle();
+
+        let colour = interpolate_attribute(&triangle, (0.6, 0.2, 0.2), |vertex| vertex.attributes.colour).to_colour8();
+        assert_eq!(colour, Colour { red: 0.6, green: 0.2, blue: 0.2, alpha: 1.0 }.to_colour8());
+
+        // Weights at a vertex give exactly that vertices attributes
+        assert_eq!(interpolate_attribute(&triangle, (0.0, 0.0, 1.0), |vertex| vertex.attributes).colour.to_colour8(), BLUE.to_colour8());
+    }
+
+    #[test]
+    fn test_vertex_attributes_lerp() {
+        let a = VertexAttributes { colour: Colour { red: 1.0, green: 0.2, blue: 0.0, alpha: 1.0 } };
+        let b = VertexAttributes { colour: Colour { red: 0.0, green: 0.6, blue: 0.5, alpha: 0.0 } };
+
+        let colour = VertexAttributes::lerp(&a, &b, 0.5).colour;
+        let average = Colour { red: 0.5, green: 0.4, blue: 0.25, alpha: 0.5 };
+        assert!(colour.approx_eq(&average, 1e-6));
+    }
+
+    #[test]
+    fn test_uv_attribute() {
+        let triangle = Triangle::new(
+            Vertex::new(Vec3::new(0.0, 0.0, 1.0), UvAttribute { uv: Vec2::new(0.0, 0.0) }),
+            Vertex::new(Vec3::new(4.0, 0.0, 1.0), UvAttribute { uv: Vec2::new(1.0, 0.0) }),
+            Vertex::new(Vec3::new(0.0, 4.0, 1.0), UvAttribute { uv: Vec2::new(0.0, 1.0) }),
+        );
+
+        let uv = interpolate_attribute(&triangle, (0.5, 0.25, 0.25), |vertex| vertex.attributes.uv);
+        assert_eq!(uv, Vec2::new(0.25, 0.25));
+    }
+
+    #[test]
+    fn test_interpolate_uv_at_centroid() {
+        let uvs = [Vec2::new(0.2, 0.1), Vec2::new(0.9, 0.3), Vec2::new(0.4, 0.8)];
+        let triangle = Triangle::new(
+            Vertex::new(Vec3::new(0.0, 0.0, 1.0), UvAttribute { uv: uvs[0] }),
+            Vertex::new(Vec3::new(4.0, 0.0, 1.0), UvAttribute { uv: uvs[1] }),
+            Vertex::new(Vec3::new(0.0, 4.0, 1.0), UvAttribute { uv: uvs[2] }),
+        );
+
+        let third = 1.0 / 3.0;
+        let uv = interpolate_attribute(&triangle, (third, third, third), |vertex| vertex.attributes.uv);
+
+        let average = Vec2::new((uvs[0].x + uvs[1].x + uvs[2].x) / 3.0, (uvs[0].y + uvs[1].y + uvs[2].y) / 3.0);
+        assert!((uv.x - average.x).abs() < 1e-6);
+        assert!((uv.y - average.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_range_clamp() {
+        let range = Range { min: 2, max: 8 };
+
+        assert_eq!(range.clamp(10), 8);
+        assert_eq!(range.clamp(-1), 2);
+        assert_eq!(range.clamp(5), 5);
+        assert_eq!(range.length(), 6);
+    }
+
+    #[test]
+    fn test_range_overlaps() {
+        let range = Range::new(2.0, 8.0);
+
+        assert!(range.overlaps(&Range::new(5.0, 12.0)));
+        assert!(range.overlaps(&Range::new(3.0, 4.0)));
+        assert!(range.overlaps(&Range::new(8.0, 9.0)));
+        assert!(!range.overlaps(&Range::new(9.0, 12.0)));
+        assert!(!range.overlaps(&Range::new(-3.0, 1.0)));
+    }
+
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 16;
+
+    fn frame_buffer() -> FrameBuffer<[u32; WIDTH * HEIGHT]> {
+        FrameBuffer::new(WIDTH, HEIGHT, [0; WIDTH * HEIGHT])
+    }
+
+    // Number of pixels which have been written to
+    fn drawn_pixels<T: FrameBufferTrait + AsRef<[u32]>>(frame_buffer: &FrameBuffer<T>) -> usize {
+        frame_buffer.read_as_slice().iter().filter(|pixel| **pixel != 0).count()
+    }
+
+    #[test]
+    fn test_cull_mode() {
+        let front = triangle();
+        let back = Triangle::new(front.v0, front.v2, front.v1);
+
+        let mut frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&front, &mut frame_buffer, &RasteriseOptions::default());
+        let front_pixels = drawn_pixels(&frame_buffer);
+        assert!(front_pixels > 0);
+
+        // Back faces are culled by default
+        let mut frame_buffer = self::frame_buffer();
+        rasterise_triangle_with_options(&back, &mut frame_buffer, &RasteriseOptions::default());
+        assert_eq!(drawn_pixels(&frame_buffer), 0);
+
+        // Without culling back faces cover the same pixels as front faces
+        let options = RasteriseOptions {cull: CullMode::None, ..RasteriseOptions::default()};
+        let mut frame_buffer = self::frame_buffer();
+        rasterise_triangle_with_options(&back, &mut frame_buffer, &options);
+        assert_eq!(drawn_pixels(&frame_buffer), front_pixels);
+
+        let options = RasteriseOptions {cull: CullMode::Front, ..RasteriseOptions::default()};
+        let mut frame_buffer = self::frame_buffer();
+        rasterise_triangle_with_options(&front, &mut frame_buffer, &options);
+        assert_eq!(drawn_pixels(&frame_buffer), 0);
+    }
+
+    #[test]
+    fn test_winding_order() {
+        let ccw = triangle();
+        let cw = Triangle::new(ccw.v0, ccw.v2, ccw.v1);
+
+        let mut ccw_frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&ccw, &mut ccw_frame_buffer, &RasteriseOptions::default());
+
+        let options = RasteriseOptions {winding: WindingOrder::CW, ..RasteriseOptions::default()};
+        let mut cw_frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&cw, &mut cw_frame_buffer, &options);
+
+        assert_eq!(drawn_pixels(&cw_frame_buffer), drawn_pixels(&ccw_frame_buffer));
+    }
+
+    #[test]
+    fn test_scissor() {
+        let options = RasteriseOptions {
+            scissor: Some(BoundingBox::new(Range::new(0, 2), Range::new(0, 2))),
+            ..RasteriseOptions::default()
+        };
+
+        let mut frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&triangle(), &mut frame_buffer, &options);
 
+        assert_eq!(drawn_pixels(&frame_buffer), 4);
+    }
+
+    #[test]
+    fn test_viewport_clip() {
+        let vertex = |x: f32, y: f32| Vertex::new(Vec3::new(x, y, 1.0), ColourAttribute { colour: WHITE });
+        let options = RasteriseOptions {cull: CullMode::None, ..RasteriseOptions::default()};
+
+        let mut frame_buffer = frame_buffer();
+        let triangle = Triangle::new(vertex(-1000.0, -1000.0), vertex(10.0, -1000.0), vertex(10.0, 10.0));
+        rasterise_triangle_with_options(&triangle, &mut frame_buffer, &options);
+
+        // Only the part of the triangle below y = x and left of x = 10 is inside the buffer
+        // Pixel centres on the diagonal edge are decided by the top left rule so they aren't checked
+        for (x, y, colour) in frame_buffer.pixels().filter(|(x, y, _)| x != y) {
+            let inside = x < 10 && y < x;
+            assert_eq!(colour == WHITE.to_colour8(), inside, "({}, {})", x, y);
+        }
+
+        // Huge triangles finish quickly because only pixels in the buffer are visited
+        let mut frame_buffer = self::frame_buffer();
+        let triangle = Triangle::new(vertex(-1e9, -1e9), vertex(1e9, -1e9), vertex(0.0, 1e9));
+        rasterise_triangle_with_options(&triangle, &mut frame_buffer, &options);
+        assert_eq!(drawn_pixels(&frame_buffer), WIDTH * HEIGHT);
+    }
+
+    #[test]
+    fn test_flat_shading() {
+        let options = RasteriseOptions {shade_mode: ShadingMode::Flat, ..RasteriseOptions::default()};
+
+        let mut frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&triangle(), &mut frame_buffer, &options);
+
+        for (_, _, colour) in frame_buffer.pixels() {
+            assert!(colour == RED.to_colour8() || colour.to_u32() == 0);
+        }
+    }
+
+    #[test]
+    fn test_alpha_blend() {
+        let mut triangle = triangle();
+        for vertex in triangle.vertices_mut() {
+            vertex.attributes.colour = Colour { red: 1.0, green: 0.0, blue: 0.0, alpha: 0.5 };
+        }
+
+        let options = RasteriseOptions {blend: BlendMode::AlphaBlend, ..RasteriseOptions::default()};
+        let mut frame_buffer = frame_buffer();
+        frame_buffer.clear_with_colour(&BLUE.to_colour8());
+        rasterise_triangle_with_options(&triangle, &mut frame_buffer, &options);
+
+        let colour = frame_buffer.read_buf(0, 0).unwrap().to_colour8();
+        assert_eq!((colour.red, colour.green, colour.blue), (128, 0, 128));
+    }
+
+    #[test]
+    fn test_screen_space_area() {
+        use crate::camera::FitResolutionGate;
+
+        let camera = Camera::new(Matrix44::identity(), Vec2::new(100, 100), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill);
+
+        let mut triangle = triangle();
+        for vertex in triangle.vertices_mut() {
+            vertex.vertex = Vec3::new(vertex.vertex.x * 0.01, vertex.vertex.y * 0.01, 1.0);
+        }
+        let near_area = triangle.screen_space_area(&camera).unwrap();
+
+        for vertex in triangle.vertices_mut() {
+            vertex.vertex.z = 2.0;
+        }
+        let far_area = triangle.screen_space_area(&camera).unwrap();
+
+        // Doubling the distance halves the projected size, quartering the area
+        assert!(near_area > 0.0);
+        assert!((far_area / near_area - 0.25).abs() < 0.05);
+
+        // Vertices behind the near clipping plane can't be projected
+        for vertex in triangle.vertices_mut() {
+            vertex.vertex.z = 0.0;
+        }
+        assert!(triangle.screen_space_area(&camera).is_none());
+    }
+
+    #[test]
+    fn test_hdr() {
+        let mut triangle = triangle();
+        for vertex in triangle.vertices_mut() {
+            vertex.attributes.colour = Colour { red: 3.0, green: 0.0, blue: 0.0, alpha: 1.0 };
+        }
+
+        let options = RasteriseOptions {hdr: true, ..RasteriseOptions::default()};
+        let mut hdr_frame_buffer = HdrFrameBuffer::new_hdr(WIDTH, HEIGHT);
+        rasterise_triangle_with_options(&triangle, &mut hdr_frame_buffer, &options);
+        assert!((hdr_frame_buffer.read_buf(0, 0).unwrap().red - 3.0).abs() < 1e-5);
+
+        let mut ldr_frame_buffer = HdrFrameBuffer::new_hdr(WIDTH, HEIGHT);
+        rasterise_triangle_with_options(&triangle, &mut ldr_frame_buffer, &RasteriseOptions::default());
+        assert_eq!(ldr_frame_buffer.read_buf(0, 0).unwrap().red, 1.0);
+    }
+
+    #[test]
+    fn test_is_degenerate() {
+        let valid = triangle();
+        assert!(!valid.is_degenerate(DEGENERATE_EPSILON));
+
+        let coincident = Triangle::new(valid.v0, valid.v0, valid.v2);
+        assert!(coincident.is_degenerate(DEGENERATE_EPSILON));
+
+        let mut collinear = valid;
+        collinear.v2.vertex = Vec3::new(8.0, 0.0, 1.0);
+        assert!(collinear.is_degenerate(DEGENERATE_EPSILON));
+
+        let mut frame_buffer = frame_buffer();
+        let options = RasteriseOptions {cull: CullMode::None, ..RasteriseOptions::default()};
+        rasterise_triangle_with_options(&coincident, &mut frame_buffer, &options);
+        assert_eq!(drawn_pixels(&frame_buffer), 0);
+    }
+
+    #[test]
+    fn test_non_finite_triangles() {
+        let options = RasteriseOptions {cull: CullMode::None, ..RasteriseOptions::default()};
+
+        let mut triangle = triangle();
+        triangle.v1.vertex.x = f32::NAN;
+        let mut frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&triangle, &mut frame_buffer, &options);
+        assert_eq!(drawn_pixels(&frame_buffer), 0);
+
+        // NaN colours are skipped so the background is left untouched
+        let mut triangle = self::triangle();
+        triangle.v0.attributes.colour.red = f32::NAN;
+        let mut frame_buffer = self::frame_buffer();
+        frame_buffer.clear_with_colour(&BLUE.to_colour8());
+        rasterise_triangle_with_options(&triangle, &mut frame_buffer, &options);
+        assert!(frame_buffer.pixels().all(|(_, _, colour)| colour == BLUE.to_colour8()));
+    }
+
+    #[test]
+    fn test_vertices_mut() {
+        let mut triangle = triangle();
+        for vertex in triangle.vertices_mut() {
+            vertex.vertex.z = 5.0;
+        }
+
+        for vertex in triangle.vertices() {
+            assert_eq!(vertex.vertex.z, 5.0);
+        }
+    }
+
+    #[test]
+    fn test_depth_buffer() {
+        // Two triangles covering the same pixels, the green one is nearer
+        let flat = |z: f32, colour: Colour| Triangle::new(
+            Vertex::new(Vec3::new(0.0, 0.0, z), ColourAttribute { colour }),
+            Vertex::new(Vec3::new(8.0, 0.0, z), ColourAttribute { colour }),
+            Vertex::new(Vec3::new(0.0, 8.0, z), ColourAttribute { colour }),
+        );
+        let near = flat(1.0, GREEN);
+        let far = flat(2.0, RED);
+
+        // The nearer triangle wins in either draw order
+        for order in [[&near, &far], [&far, &near]] {
+            let mut frame_buffer = frame_buffer();
+            let mut depth_buffer = DepthBuffer::new(WIDTH, HEIGHT);
+            for triangle in order {
+                rasterise_triangle_with_depth(triangle, &mut frame_buffer, &mut depth_buffer, &RasteriseOptions::default());
+            }
+
+            assert_eq!(frame_buffer.read_buf(1, 1).unwrap().to_colour8(), GREEN.to_colour8());
+            assert_eq!(depth_buffer.read(1, 1).unwrap(), 1.0);
+            assert_eq!(depth_buffer.read(15, 15).unwrap(), f32::INFINITY);
+        }
+
+        // Without a depth buffer the last triangle drawn wins
+        let mut frame_buffer = frame_buffer();
+        rasterise_triangle_with_options(&near, &mut frame_buffer, &RasteriseOptions::default());
+        rasterise_triangle_with_options(&far, &mut frame_buffer, &RasteriseOptions::default());
+        assert_eq!(frame_buffer.read_buf(1, 1).unwrap().to_colour8(), RED.to_colour8());
+    }
+
+    #[test]
+    fn test_depth_buffer_intersecting() {
+        // A sloped triangle passes through a flat one at x = 10, where 1 / z is halfway between its vertices
+        let flat = Triangle::new(
+            Vertex::new(Vec3::new(0.0, 0.0, 2.0), ColourAttribute { colour: RED }),
+            Vertex::new(Vec3::new(16.0, 0.0, 2.0), ColourAttribute { colour: RED }),
+            Vertex::new(Vec3::new(0.0, 16.0, 2.0), ColourAttribute { colour: RED }),
+        );
+        let sloped = Triangle::new(
+            Vertex::new(Vec3::new(0.0, 0.0, 1.0), ColourAttribute { colour: GREEN }),
+            Vertex::new(Vec3::new(16.0, 0.0, 5.0), ColourAttribute { colour: GREEN }),
+            Vertex::new(Vec3::new(0.0, 16.0, 1.0), ColourAttribute { colour: GREEN }),
+        );
+
+        let mut frame_buffer = frame_buffer();
+        let mut depth_buffer = DepthBuffer::new(WIDTH, HEIGHT);
+        rasterise_triangle_with_depth(&flat, &mut frame_buffer, &mut depth_buffer, &RasteriseOptions::default());
+        rasterise_triangle_with_depth(&sloped, &mut frame_buffer, &mut depth_buffer, &RasteriseOptions::default());
+
+        assert_eq!(frame_buffer.read_buf(1, 1).unwrap().to_colour8(), GREEN.to_colour8());
+        assert_eq!(frame_buffer.read_buf(13, 1).unwrap().to_colour8(), RED.to_colour8());
+    }
+
+    #[test]
+    fn test_shadow_term() {
+        use crate::mesh::Mesh;
+        use crate::shadow_map::DirectionalLight;
+
+        // Blocker between a light shining along z and the right half of the triangle
+        let blocker_attributes = ColourAttribute { colour: WHITE };
+        let blocker = Mesh::new(vec![Triangle::new(
+            Vertex::new(Vec3::new(2.0, -10.0, 0.0), blocker_attributes),
+            Vertex::new(Vec3::new(20.0, -10.0, 0.0), blocker_attributes),
+            Vertex::new(Vec3::new(2.0, 20.0, 0.0), blocker_attributes),
+        )]);
+        let light = DirectionalLight::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 0.0), 16.0);
+        let shadow_map = ShadowMap::new(&blocker, &light, 64, 0.05);
+
+        // Screen space triangle whose world positions match its screen positions
+        let vertex = |x: f32, y: f32| Vertex::new(Vec3::new(x, y, 1.0), PositionAttribute { colour: WHITE, world_position: Vec3::new(x, y, 1.0) });
+        let triangle = Triangle::new(vertex(0.0, 0.0), vertex(8.0, 0.0), vertex(0.0, 8.0));
+
+        let mut frame_buffer = frame_buffer();
+        let options = RasteriseOptions {shadow: Some(&shadow_map), ..RasteriseOptions::default()};
+        rasterise_triangle_with_options(&triangle, &mut frame_buffer, &options);
+
+        assert_eq!(frame_buffer.read_buf(0, 0).unwrap().to_colour8(), WHITE.to_colour8());
+        let shadowed = frame_buffer.read_buf(4, 1).unwrap().to_colour8();
+        assert_eq!((shadowed.red, shadowed.green, shadowed.blue, shadowed.alpha), (0, 0, 0, 255));
+    }
+}
@@ -0,0 +1,172 @@
+// This file contains basic shadow mapping for directional lights
+// The scene depth is rendered from the light's point of view, a point is in shadow if something is closer to the light than it is
+
+use crate::linear_algebra::{Matrix44, Vec3};
+use crate::mesh::Mesh;
+use crate::rasterisation::VertexAttribute;
+
+// Light infinitely far away which shines in a single direction
+// The shadow map covers a square of side 2 * extent centred on centre, perpendicular to the light direction
+pub struct DirectionalLight {
+    pub direction: Vec3<f32>,
+    pub centre: Vec3<f32>,
+    pub extent: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3<f32>, centre: Vec3<f32>, extent: f32) -> Self {
+        DirectionalLight {direction, centre, extent}
+    }
+
+    // Returns the orthographic matrix which transforms from world space to light space
+    // x and y are in [-1, 1] inside the shadow map and z is the distance along the light direction from the centre
+    pub fn view_projection(&self) -> Matrix44 {
+        let mut direction = self.direction;
+        direction.normalise();
+        let (tangent, bitangent) = Vec3::tangent_frame(&direction);
+
+        let x = tangent / self.extent;
+        let y = bitangent / self.extent;
+        let z = direction;
+
+        Matrix44::new([
+            [x.x, y.x, z.x, 0.0],
+            [x.y, y.y, z.y, 0.0],
+            [x.z, y.z, z.z, 0.0],
+            [-self.centre.dot(&x), -self.centre.dot(&y), -self.centre.dot(&z), 1.0],
+        ])
+    }
+}
+
+// Rendered shadow map along with everything needed to test points against it
+pub struct ShadowMap {
+    pub depths: Vec<f32>,
+    pub light_vp_matrix: Matrix44,
+    pub resolution: usize,
+    pub bias: f32, // Depth offset which stops surfaces from shadowing themselves
+}
+
+impl ShadowMap {
+    pub fn new<A: VertexAttribute>(mesh: &Mesh<A>, light: &DirectionalLight, resolution: usize, bias: f32) -> Self {
+        ShadowMap {
+            depths: render(mesh, light, resolution),
+            light_vp_matrix: light.view_projection(),
+            resolution,
+            bias,
+        }
+    }
+
+    // Returns 0 if the point is in shadow and 1 if it is lit
+    pub fn test(&self, world_pos: &Vec3<f32>) -> f32 {
+        test(world_pos, &self.depths, &self.light_vp_matrix, self.resolution, self.bias)
+    }
+}
+
+// Rasterises the depth of the mesh from the light's point of view into a resolution x resolution map
+// Depths are indexed by px_x + px_y * resolution, pixels which no triangle covers are infinitely far away
+pub fn render<A: VertexAttribute>(mesh: &Mesh<A>, light: &DirectionalLight, resolution: usize) -> Vec<f32> {
+    let light_vp_matrix = light.view_projection();
+    let mut depths = vec![f32::INFINITY; resolution * resolution];
+
+    for triangle in mesh.triangles.iter() {
+        let points = triangle.vertices().map(|vertex| {
+            let light_point = vertex.vertex.homogeneous_mult_matrix(&light_vp_matrix);
+            Vec3::new(to_shadow_map(light_point.x, resolution), to_shadow_map(light_point.y, resolution), light_point.z)
+        });
+
+        // Both windings are drawn since light can hit either side of a triangle
+        let double_area = (points[1].x - points[0].x) * (points[2].y - points[0].y) - (points[2].x - points[0].x) * (points[1].y - points[0].y);
+        if double_area == 0.0 {
+            continue;
+        }
+
+        let min_x = points.iter().map(|p| p.x).fold(f32::INFINITY, f32::min).floor().max(0.0) as usize;
+        let min_y = points.iter().map(|p| p.y).fold(f32::INFINITY, f32::min).floor().max(0.0) as usize;
+        let max_x = (points.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as usize).min(resolution);
+        let max_y = (points.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as usize).min(resolution);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let pixel_centre = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+                let (l0, l1, l2) = Vec3::cartesian_to_barycentric(&pixel_centre, &points[0], &points[1], &points[2]);
+                if l0 < 0.0 || l1 < 0.0 || l2 < 0.0 {
+                    continue;
+                }
+
+                // The projection is orthographic so depth is interpolated linearly
+                let depth = Vec3::barycentric_to_cartesian(l0, l1, l2, &points[0], &points[1], &points[2]).z;
+                let index = x + y * resolution;
+                depths[index] = depths[index].min(depth);
+            }
+        }
+    }
+
+    depths
+}
+
+// Returns 0 if world_pos is in shadow and 1 if it is lit
+// Points outside of the shadow map are lit
+pub fn test(world_pos: &Vec3<f32>, shadow_map: &[f32], light_vp_matrix: &Matrix44, resolution: usize, bias: f32) -> f32 {
+    let light_point = world_pos.homogeneous_mult_matrix(light_vp_matrix);
+    let x = to_shadow_map(light_point.x, resolution).floor();
+    let y = to_shadow_map(light_point.y, resolution).floor();
+
+    if x < 0.0 || y < 0.0 || x >= resolution as f32 || y >= resolution as f32 {
+        return 1.0;
+    }
+
+    let occluder_depth = shadow_map[x as usize + y as usize * resolution];
+    if light_point.z - bias > occluder_depth {0.0} else {1.0}
+}
+
+// Converts a light space coordinate in [-1, 1] to a shadow map pixel coordinate
+fn to_shadow_map(coordinate: f32, resolution: usize) -> f32 {
+    (coordinate * 0.5 + 0.5) * resolution as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::WHITE;
+    use crate::rasterisation::{ColourAttribute, Triangle, Vertex};
+
+    const RESOLUTION: usize = 64;
+    const BIAS: f32 = 0.05;
+
+    // Light shining straight down onto the ground
+    fn light() -> DirectionalLight {
+        DirectionalLight::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, 0.0), 10.0)
+    }
+
+    // Blocker floating 5 units above the ground, covering the area around the origin
+    fn blocker() -> Mesh {
+        let attributes = ColourAttribute { colour: WHITE };
+        Mesh::new(vec![Triangle::new(
+            Vertex::new(Vec3::new(-3.0, 5.0, -3.0), attributes),
+            Vertex::new(Vec3::new(3.0, 5.0, -3.0), attributes),
+            Vertex::new(Vec3::new(0.0, 5.0, 3.0), attributes),
+        )])
+    }
+
+    #[test]
+    fn test_point_behind_blocker() {
+        let light = light();
+        let shadow_map = render(&blocker(), &light, RESOLUTION);
+
+        let shadow = test(&Vec3::new(0.0, 0.0, 0.0), &shadow_map, &light.view_projection(), RESOLUTION, BIAS);
+        assert_eq!(shadow, 0.0);
+    }
+
+    #[test]
+    fn test_lit_points() {
+        let shadow_map = ShadowMap::new(&blocker(), &light(), RESOLUTION, BIAS);
+
+        // Beside the blocker, on top of the blocker, and between the blocker and the light
+        assert_eq!(shadow_map.test(&Vec3::new(8.0, 0.0, 8.0)), 1.0);
+        assert_eq!(shadow_map.test(&Vec3::new(0.0, 5.0, 0.0)), 1.0);
+        assert_eq!(shadow_map.test(&Vec3::new(0.0, 7.0, 0.0)), 1.0);
+
+        // Outside of the shadow map
+        assert_eq!(shadow_map.test(&Vec3::new(50.0, 0.0, 0.0)), 1.0);
+    }
+}
@@ -0,0 +1,101 @@
+// This file contains billboarded sprites, flat textured squares which always face the camera
+
+use crate::camera::Camera;
+use crate::colour::Colour;
+use crate::frame_buffer::{FrameBuffer, FrameBufferTrait};
+use crate::linear_algebra::{Vec2, Vec3};
+use crate::texture::Texture;
+
+// Draws a sprite centred on world_pos which is size world units wide and tall
+// The sprite lies in the plane facing the camera so it is never viewed at an angle
+// The texture is alpha blended over the frame buffer, sprites which are clipped by the near or far planes are not drawn
+pub fn render<T: FrameBufferTrait>(world_pos: &Vec3<f32>, size: Vec2<f32>, texture: &Texture, camera: &Camera, frame_buffer: &mut FrameBuffer<T>) {
+    let centre = world_pos.homogeneous_mult_matrix(&camera.transformation_matrix);
+
+    // Offset the corners in camera space so the sprite faces the camera
+    let corner0 = Vec3::new(centre.x - size.x / 2.0, centre.y - size.y / 2.0, centre.z);
+    let corner1 = Vec3::new(centre.x + size.x / 2.0, centre.y + size.y / 2.0, centre.z);
+    let (Ok(raster0), Ok(raster1)) = (camera.camera_to_raster(&corner0), camera.camera_to_raster(&corner1)) else {
+        return;
+    };
+
+    let (min_x, max_x) = (raster0.x.min(raster1.x), raster0.x.max(raster1.x));
+    let (min_y, max_y) = (raster0.y.min(raster1.y), raster0.y.max(raster1.y));
+    if max_x <= min_x || max_y <= min_y {
+        return;
+    }
+
+    // Only visit pixels whose centres are inside the sprite and the frame buffer
+    let start_x = (min_x - 0.5).ceil().max(0.0) as usize;
+    let start_y = (min_y - 0.5).ceil().max(0.0) as usize;
+    let end_x = ((max_x - 0.5).ceil().max(0.0) as usize).min(frame_buffer.width_px);
+    let end_y = ((max_y - 0.5).ceil().max(0.0) as usize).min(frame_buffer.height_px);
+
+    for y in start_y..end_y {
+        for x in start_x..end_x {
+            let u = (x as f32 + 0.5 - min_x) / (max_x - min_x);
+            let v = (y as f32 + 0.5 - min_y) / (max_y - min_y);
+            let source = texture.sample_nearest(u, v).to_colour();
+
+            let Ok(destination) = frame_buffer.read_buf(x, y) else {
+                continue;
+            };
+            let blended = source.multiply_float(source.alpha) + destination.multiply_float(1.0 - source.alpha);
+            let _ = frame_buffer.write_buf(x, y, &Colour {alpha: source.alpha + destination.alpha * (1.0 - source.alpha), ..blended});
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::FitResolutionGate;
+    use crate::colour::{Colour8, BLANK};
+    use crate::linear_algebra::Matrix44;
+
+    const SIZE: usize = 100;
+
+    // Camera 10 units back from the origin
+    fn camera() -> Camera {
+        let world_to_camera = Matrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 10.0, 1.0],
+        ]);
+
+        Camera::new(world_to_camera, Vec2::new(SIZE as i32, SIZE as i32), 35.0, Vec2::new(24.0, 24.0), 0.1, 100.0, FitResolutionGate::Fill)
+    }
+
+    #[test]
+    fn test_render_sprite_at_origin() {
+        let camera = camera();
+        let mut frame_buffer = FrameBuffer::new(SIZE, SIZE, vec![0u32; SIZE * SIZE]);
+        let colour = Colour8::new(255, 128, 0, 255);
+        let texture = Texture::solid(4, 4, colour);
+
+        render(&Vec3::new(0.0, 0.0, 0.0), Vec2::new(1.0, 1.0), &texture, &camera, &mut frame_buffer);
+
+        // The sprite is centred in the image
+        let pixel = |x: usize, y: usize| frame_buffer.read_buf(x, y).unwrap().to_colour8();
+        assert_eq!(pixel(50, 50), colour);
+        assert_eq!(pixel(40, 40), colour);
+        assert_eq!(pixel(60, 60), colour);
+
+        // One world unit is around 29 pixels at this distance
+        assert_eq!(pixel(50, 30), BLANK.to_colour8());
+        assert_eq!(pixel(30, 50), BLANK.to_colour8());
+        assert_eq!(pixel(70, 70), BLANK.to_colour8());
+    }
+
+    #[test]
+    fn test_render_sprite_behind_camera() {
+        let camera = camera();
+        let mut frame_buffer = FrameBuffer::new(SIZE, SIZE, vec![0u32; SIZE * SIZE]);
+        let texture = Texture::solid(4, 4, Colour8::new(255, 255, 255, 255));
+
+        render(&Vec3::new(0.0, 0.0, -20.0), Vec2::new(1.0, 1.0), &texture, &camera, &mut frame_buffer);
+
+        assert!(frame_buffer.buf.iter().all(|word| *word == 0));
+    }
+}
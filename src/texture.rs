@@ -0,0 +1,53 @@
+// This file contains the texture type which is sampled with texture coordinates
+
+use crate::colour::Colour8;
+
+// Grid of texels with its origin in the bottom left, like FrameBuffer
+// Texels are stored in rows from the bottom of the texture and indexed by x + y * width_px
+#[derive(Debug, PartialEq, Clone)]
+pub struct Texture {
+    pub width_px: usize,
+    pub height_px: usize,
+    pub texels: Vec<Colour8>,
+}
+
+impl Texture {
+    // Panics if there isn't one texel for every pixel of the texture
+    pub fn new(width_px: usize, height_px: usize, texels: Vec<Colour8>) -> Self {
+        assert_eq!(texels.len(), width_px * height_px, "Texture must have one texel per pixel");
+        Texture {width_px, height_px, texels}
+    }
+
+    // Makes a texture where every texel is the same colour
+    pub fn solid(width_px: usize, height_px: usize, colour: Colour8) -> Self {
+        Texture::new(width_px, height_px, vec![colour; width_px * height_px])
+    }
+
+    // Returns the texel nearest to the texture coordinates (u, v)
+    // (0, 0) is the bottom left of the texture and (1, 1) is the top right, coordinates outside this are clamped
+    pub fn sample_nearest(&self, u: f32, v: f32) -> Colour8 {
+        let x = ((u * self.width_px as f32) as usize).min(self.width_px - 1);
+        let y = ((v * self.height_px as f32) as usize).min(self.height_px - 1);
+        self.texels[x + y * self.width_px]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_nearest() {
+        let texels = vec![
+            Colour8::new(1, 0, 0, 255), Colour8::new(2, 0, 0, 255),
+            Colour8::new(3, 0, 0, 255), Colour8::new(4, 0, 0, 255),
+        ];
+        let texture = Texture::new(2, 2, texels);
+
+        assert_eq!(texture.sample_nearest(0.25, 0.25).red, 1);
+        assert_eq!(texture.sample_nearest(0.75, 0.25).red, 2);
+        assert_eq!(texture.sample_nearest(0.25, 0.75).red, 3);
+        assert_eq!(texture.sample_nearest(1.0, 1.0).red, 4);
+        assert_eq!(texture.sample_nearest(-1.0, -1.0).red, 1);
+    }
+}